@@ -3,30 +3,76 @@ extern crate itersynth;
 extern crate nom;
 extern crate sdl2;
 
-use itersynth::{Wave, WaveGen};
+use itersynth::{Breakpoint, Curve, EffectCategory, Grains, LfoShape,
+                LfsrMode, NesPulse, NesTriangle, PitchMode, SampleBuffer,
+                SfxrParams, Sn76489Tone, Vowel, Wave, WaveGen, note_to_freq};
 use std::str::{self, FromStr};
 use std::sync::{Arc, Condvar, Mutex, MutexGuard};
 
 // ========================================================================= //
 
+/// The sample rate at which waves used as granular sources are rendered.
+const BUFFER_SAMPLE_RATE: f32 = 44100.0;
+
+/// The maximum duration of a wave used as a granular source, in seconds.
+const MAX_BUFFER_DURATION: f32 = 60.0;
+
+// ========================================================================= //
+
 pub enum WaveOp {
     Add(Wave),
-    Adshr(f32, f32, f32, f32, f32),
+    Adshr(f32, f32, f32, f32, f32, (Curve, Curve, Curve)),
+    Bandpass(Wave, Wave),
+    Crop(f32, f32, f32),
     Delayed(f32),
+    Highpass(Wave),
     Looped,
+    Lowpass(Wave),
     Mul(Wave),
+    Phase(f32),
+    PhaseOffset(Wave),
+    Pitched(Wave, PitchMode),
     Repeated(i32),
+    Reversed,
+    RingMod(Wave),
+    Skip(f32),
+    Speed(Wave),
+    Sync(Wave),
+    Take(f32),
+    Then(Wave),
+    Transpose(Wave, PitchMode),
 }
 
 impl WaveOp {
     fn apply(self, wave: Wave) -> Wave {
         match self {
             WaveOp::Add(other) => wave + other,
-            WaveOp::Adshr(a, d, s, h, r) => wave.adshr(a, d, s, h, r),
+            WaveOp::Adshr(a, d, s, h, r, curves) => {
+                wave.adshr_curved(a, d, s, h, r, curves)
+            }
+            WaveOp::Bandpass(center, width) => wave.bandpass(center, width),
+            WaveOp::Crop(start, end, fade) => {
+                wave.crop_faded(start, end, fade)
+            }
             WaveOp::Delayed(time) => wave.delayed(time),
+            WaveOp::Highpass(cutoff) => wave.highpass(cutoff),
             WaveOp::Looped => wave.looped(),
+            WaveOp::Lowpass(cutoff) => wave.lowpass(cutoff),
             WaveOp::Mul(other) => wave * other,
+            WaveOp::Phase(phase) => wave.with_initial_phase(phase),
+            WaveOp::PhaseOffset(offset) => wave.with_phase_offset(offset),
+            WaveOp::Pitched(ratio, mode) => wave.pitched_with(ratio, mode),
             WaveOp::Repeated(num_times) => wave.repeated(num_times),
+            WaveOp::Reversed => wave.reversed(),
+            WaveOp::RingMod(other) => wave.ring_mod(other),
+            WaveOp::Skip(time) => wave.skip(time),
+            WaveOp::Speed(factor) => wave.speed(factor),
+            WaveOp::Sync(master) => wave.hard_sync(master),
+            WaveOp::Take(time) => wave.take(time),
+            WaveOp::Then(other) => wave.then(other),
+            WaveOp::Transpose(semitones, mode) => {
+                wave.transpose_with(semitones, mode)
+            }
         }
     }
 }
@@ -40,17 +86,241 @@ named!(any_wave<Wave>,
             }));
 
 named!(base_wave<Wave>,
-       alt!(const_wave | noise_wave | product_wave | pulse_wave | sine_wave |
-            slide_wave | sum_wave | triangle_wave));
+       alt!(additive_wave | clap_wave | colored_noise_wave | const_wave |
+            effect_wave | envelope_wave | formant_wave | granular_wave |
+            hihat_wave | kick_wave | lfo_wave | lfo_synced_wave |
+            lfsr_noise_wave | nes_noise_wave | nes_pulse_wave |
+            nes_triangle_wave | noise_wave | note_wave | pluck_wave |
+            product_wave | pulse_wave | saw_wave | saw_down_wave |
+            sequence_wave | sfxr_wave | sine_wave | sine_feedback_wave |
+            sine_pm_wave | slide_wave | snare_wave | sn_tone_wave | sum_wave |
+            supersaw_wave | tom_wave | tremolo_wave | triangle_wave |
+            velvet_noise_wave | vibrato_wave));
+
+named!(additive_wave<Wave>,
+       map!(preceded!(tag!("additive"),
+                      delimited!(char!('('),
+                                 separated_pair!(
+                                     any_wave,
+                                     char!(','),
+                                     separated_nonempty_list!(
+                                         char!(','),
+                                         separated_pair!(float_literal,
+                                                         char!(':'),
+                                                         any_wave))),
+                                 char!(')'))),
+            |(freq, partials)| Wave::additive(freq, partials)));
+
+named!(clap_wave<Wave>,
+       map!(preceded!(tag!("clap"),
+                      delimited!(char!('('),
+                                 separated_pair!(float_literal,
+                                                 char!(','),
+                                                 float_literal),
+                                 char!(')'))),
+            |(decay, tone)| Wave::clap(decay, tone)));
+
+named!(colored_noise_wave<Wave>,
+       alt!(value!(Wave::white_noise(), tag!("white()")) |
+            value!(Wave::gaussian_noise(), tag!("gaussian()")) |
+            value!(Wave::pink_noise(), tag!("pink()")) |
+            value!(Wave::brown_noise(), tag!("brown()")) |
+            value!(Wave::blue_noise(), tag!("blue()"))));
 
 named!(const_wave<Wave>, map!(float_literal, Into::into));
 
+named!(effect_wave<Wave>,
+       map!(preceded!(tag!("effect"),
+                      delimited!(char!('('),
+                                 separated_pair!(effect_category,
+                                                 char!(','),
+                                                 seed_literal),
+                                 char!(')'))),
+            |(category, seed)| Wave::random_effect(category, seed)));
+
+named!(envelope_wave<Wave>,
+       map_opt!(preceded!(tag!("env"),
+                          delimited!(char!('('),
+                                     separated_nonempty_list!(
+                                         pair!(char!(','),
+                                               opt!(nom::space)),
+                                         envelope_point),
+                                     char!(')'))),
+                make_envelope));
+
+// Parses one envelope point, such as `0.2:0.3 exp`.  The point may be
+// preceded by `[` or followed by `]` to mark the start or end of a sustain
+// loop.
+named!(envelope_point<(bool, Breakpoint, bool)>,
+       map!(tuple!(opt!(char!('[')),
+                   float_literal,
+                   char!(':'),
+                   float_literal,
+                   opt!(preceded!(opt!(nom::space), curve_literal)),
+                   opt!(char!(']'))),
+            |(open, time, _, value, curve, close): (Option<char>,
+                                                    f32,
+                                                    char,
+                                                    f32,
+                                                    Option<Curve>,
+                                                    Option<char>)| {
+                let curve = curve.unwrap_or(Curve::Linear);
+                (open.is_some(), (time, value, curve).into(), close.is_some())
+            }));
+
+fn make_envelope(points: Vec<(bool, Breakpoint, bool)>) -> Option<Wave> {
+    let loop_start = points.iter().position(|&(open, _, _)| open);
+    let loop_end = points.iter().position(|&(_, _, close)| close);
+    let points = points.into_iter().map(|(_, point, _)| point);
+    match (loop_start, loop_end) {
+        (None, None) => Some(Wave::envelope(points)),
+        (Some(start), Some(end)) if start <= end => {
+            Some(Wave::envelope_looped(points, start, end, 1.0))
+        }
+        _ => None,
+    }
+}
+
+named!(formant_wave<Wave>,
+       map!(preceded!(tag!("formant"),
+                      delimited!(char!('('),
+                                 separated_pair!(any_wave,
+                                                 char!(','),
+                                                 alt!(vowel_literal |
+                                                      any_wave)),
+                                 char!(')'))),
+            |(source, vowel)| Wave::formant(source, vowel)));
+
+named!(granular_wave<Wave>,
+       map!(preceded!(tag!("granular"),
+                      delimited!(char!('('),
+                                 separated_pair!(
+                                     separated_pair!(any_wave,
+                                                     char!(','),
+                                                     any_wave),
+                                     char!(','),
+                                     separated_pair!(any_wave,
+                                                     char!(','),
+                                                     any_wave)),
+                                 char!(')'))),
+            |((source, density), (size, position))| {
+                let buffer = SampleBuffer::render(source,
+                                                  BUFFER_SAMPLE_RATE,
+                                                  MAX_BUFFER_DURATION);
+                Wave::granular(buffer, Grains::new(density, size, position))
+            }));
+
+named!(hihat_wave<Wave>,
+       map!(preceded!(tag!("hihat"),
+                      delimited!(char!('('),
+                                 separated_pair!(float_literal,
+                                                 char!(','),
+                                                 float_literal),
+                                 char!(')'))),
+            |(decay, tone)| Wave::hihat(decay, tone)));
+
+named!(kick_wave<Wave>,
+       map!(preceded!(tag!("kick"),
+                      delimited!(char!('('),
+                                 separated_pair!(
+                                     separated_pair!(float_literal,
+                                                     char!(','),
+                                                     float_literal),
+                                     char!(','),
+                                     float_literal),
+                                 char!(')'))),
+            |((pitch, decay), click)| Wave::kick(pitch, decay, click)));
+
+named!(lfo_wave<Wave>,
+       map!(preceded!(tag!("lfo"),
+                      delimited!(char!('('),
+                                 separated_pair!(
+                                     separated_pair!(lfo_shape,
+                                                     char!(','),
+                                                     any_wave),
+                                     char!(','),
+                                     separated_pair!(float_literal,
+                                                     char!(','),
+                                                     float_literal)),
+                                 char!(')'))),
+            |((shape, rate), (min, max))| Wave::lfo(shape, rate, min, max)));
+
+named!(lfo_synced_wave<Wave>,
+       map!(preceded!(tag!("lfosync"),
+                      delimited!(char!('('),
+                                 separated_pair!(
+                                     separated_pair!(
+                                         lfo_shape,
+                                         char!(','),
+                                         separated_pair!(float_literal,
+                                                         char!(','),
+                                                         float_literal)),
+                                     char!(','),
+                                     separated_pair!(float_literal,
+                                                     char!(','),
+                                                     float_literal)),
+                                 char!(')'))),
+            |((shape, (bpm, beats)), (min, max))| {
+                Wave::lfo_synced(shape, bpm, beats, min, max)
+            }));
+
+named!(lfsr_noise_wave<Wave>,
+       map!(preceded!(tag!("lfsr"),
+                      delimited!(char!('('),
+                                 pair!(any_wave, lfsr_mode),
+                                 char!(')'))),
+            |(freq, mode)| Wave::lfsr_noise(freq, mode)));
+
+named!(nes_noise_wave<Wave>,
+       map!(preceded!(tag!("nesnoise"),
+                      delimited!(char!('('),
+                                 pair!(any_wave, short_flag),
+                                 char!(')'))),
+            |(index, short)| Wave::nes_noise(index, short)));
+
+named!(nes_pulse_wave<Wave>,
+       map!(preceded!(tag!("nespulse"),
+                      delimited!(char!('('),
+                                 separated_pair!(float_literal,
+                                                 char!(','),
+                                                 int_literal),
+                                 char!(')'))),
+            |(freq, duty)| {
+                Wave::nes_pulse(NesPulse::new(freq, (duty & 3) as u8))
+            }));
+
+named!(nes_triangle_wave<Wave>,
+       map!(preceded!(tag!("nestriangle"),
+                      delimited!(char!('('), float_literal, char!(')'))),
+            |freq| Wave::nes_triangle(NesTriangle::new(freq))));
+
 named!(noise_wave<Wave>,
        map!(preceded!(tag!("noise"),
                       delimited!(char!('('),
-                                 any_wave,
+                                 pair!(any_wave,
+                                       opt!(preceded!(char!(','),
+                                                      seed_literal))),
+                                 char!(')'))),
+            |(freq, seed)| match seed {
+                Some(seed) => Wave::noise_seeded(freq, seed),
+                None => Wave::noise(freq),
+            }));
+
+named!(note_wave<Wave>, map!(note_literal, Into::into));
+
+named!(pluck_wave<Wave>,
+       map!(preceded!(tag!("pluck"),
+                      delimited!(char!('('),
+                                 separated_pair!(
+                                     separated_pair!(float_literal,
+                                                     char!(','),
+                                                     float_literal),
+                                     char!(','),
+                                     float_literal),
                                  char!(')'))),
-            Wave::noise));
+            |((freq, decay), brightness)| {
+                Wave::pluck(freq, decay, brightness)
+            }));
 
 named!(product_wave<Wave>,
        map!(preceded!(tag!("mul"),
@@ -70,6 +340,37 @@ named!(pulse_wave<Wave>,
                                  char!(')'))),
             |(freq, duty)| Wave::pulse(freq, duty)));
 
+named!(saw_wave<Wave>,
+       map!(preceded!(tag!("saw"),
+                      delimited!(char!('('),
+                                 any_wave,
+                                 char!(')'))),
+            Wave::saw));
+
+named!(saw_down_wave<Wave>,
+       map!(preceded!(tag!("sawdown"),
+                      delimited!(char!('('),
+                                 any_wave,
+                                 char!(')'))),
+            Wave::saw_down));
+
+named!(sequence_wave<Wave>,
+       map!(preceded!(tag!("seq"),
+                      delimited!(char!('('),
+                                 separated_nonempty_list!(char!(','),
+                                                          any_wave),
+                                 char!(')'))),
+            Wave::sequence));
+
+named!(sfxr_wave<Wave>,
+       map!(map_res!(map_res!(preceded!(tag!("sfxr"),
+                                        delimited!(char!('('),
+                                                   call!(nom::alphanumeric),
+                                                   char!(')'))),
+                              str::from_utf8),
+                     SfxrParams::from_b58),
+            Wave::sfxr));
+
 named!(sine_wave<Wave>,
        map!(preceded!(tag!("sine"),
                       delimited!(char!('('),
@@ -77,6 +378,24 @@ named!(sine_wave<Wave>,
                                  char!(')'))),
             Wave::sine));
 
+named!(sine_feedback_wave<Wave>,
+       map!(preceded!(tag!("sinefb"),
+                      delimited!(char!('('),
+                                 separated_pair!(any_wave,
+                                                 char!(','),
+                                                 any_wave),
+                                 char!(')'))),
+            |(freq, feedback)| Wave::sine_feedback(freq, feedback)));
+
+named!(sine_pm_wave<Wave>,
+       map!(preceded!(tag!("sinepm"),
+                      delimited!(char!('('),
+                                 separated_pair!(any_wave,
+                                                 char!(','),
+                                                 any_wave),
+                                 char!(')'))),
+            |(freq, phase_mod)| Wave::sine_pm(freq, phase_mod)));
+
 named!(slide_wave<Wave>,
        map!(preceded!(tag!("slide"),
                       delimited!(char!('('),
@@ -98,6 +417,62 @@ named!(sum_wave<Wave>,
                                  char!(')'))),
             |(wave1, wave2)| wave1 + wave2));
 
+named!(snare_wave<Wave>,
+       map!(preceded!(tag!("snare"),
+                      delimited!(char!('('),
+                                 separated_pair!(
+                                     separated_pair!(float_literal,
+                                                     char!(','),
+                                                     float_literal),
+                                     char!(','),
+                                     float_literal),
+                                 char!(')'))),
+            |((pitch, decay), snappy)| Wave::snare(pitch, decay, snappy)));
+
+named!(sn_tone_wave<Wave>,
+       map!(preceded!(tag!("sntone"),
+                      delimited!(char!('('),
+                                 separated_pair!(float_literal,
+                                                 char!(','),
+                                                 int_literal),
+                                 char!(')'))),
+            |(freq, attenuation): (f32, i32)| {
+                let attenuation = attenuation.clamp(0, 15) as u8;
+                Wave::sn76489_tone(Sn76489Tone::new(freq, attenuation))
+            }));
+
+named!(supersaw_wave<Wave>,
+       map!(preceded!(tag!("supersaw"),
+                      delimited!(char!('('),
+                                 separated_pair!(
+                                     separated_pair!(any_wave,
+                                                     char!(','),
+                                                     int_literal),
+                                     char!(','),
+                                     float_literal),
+                                 char!(')'))),
+            |((freq, voices), detune): ((Wave, i32), f32)| {
+                Wave::supersaw(freq, voices.max(1) as usize, detune)
+            }));
+
+named!(tom_wave<Wave>,
+       map!(preceded!(tag!("tom"),
+                      delimited!(char!('('),
+                                 separated_pair!(float_literal,
+                                                 char!(','),
+                                                 float_literal),
+                                 char!(')'))),
+            |(pitch, decay)| Wave::tom(pitch, decay)));
+
+named!(tremolo_wave<Wave>,
+       map!(preceded!(tag!("tremolo"),
+                      delimited!(char!('('),
+                                 separated_pair!(any_wave,
+                                                 char!(','),
+                                                 float_literal),
+                                 char!(')'))),
+            |(rate, depth)| Wave::tremolo(rate, depth)));
+
 named!(triangle_wave<Wave>,
        map!(preceded!(tag!("triangle"),
                       delimited!(char!('('),
@@ -107,11 +482,34 @@ named!(triangle_wave<Wave>,
                                  char!(')'))),
             |(freq, duty)| Wave::triangle(freq, duty)));
 
+named!(velvet_noise_wave<Wave>,
+       map!(preceded!(tag!("velvet"),
+                      delimited!(char!('('),
+                                 any_wave,
+                                 char!(')'))),
+            Wave::velvet_noise));
+
+named!(vibrato_wave<Wave>,
+       map!(preceded!(tag!("vibrato"),
+                      delimited!(char!('('),
+                                 separated_pair!(
+                                     separated_pair!(any_wave,
+                                                     char!(','),
+                                                     any_wave),
+                                     char!(','),
+                                     float_literal),
+                                 char!(')'))),
+            |((freq, rate), depth)| Wave::vibrato(freq, rate, depth)));
+
 // ========================================================================= //
 
 named!(wave_suffix<WaveOp>,
-       alt!(add_suffix | adshr_suffix | delayed_suffix | looped_suffix |
-            mul_suffix | repeated_suffix));
+       alt!(add_suffix | adshr_suffix | bandpass_suffix | crop_suffix |
+            delayed_suffix | highpass_suffix | looped_suffix |
+            lowpass_suffix | mul_suffix | phase_offset_suffix |
+            phase_suffix | pitched_suffix | repeated_suffix |
+            reversed_suffix | ring_suffix | skip_suffix | speed_suffix |
+            sync_suffix | take_suffix | then_suffix | transpose_suffix));
 
 named!(add_suffix<WaveOp>,
        map!(preceded!(tag!(".add"),
@@ -124,18 +522,42 @@ named!(adshr_suffix<WaveOp>,
        map!(preceded!(tag!(".adshr"),
                       delimited!(char!('('),
                                  separated_pair!(
-                                     separated_pair!(float_literal,
+                                     separated_pair!(curved_float,
                                                      char!(','),
-                                                     float_literal),
+                                                     curved_float),
                                      char!(','),
                                      separated_pair!(
                                          separated_pair!(float_literal,
                                                          char!(','),
                                                          float_literal),
                                          char!(','),
-                                         float_literal)),
+                                         curved_float)),
+                                 char!(')'))),
+            |(((a, ac), (d, dc)), ((s, h), (r, rc)))| {
+                WaveOp::Adshr(a, d, s, h, r, (ac, dc, rc))
+            }));
+
+named!(bandpass_suffix<WaveOp>,
+       map!(preceded!(tag!(".bandpass"),
+                      delimited!(char!('('),
+                                 separated_pair!(any_wave,
+                                                 char!(','),
+                                                 any_wave),
                                  char!(')'))),
-            |((a, d), ((s, h), r))| WaveOp::Adshr(a, d, s, h, r)));
+            |(center, width)| WaveOp::Bandpass(center, width)));
+
+named!(crop_suffix<WaveOp>,
+       map!(preceded!(tag!(".crop"),
+                      delimited!(char!('('),
+                                 pair!(separated_pair!(float_literal,
+                                                       char!(','),
+                                                       float_literal),
+                                       opt!(preceded!(char!(','),
+                                                      float_literal))),
+                                 char!(')'))),
+            |((start, end), fade): ((f32, f32), Option<f32>)| {
+                WaveOp::Crop(start, end, fade.unwrap_or(0.0))
+            }));
 
 named!(delayed_suffix<WaveOp>,
        map!(preceded!(tag!(".delayed"),
@@ -144,9 +566,23 @@ named!(delayed_suffix<WaveOp>,
                                  char!(')'))),
             WaveOp::Delayed));
 
+named!(highpass_suffix<WaveOp>,
+       map!(preceded!(tag!(".highpass"),
+                      delimited!(char!('('),
+                                 any_wave,
+                                 char!(')'))),
+            WaveOp::Highpass));
+
 named!(looped_suffix<WaveOp>,
        value!(WaveOp::Looped, tag!(".looped()")));
 
+named!(lowpass_suffix<WaveOp>,
+       map!(preceded!(tag!(".lowpass"),
+                      delimited!(char!('('),
+                                 any_wave,
+                                 char!(')'))),
+            WaveOp::Lowpass));
+
 named!(mul_suffix<WaveOp>,
        map!(preceded!(tag!(".mul"),
                       delimited!(char!('('),
@@ -154,6 +590,27 @@ named!(mul_suffix<WaveOp>,
                                  char!(')'))),
             WaveOp::Mul));
 
+named!(phase_offset_suffix<WaveOp>,
+       map!(preceded!(tag!(".phaseoffset"),
+                      delimited!(char!('('),
+                                 any_wave,
+                                 char!(')'))),
+            WaveOp::PhaseOffset));
+
+named!(phase_suffix<WaveOp>,
+       map!(preceded!(tag!(".phase"),
+                      delimited!(char!('('),
+                                 float_literal,
+                                 char!(')'))),
+            WaveOp::Phase));
+
+named!(pitched_suffix<WaveOp>,
+       map!(preceded!(tag!(".pitched"),
+                      delimited!(char!('('),
+                                 pair!(any_wave, pitch_mode_flag),
+                                 char!(')'))),
+            |(ratio, mode)| WaveOp::Pitched(ratio, mode)));
+
 named!(repeated_suffix<WaveOp>,
        map!(preceded!(tag!(".repeated"),
                       delimited!(char!('('),
@@ -161,6 +618,58 @@ named!(repeated_suffix<WaveOp>,
                                  char!(')'))),
             WaveOp::Repeated));
 
+named!(reversed_suffix<WaveOp>,
+       value!(WaveOp::Reversed, tag!(".reversed()")));
+
+named!(ring_suffix<WaveOp>,
+       map!(preceded!(tag!(".ring"),
+                      delimited!(char!('('),
+                                 any_wave,
+                                 char!(')'))),
+            WaveOp::RingMod));
+
+named!(skip_suffix<WaveOp>,
+       map!(preceded!(tag!(".skip"),
+                      delimited!(char!('('),
+                                 float_literal,
+                                 char!(')'))),
+            WaveOp::Skip));
+
+named!(speed_suffix<WaveOp>,
+       map!(preceded!(tag!(".speed"),
+                      delimited!(char!('('),
+                                 any_wave,
+                                 char!(')'))),
+            WaveOp::Speed));
+
+named!(sync_suffix<WaveOp>,
+       map!(preceded!(tag!(".sync"),
+                      delimited!(char!('('),
+                                 any_wave,
+                                 char!(')'))),
+            WaveOp::Sync));
+
+named!(take_suffix<WaveOp>,
+       map!(preceded!(tag!(".take"),
+                      delimited!(char!('('),
+                                 float_literal,
+                                 char!(')'))),
+            WaveOp::Take));
+
+named!(then_suffix<WaveOp>,
+       map!(preceded!(tag!(".then"),
+                      delimited!(char!('('),
+                                 any_wave,
+                                 char!(')'))),
+            WaveOp::Then));
+
+named!(transpose_suffix<WaveOp>,
+       map!(preceded!(tag!(".transpose"),
+                      delimited!(char!('('),
+                                 pair!(any_wave, pitch_mode_flag),
+                                 char!(')'))),
+            |(semitones, mode)| WaveOp::Transpose(semitones, mode)));
+
 // ========================================================================= //
 
 named!(float_literal<f32>,
@@ -171,6 +680,82 @@ named!(float_literal<f32>,
                          str::from_utf8),
                 FromStr::from_str));
 
+named!(curve_literal<Curve>,
+       alt!(value!(Curve::Linear, tag!("lin")) |
+            value!(Curve::Exponential, tag!("exp")) |
+            value!(Curve::Logarithmic, tag!("log")) |
+            map!(preceded!(tag!("tension"),
+                           delimited!(char!('('),
+                                      float_literal,
+                                      char!(')'))),
+                 Curve::Tension)));
+
+named!(curved_float<(f32, Curve)>,
+       pair!(float_literal,
+             map!(opt!(preceded!(opt!(nom::space), curve_literal)),
+                  |curve: Option<Curve>| curve.unwrap_or(Curve::Linear))));
+
+// A note name, such as "A4", "C#3", or "Bb5", giving its frequency in
+// twelve-tone equal temperament.
+named!(note_literal<f32>,
+       map_res!(map_res!(recognize!(tuple!(one_of!("ABCDEFG"),
+                                           many0!(one_of!("#b")),
+                                           opt!(char!('-')),
+                                           call!(nom::digit))),
+                         str::from_utf8),
+                note_to_freq));
+
+// A vowel letter must be followed by the closing parenthesis, so that e.g.
+// "add" isn't mistaken for the vowel "a".
+named!(vowel_literal<Wave>,
+       map!(terminated!(alt!(value!(Vowel::A, char!('a')) |
+                             value!(Vowel::E, char!('e')) |
+                             value!(Vowel::I, char!('i')) |
+                             value!(Vowel::O, char!('o')) |
+                             value!(Vowel::U, char!('u'))),
+                        peek!(char!(')'))),
+            Wave::from));
+
+named!(effect_category<EffectCategory>,
+       alt!(value!(EffectCategory::Explosion, tag!("explosion")) |
+            value!(EffectCategory::Hit, tag!("hit")) |
+            value!(EffectCategory::Jump, tag!("jump")) |
+            value!(EffectCategory::Laser, tag!("laser")) |
+            value!(EffectCategory::Pickup, tag!("pickup"))));
+
+named!(lfo_shape<LfoShape>,
+       alt!(value!(LfoShape::Sine, tag!("sine")) |
+            value!(LfoShape::Triangle, tag!("triangle")) |
+            value!(LfoShape::Square, tag!("square")) |
+            value!(LfoShape::SawDown, tag!("sawdown")) |
+            value!(LfoShape::Saw, tag!("saw")) |
+            map!(preceded!(tag!("random"),
+                           opt!(delimited!(char!('('),
+                                           seed_literal,
+                                           char!(')')))),
+                 |seed: Option<u64>| {
+                     LfoShape::SampleAndHold(seed.unwrap_or(0))
+                 })));
+
+named!(lfsr_mode<LfsrMode>,
+       map!(opt!(alt!(value!(LfsrMode::NesShort, tag!(",short")) |
+                      value!(LfsrMode::GameBoyShort, tag!(",gbshort")))),
+            |mode: Option<LfsrMode>| mode.unwrap_or(LfsrMode::Long)));
+
+named!(pitch_mode_flag<PitchMode>,
+       map!(opt!(tag!(",formants")),
+            |flag: Option<&[u8]>| if flag.is_some() {
+                PitchMode::PreserveFormants
+            } else {
+                PitchMode::Resample
+            }));
+
+named!(short_flag<bool>,
+       map!(opt!(tag!(",short")), |flag: Option<&[u8]>| flag.is_some()));
+
+named!(seed_literal<u64>,
+       map_res!(map_res!(nom::digit, str::from_utf8), FromStr::from_str));
+
 named!(int_literal<i32>,
        map_res!(map_res!(recognize!(pair!(opt!(char!('-')), nom::digit)),
                          str::from_utf8),
@@ -0,0 +1,247 @@
+//! Buffers of pre-rendered or loaded sample data, and reversed playback.
+
+use std::sync::Arc;
+use super::{Sample, Wave, WaveGen};
+
+// ========================================================================= //
+
+/// A buffer of sample data, recorded at a fixed sample rate, for use as a
+/// source for e.g. [`Wave::granular`](struct.Wave.html#method.granular).
+///
+/// The samples may come from an audio file loaded by the caller, or be
+/// rendered from a `Wave` with `SampleBuffer::render`.  Cloning a
+/// `SampleBuffer` is cheap, so one buffer can be shared by many waveforms.
+#[derive(Clone)]
+pub struct SampleBuffer {
+    samples: Arc<Vec<Sample>>,
+    sample_rate: f32,
+}
+
+impl SampleBuffer {
+    /// Creates a new buffer from the given sample values, recorded at the
+    /// given sample rate (in hertz).
+    pub fn new<S: Into<Vec<Sample>>>(samples: S, sample_rate: f32)
+                                     -> SampleBuffer {
+        SampleBuffer {
+            samples: Arc::new(samples.into()),
+            sample_rate,
+        }
+    }
+
+    /// Renders the given waveform into a new buffer at the given sample rate
+    /// (in hertz), until either the waveform finishes or `max_duration`
+    /// seconds have been rendered, whichever comes first.
+    pub fn render(mut wave: Wave, sample_rate: f32, max_duration: f32)
+                  -> SampleBuffer {
+        let step = 1.0 / sample_rate;
+        let max_len = (max_duration.max(0.0) * sample_rate).ceil() as usize;
+        let mut samples = Vec::new();
+        while samples.len() < max_len {
+            match wave.next(step) {
+                Some(sample) => samples.push(sample),
+                None => break,
+            }
+        }
+        SampleBuffer::new(samples, sample_rate)
+    }
+
+    /// Returns the sample values in this buffer.
+    pub fn samples(&self) -> &[Sample] { &self.samples }
+
+    /// Returns the sample rate of this buffer, in hertz.
+    pub fn sample_rate(&self) -> f32 { self.sample_rate }
+
+    /// Returns the number of samples in this buffer.
+    pub fn len(&self) -> usize { self.samples.len() }
+
+    /// Returns true if this buffer has no samples.
+    pub fn is_empty(&self) -> bool { self.samples.is_empty() }
+
+    /// Returns the length of this buffer, in seconds.
+    pub fn duration(&self) -> f32 {
+        self.samples.len() as f32 / self.sample_rate
+    }
+
+    /// Returns the value of the buffer at the given time (in seconds),
+    /// interpolating linearly between samples.  The buffer is treated as
+    /// silent outside of its duration.
+    pub(crate) fn value_at(&self, time: f64) -> Sample {
+        let index = time * self.sample_rate as f64;
+        let floor = index.floor();
+        let fraction = (index - floor) as f32;
+        let first = self.sample(floor);
+        let second = self.sample(floor + 1.0);
+        first + fraction * (second - first)
+    }
+
+    fn sample(&self, index: f64) -> Sample {
+        if index >= 0.0 && index < self.samples.len() as f64 {
+            self.samples[index as usize]
+        } else {
+            0.0
+        }
+    }
+}
+
+impl<S: Into<Vec<Sample>>> From<(S, f32)> for SampleBuffer {
+    fn from((samples, sample_rate): (S, f32)) -> SampleBuffer {
+        SampleBuffer::new(samples, sample_rate)
+    }
+}
+
+// ========================================================================= //
+
+/// The maximum duration (in seconds) of a waveform rendered by
+/// `Wave::reversed`.
+pub const REVERSED_MAX_DURATION: f32 = 60.0;
+
+/// The maximum sample rate (in hertz) at which `Wave::reversed` renders a
+/// waveform; smaller steps interpolate between the rendered samples.
+const REVERSED_MAX_SAMPLE_RATE: f32 = 192000.0;
+
+impl Wave {
+    /// Plays this waveform backwards.  The first time the new waveform is
+    /// stepped forward, this waveform is rendered in full into a buffer (at
+    /// the sample rate given by that step, up to 192 kHz), which is then
+    /// played from its end back to its start; until then, the new waveform
+    /// is silent.  To keep infinite waveforms from rendering forever,
+    /// at most `REVERSED_MAX_DURATION` seconds are rendered; see
+    /// `reversed_capped` to choose a different limit.  Resetting the new
+    /// waveform restarts playback from the end of the buffer, without
+    /// rendering it again.
+    pub fn reversed(self) -> Wave {
+        self.reversed_capped(REVERSED_MAX_DURATION)
+    }
+
+    /// Like `reversed`, but renders at most `max_duration` seconds of this
+    /// waveform.  If this waveform lasts longer than that, then it is cut
+    /// off, and the reversed waveform starts from the cut.
+    pub fn reversed_capped(self, max_duration: f32) -> Wave {
+        Wave::new(Box::new(Reversed {
+            source: Some(self),
+            max_duration,
+            buffer: None,
+            elapsed: 0.0,
+        }))
+    }
+}
+
+// ========================================================================= //
+
+/// A waveform played backwards from a buffer.
+#[derive(Clone)]
+struct Reversed {
+    /// The waveform to reverse, until it has been rendered into the buffer.
+    source: Option<Wave>,
+    max_duration: f32,
+    buffer: Option<SampleBuffer>,
+    /// The time since the start of playback, in seconds.
+    elapsed: f64,
+}
+
+impl WaveGen for Reversed {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        if self.source.is_some() {
+            if step <= 0.0 {
+                // Wait for a step that gives a sample rate to render at.
+                return Some(0.0);
+            }
+            let sample_rate = (1.0 / step).min(REVERSED_MAX_SAMPLE_RATE);
+            self.buffer = Some(SampleBuffer::render(self.source
+                                                        .take()
+                                                        .unwrap(),
+                                                    sample_rate,
+                                                    self.max_duration));
+        }
+        let buffer = self.buffer.as_ref()?;
+        let rate = buffer.sample_rate() as f64;
+        // Play the last sample at time zero, and the first sample last.
+        let index = (buffer.len() as f64 - 1.0) - self.elapsed * rate;
+        if index < -1e-6 {
+            return None;
+        }
+        self.elapsed += step as f64;
+        Some(buffer.value_at(index.max(0.0) / rate))
+    }
+
+    fn reset(&mut self) {
+        // Once rendered, the buffer is kept, so that resetting is cheap.
+        self.elapsed = 0.0;
+        if let Some(ref mut source) = self.source {
+            source.reset();
+        }
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+}
+
+// ========================================================================= //
+
+#[cfg(test)]
+mod tests {
+    use super::{Reversed, SampleBuffer};
+    use super::super::{Wave, WaveGen, render};
+
+    #[test]
+    fn render_buffer() {
+        let buffer = SampleBuffer::render(Wave::slide(0.0, 1.0, 0.0), 4.0,
+                                          1.0);
+        assert_eq!(&[0.0, 0.25, 0.5, 0.75], buffer.samples());
+        assert_eq!(1.0, buffer.duration());
+        // Rendering stops early if the wave finishes.
+        let wave = Wave::from(1.0).take(0.5);
+        let buffer = SampleBuffer::render(wave, 4.0, 10.0);
+        assert_eq!(2, buffer.len());
+    }
+
+    #[test]
+    fn buffer_interpolation() {
+        let buffer = SampleBuffer::new(vec![1.0, 3.0], 2.0);
+        assert_eq!(1.0, buffer.value_at(0.0));
+        assert_eq!(2.0, buffer.value_at(0.25));
+        assert_eq!(3.0, buffer.value_at(0.5));
+        assert_eq!(1.5, buffer.value_at(0.75));
+        assert_eq!(0.0, buffer.value_at(1.0));
+        assert_eq!(0.5, buffer.value_at(-0.25));
+    }
+
+    #[test]
+    fn reversed() {
+        let wave = Wave::slide(0.0, 1.0, 0.0).take(1.0);
+        let mut reversed = wave.reversed();
+        let samples: Vec<f32> =
+            (0..4).map(|_| reversed.next(0.25).unwrap()).collect();
+        assert_eq!(vec![0.75, 0.5, 0.25, 0.0], samples);
+        assert_eq!(None, reversed.next(0.25));
+        // Resetting plays the buffer again, and later steps of a different
+        // size interpolate between the rendered samples.
+        reversed.reset();
+        assert_eq!(vec![0.75, 0.625, 0.5, 0.375, 0.25, 0.125, 0.0],
+                   render(reversed, 0.125, 100));
+        // Infinite waveforms are cut off.
+        let wave = Wave::slide(0.0, 1.0, 0.0).reversed_capped(2.0);
+        assert_eq!(vec![1.5, 1.0, 0.5, 0.0], render(wave, 0.5, 100));
+        let wave = Wave::from(1.0).reversed();
+        assert_eq!(6000, render(wave, 0.01, 10000).len());
+        // Rendering waits for a positive step, and tiny steps render at a
+        // bounded sample rate.
+        let mut wave = Wave::sine(100.0).reversed();
+        assert_eq!(Some(0.0), wave.next(0.0));
+        let mut wave = Wave::slide(0.0, 1.0, 0.0).take(1.0).reversed();
+        assert_eq!(Some(0.0), wave.next(0.0));
+        assert_eq!(Some(0.75), wave.next(0.25));
+        assert_eq!(Some(0.5), wave.next(0.25));
+        assert_eq!(Some(0.25), wave.next(0.0));
+        assert_eq!(Some(0.25), wave.next(0.25));
+        let mut reversed = Reversed {
+            source: Some(Wave::from(1.0)),
+            max_duration: 1.0,
+            buffer: None,
+            elapsed: 0.0,
+        };
+        assert_eq!(Some(1.0), reversed.next(1e-9));
+        assert_eq!(192000, reversed.buffer.unwrap().len());
+    }
+}
+
+// ========================================================================= //
@@ -0,0 +1,1342 @@
+//! Generators emulating the sound hardware of classic video game consoles.
+
+use std::sync::Arc;
+use super::{Sample, Wave, WaveGen};
+
+// ========================================================================= //
+
+/// The CPU clock rate of an NTSC NES, in hertz, which drives its audio
+/// hardware.
+pub const NES_CPU_CLOCK: f32 = 1789773.0;
+
+/// The table of noise channel periods of an NTSC NES, measured in CPU
+/// cycles per shift of the noise LFSR, indexed by the 4-bit period index.
+pub const NES_NOISE_PERIODS: [u16; 16] = [4, 8, 16, 32, 64, 96, 128, 160, 202,
+                                          254, 380, 508, 762, 1016, 2034,
+                                          4068];
+
+/// The rate at which the NES frame counter (in 4-step mode) clocks the
+/// envelopes and linear counter, in hertz; the length counters and sweep
+/// units are clocked at half this rate.
+const NES_QUARTER_FRAME_RATE: f32 = 240.0;
+
+/// The length counter values that can be loaded from an NES channel's
+/// 5-bit length index, measured in half-frames.
+const NES_LENGTH_TABLE: [u8; 32] = [10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60,
+                                    10, 14, 12, 26, 14, 12, 16, 24, 18, 48,
+                                    20, 96, 22, 192, 24, 72, 26, 16, 28, 32,
+                                    30];
+
+/// The waveforms for each of the NES pulse channel's duty settings.
+const NES_DUTY_SEQUENCES: [[bool; 8]; 4] = [
+    [false, true, false, false, false, false, false, false],
+    [false, true, true, false, false, false, false, false],
+    [false, true, true, true, true, false, false, false],
+    [true, false, false, true, true, true, true, true],
+];
+
+/// The DMC's bit rates for each rate index, measured in CPU cycles per bit.
+const NES_DMC_PERIODS: [u16; 16] = [428, 380, 340, 320, 286, 254, 226, 214,
+                                    190, 160, 142, 128, 106, 84, 72, 54];
+
+/// The master clock rate of the Game Boy, in hertz.
+const GB_CLOCK: f32 = 4194304.0;
+
+/// The rate at which the Game Boy's length counters are clocked, in hertz.
+const GB_LENGTH_RATE: f32 = 256.0;
+
+/// The clock rate of an SN76489 as used in the Sega Master System (and many
+/// other machines), in hertz.
+const SN76489_CLOCK: f32 = 3579545.0;
+
+/// The initial state of the noise LFSR, as on power-up of the NES.
+const LFSR_INIT_STATE: u16 = 1;
+
+/// The initial state of the SN76489's noise LFSR.
+const SN76489_LFSR_INIT_STATE: u16 = 1 << 14;
+
+/// The number of shifts after which the LFSR is guaranteed to have returned
+/// to the same state, in any mode (the long mode has a period of 32767
+/// shifts, the NES short mode has a period of 93 or 31 shifts, and the Game
+/// Boy short mode has a period of 127 shifts, all of which divide this
+/// number).
+const LFSR_CYCLE: u32 = 32767 * 3 * 127;
+
+// ========================================================================= //
+
+/// The feedback mode of an emulated 15-bit LFSR noise generator, for use
+/// with [`Wave::lfsr_noise`](struct.Wave.html#method.lfsr_noise).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LfsrMode {
+    /// The normal mode of both the NES and the Game Boy, in which the output
+    /// repeats only every 32767 shifts, giving a hissing sound.
+    Long,
+    /// The NES's short mode, in which the output repeats every 93 shifts,
+    /// giving a buzzing, metallic tone.
+    NesShort,
+    /// The Game Boy's 7-bit mode, in which the output repeats every 127
+    /// shifts, giving a buzzing tone a little lower than `NesShort`.
+    GameBoyShort,
+}
+
+// ========================================================================= //
+
+/// The settings for an emulated NES pulse channel, for use with
+/// [`Wave::nes_pulse`](struct.Wave.html#method.nes_pulse).
+///
+/// As on real hardware, the channel is muted whenever its period is less than
+/// 8, or whenever the sweep unit's target period (which is computed even if
+/// the sweep unit is disabled) is greater than $7FF.  In particular, with a
+/// sweep shift of 0 and negate off, any period of $400 or more is muted; for
+/// this reason, `NesPulse::new` sets the sweep unit's negate flag.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct NesPulse {
+    /// The 11-bit timer period; the frequency of the output is
+    /// `NES_CPU_CLOCK / (16 * (period + 1))`.
+    pub period: u16,
+    /// The duty cycle setting, from 0 to 3 (for duty cycles of 12.5%, 25%,
+    /// 50%, and 25% negated, respectively).
+    pub duty: u8,
+    /// The constant volume (if `constant_volume` is true), or else the
+    /// envelope's divider period, from 0 to 15.
+    pub volume: u8,
+    /// If true, the channel plays at a constant volume; if false, its volume
+    /// decays from 15 to 0 under control of the envelope.
+    pub constant_volume: bool,
+    /// If true, the envelope loops back to 15 after decaying to 0, and the
+    /// length counter is halted.
+    pub envelope_loop: bool,
+    /// The initial value of the length counter, measured in half-frames
+    /// (120ths of a second).  The wave finishes when the counter reaches 0
+    /// (unless the counter is halted).  If `None`, the wave never finishes.
+    pub length: Option<u8>,
+    /// The sweep unit settings.
+    pub sweep: NesSweep,
+    /// True for the NES's first pulse channel, whose sweep unit (when
+    /// negated) subtracts one more than the second channel's.
+    pub first_channel: bool,
+}
+
+impl NesPulse {
+    /// Creates settings for a pulse channel playing the given frequency (in
+    /// hertz), rounded to the nearest available period, with the given duty
+    /// setting (from 0 to 3), at constant full volume.
+    pub fn new(freq: f32, duty: u8) -> NesPulse {
+        let period = NES_CPU_CLOCK / (16.0 * freq) - 1.0;
+        NesPulse {
+            period: period.round().clamp(0.0, 2047.0) as u16,
+            duty,
+            volume: 15,
+            constant_volume: true,
+            sweep: NesSweep {
+                negate: true,
+                ..NesSweep::default()
+            },
+            ..NesPulse::default()
+        }
+    }
+
+    /// Creates settings for a pulse channel from the values written to its
+    /// four registers ($4000-$4003 for the first pulse channel, or
+    /// $4004-$4007 for the second).
+    pub fn from_registers(registers: [u8; 4], first_channel: bool)
+                          -> NesPulse {
+        let envelope_loop = registers[0] & 0x20 != 0;
+        NesPulse {
+            period: registers[2] as u16 | ((registers[3] as u16 & 7) << 8),
+            duty: registers[0] >> 6,
+            volume: registers[0] & 0xf,
+            constant_volume: registers[0] & 0x10 != 0,
+            envelope_loop,
+            length: if envelope_loop {
+                None
+            } else {
+                Some(NES_LENGTH_TABLE[(registers[3] >> 3) as usize])
+            },
+            sweep: NesSweep {
+                enabled: registers[1] & 0x80 != 0,
+                period: (registers[1] >> 4) & 7,
+                negate: registers[1] & 0x08 != 0,
+                shift: registers[1] & 7,
+            },
+            first_channel,
+        }
+    }
+}
+
+/// The settings for an NES pulse channel's sweep unit, which periodically
+/// adjusts the channel's period to bend its pitch up or down.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct NesSweep {
+    /// Whether the sweep unit adjusts the period.
+    pub enabled: bool,
+    /// The sweep unit's divider period, from 0 to 7; the period is adjusted
+    /// every `period + 1` half-frames.
+    pub period: u8,
+    /// If true, the period decreases (raising the pitch); otherwise it
+    /// increases (lowering the pitch).
+    pub negate: bool,
+    /// The shift count, from 0 to 7; each adjustment changes the period by
+    /// the period shifted right by this many bits.  A shift of 0 disables
+    /// adjustment.
+    pub shift: u8,
+}
+
+/// The settings for an emulated NES triangle channel, for use with
+/// [`Wave::nes_triangle`](struct.Wave.html#method.nes_triangle).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct NesTriangle {
+    /// The 11-bit timer period; the frequency of the output is
+    /// `NES_CPU_CLOCK / (32 * (period + 1))`.
+    pub period: u16,
+    /// The initial value of the linear counter, from 0 to 127, measured in
+    /// quarter-frames (240ths of a second).  The wave finishes when the
+    /// counter reaches 0.  If `None`, the counter is halted.
+    pub linear: Option<u8>,
+    /// The initial value of the length counter, measured in half-frames
+    /// (120ths of a second).  The wave finishes when the counter reaches 0.
+    /// If `None`, the counter is halted.
+    pub length: Option<u8>,
+}
+
+impl NesTriangle {
+    /// Creates settings for a triangle channel playing the given frequency
+    /// (in hertz), rounded to the nearest available period, indefinitely.
+    pub fn new(freq: f32) -> NesTriangle {
+        let period = NES_CPU_CLOCK / (32.0 * freq) - 1.0;
+        NesTriangle {
+            period: period.round().clamp(0.0, 2047.0) as u16,
+            linear: None,
+            length: None,
+        }
+    }
+
+    /// Creates settings for a triangle channel from the values written to
+    /// its three registers ($4008, $400A, and $400B).
+    pub fn from_registers(registers: [u8; 3]) -> NesTriangle {
+        let control = registers[0] & 0x80 != 0;
+        let reload = registers[0] & 0x7f;
+        NesTriangle {
+            period: registers[1] as u16 | ((registers[2] as u16 & 7) << 8),
+            // With the control flag set, the linear counter is reloaded on
+            // every clock, so it never counts down.
+            linear: if control && reload > 0 {
+                None
+            } else {
+                Some(reload)
+            },
+            length: if control {
+                None
+            } else {
+                Some(NES_LENGTH_TABLE[(registers[2] >> 3) as usize])
+            },
+        }
+    }
+}
+
+/// The settings for an emulated NES delta modulation channel (DMC), for use
+/// with [`Wave::nes_dmc`](struct.Wave.html#method.nes_dmc).
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct NesDmc {
+    /// The 1-bit delta-encoded sample data, read starting with the least
+    /// significant bit of each byte.  Each 1 bit raises the output level by
+    /// 2, and each 0 bit lowers it by 2.
+    pub data: Vec<u8>,
+    /// The rate index, from 0 to 15, which selects how many bits per second
+    /// are played (from about 4.2 kHz up to about 33.1 kHz).
+    pub rate: u8,
+    /// If true, the sample data loops forever; otherwise, the wave finishes
+    /// at the end of the data.
+    pub looped: bool,
+    /// The initial output level, from 0 to 127.
+    pub initial_level: u8,
+}
+
+impl NesDmc {
+    /// Delta-encodes the given samples (which should lie between -1 and 1,
+    /// and be sampled at the DMC's bit rate) into DMC sample data, assuming
+    /// that playback starts from the given initial level (from 0 to 127).
+    pub fn encode(samples: &[Sample], initial_level: u8) -> Vec<u8> {
+        let mut level = initial_level.min(127) as f32;
+        let mut data = vec![0u8; samples.len().div_ceil(8)];
+        for (index, &sample) in samples.iter().enumerate() {
+            let target = (sample + 1.0) * 63.5;
+            if target > level {
+                data[index / 8] |= 1 << (index % 8);
+                if level <= 125.0 {
+                    level += 2.0;
+                }
+            } else if level >= 2.0 {
+                level -= 2.0;
+            }
+        }
+        data
+    }
+}
+
+/// The settings for an emulated Game Boy wave channel, for use with
+/// [`Wave::gb_wave`](struct.Wave.html#method.gb_wave).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct GbWaveChannel {
+    /// The 32 4-bit samples (from 0 to 15) making up one cycle of the wave.
+    pub samples: [u8; 32],
+    /// The 11-bit period; the frequency of the output is
+    /// `65536 / (2048 - period)`.
+    pub period: u16,
+    /// The volume code, from 0 to 3 (for volumes of 0%, 100%, 50%, and 25%,
+    /// respectively).
+    pub volume: u8,
+    /// The initial value of the length counter, measured in 256ths of a
+    /// second.  The wave finishes when the counter reaches 0.  If `None`,
+    /// the wave never finishes.
+    pub length: Option<u16>,
+}
+
+impl GbWaveChannel {
+    /// Creates settings for a wave channel playing the given 32 4-bit
+    /// samples at the given frequency (in hertz), rounded to the nearest
+    /// available period, at full volume.
+    pub fn new(samples: [u8; 32], freq: f32) -> GbWaveChannel {
+        let period = 2048.0 - GB_CLOCK / (64.0 * freq);
+        GbWaveChannel {
+            samples,
+            period: period.round().clamp(0.0, 2047.0) as u16,
+            volume: 1,
+            length: None,
+        }
+    }
+
+    /// Creates settings for a wave channel from the values written to its
+    /// five registers (NR30-NR34) and to wave RAM.
+    pub fn from_registers(registers: [u8; 5], wave_ram: [u8; 16])
+                          -> GbWaveChannel {
+        let mut samples = [0; 32];
+        for (index, &byte) in wave_ram.iter().enumerate() {
+            samples[2 * index] = byte >> 4;
+            samples[2 * index + 1] = byte & 0xf;
+        }
+        let dac_enabled = registers[0] & 0x80 != 0;
+        GbWaveChannel {
+            samples,
+            period: registers[3] as u16 | ((registers[4] as u16 & 7) << 8),
+            volume: if dac_enabled { (registers[2] >> 5) & 3 } else { 0 },
+            length: if registers[4] & 0x40 != 0 {
+                Some(256 - registers[1] as u16)
+            } else {
+                None
+            },
+        }
+    }
+}
+
+/// The settings for an emulated SN76489 tone channel, for use with
+/// [`Wave::sn76489_tone`](struct.Wave.html#method.sn76489_tone).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Sn76489Tone {
+    /// The 10-bit period; the frequency of the output is `3579545 / (32 *
+    /// period)`.  A period of 0 acts like a period of 1024.
+    pub period: u16,
+    /// The attenuation, from 0 (loudest) to 15 (silent), in steps of 2 dB.
+    pub attenuation: u8,
+}
+
+impl Sn76489Tone {
+    /// Creates settings for a tone channel playing the given frequency (in
+    /// hertz), rounded to the nearest available period, with the given
+    /// attenuation (from 0 to 15).
+    pub fn new(freq: f32, attenuation: u8) -> Sn76489Tone {
+        let period = SN76489_CLOCK / (32.0 * freq);
+        Sn76489Tone {
+            period: period.round().clamp(1.0, 1023.0) as u16,
+            attenuation,
+        }
+    }
+}
+
+/// The settings for an emulated SN76489 noise channel, for use with
+/// [`Wave::sn76489_noise`](struct.Wave.html#method.sn76489_noise).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Sn76489Noise {
+    /// The shift rate setting, from 0 to 3.  Settings 0 to 2 shift the noise
+    /// register at 1/512, 1/1024, or 1/2048 of the clock rate; setting 3
+    /// shifts it at the frequency of the third tone channel, given by
+    /// `tone_period`.
+    pub rate: u8,
+    /// The period of the third tone channel, used when `rate` is 3.
+    pub tone_period: u16,
+    /// If true, the channel plays white noise; otherwise, it plays
+    /// "periodic" noise, a buzzy tone repeating every 15 shifts.
+    pub white: bool,
+    /// The attenuation, from 0 (loudest) to 15 (silent), in steps of 2 dB.
+    pub attenuation: u8,
+}
+
+// ========================================================================= //
+
+impl Wave {
+    /// Creates a noise wave, with an amplitude of 1, that emulates the 15-bit
+    /// linear-feedback shift register (LFSR) noise generator of the NES and
+    /// Game Boy, in the given feedback mode.  The `freq` waveform (which may
+    /// be a constant) controls how many times per second the register is
+    /// shifted.
+    pub fn lfsr_noise<F: Into<Wave>>(freq: F, mode: LfsrMode) -> Wave {
+        Wave::new(Box::new(LfsrNoise::new(freq.into(), false, mode)))
+    }
+
+    /// Like `lfsr_noise`, but with the shift rate controlled by the
+    /// `period_index` waveform (which may be a constant), as in the NES noise
+    /// channel.  The index is rounded to the nearest integer and clamped to
+    /// the range 0 to 15, and selects an entry from `NES_NOISE_PERIODS`; an
+    /// index of 0 gives the highest-pitched noise, and 15 the lowest.  If
+    /// `short` is true, the NES's short mode is used (see
+    /// `LfsrMode::NesShort`).
+    pub fn nes_noise<P: Into<Wave>>(period_index: P, short: bool) -> Wave {
+        let mode = if short {
+            LfsrMode::NesShort
+        } else {
+            LfsrMode::Long
+        };
+        Wave::new(Box::new(LfsrNoise::new(period_index.into(), true, mode)))
+    }
+
+    /// Creates a wave emulating one of the NES's two pulse channels: a pulse
+    /// wave with four duty settings, whose volume may be controlled by an
+    /// envelope, whose pitch may be bent by a sweep unit, and whose duration
+    /// may be limited by a length counter.  The output ranges from -1 to 1.
+    pub fn nes_pulse(pulse: NesPulse) -> Wave {
+        Wave::new(Box::new(NesPulseWave::new(pulse)))
+    }
+
+    /// Creates a wave emulating the NES's triangle channel: a triangle wave
+    /// stepped through 16 levels, whose duration may be limited by a linear
+    /// counter and a length counter.  The output ranges from -1 to 1.
+    pub fn nes_triangle(triangle: NesTriangle) -> Wave {
+        Wave::new(Box::new(NesTriangleWave::new(triangle)))
+    }
+
+    /// Creates a wave emulating the NES's delta modulation channel, which
+    /// plays back 1-bit delta-encoded sample data.  The output level, from 0
+    /// to 127, is scaled to range from -1 to 1.
+    pub fn nes_dmc(dmc: NesDmc) -> Wave {
+        Wave::new(Box::new(NesDmcWave::new(dmc)))
+    }
+
+    /// Creates a wave emulating the Game Boy's wave channel, which plays
+    /// back a cycle of 32 4-bit samples, with a coarse volume control, and
+    /// whose duration may be limited by a length counter.  The output ranges
+    /// from -1 to 1.
+    pub fn gb_wave(channel: GbWaveChannel) -> Wave {
+        Wave::new(Box::new(GbWaveWave::new(channel)))
+    }
+
+    /// Creates a wave emulating one of the SN76489's three tone channels: a
+    /// square wave with a logarithmic volume control.  The output ranges from
+    /// -1 to 1.
+    pub fn sn76489_tone(tone: Sn76489Tone) -> Wave {
+        Wave::new(Box::new(Sn76489ToneWave::new(tone)))
+    }
+
+    /// Creates a wave emulating the SN76489's noise channel, which plays
+    /// either white or periodic noise from a 15-bit LFSR, with a logarithmic
+    /// volume control.  The output ranges from -1 to 1.
+    pub fn sn76489_noise(noise: Sn76489Noise) -> Wave {
+        Wave::new(Box::new(Sn76489NoiseWave::new(noise)))
+    }
+}
+
+// ========================================================================= //
+
+/// A variable-rate 15-bit LFSR noise wave.
+#[derive(Clone)]
+struct LfsrNoise {
+    /// Either the shift rate, in hertz, or an index into
+    /// `NES_NOISE_PERIODS`.
+    rate: Wave,
+    period_index: bool,
+    mode: LfsrMode,
+    register: u16,
+    phase: f32,
+}
+
+impl LfsrNoise {
+    fn new(rate: Wave, period_index: bool, mode: LfsrMode) -> LfsrNoise {
+        LfsrNoise {
+            rate,
+            period_index,
+            mode,
+            register: LFSR_INIT_STATE,
+            phase: 0.0,
+        }
+    }
+
+    fn shift(&mut self) {
+        // The feedback bit is bit 0 XORed with either bit 1 or (in the NES
+        // short mode) bit 6, and is shifted in at the top of the register.
+        // In the Game Boy short mode, it is also written to bit 6, so that
+        // the bottom seven bits form a register of their own.
+        let tap = if self.mode == LfsrMode::NesShort { 6 } else { 1 };
+        let feedback = (self.register ^ (self.register >> tap)) & 1;
+        self.register = (self.register >> 1) | (feedback << 14);
+        if self.mode == LfsrMode::GameBoyShort {
+            self.register = (self.register & !(1 << 6)) | (feedback << 6);
+        }
+    }
+}
+
+impl WaveGen for LfsrNoise {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        let rate = self.rate.next(step)?;
+        let freq = if self.period_index {
+            let index = rate.round().clamp(0.0, 15.0) as usize;
+            NES_CPU_CLOCK / NES_NOISE_PERIODS[index] as f32
+        } else {
+            rate.max(0.0)
+        };
+        // The NES silences the channel whenever bit 0 is set.
+        let value = if self.register & 1 == 0 { 1.0 } else { -1.0 };
+        self.phase += freq * step;
+        let shifts = self.phase.floor();
+        self.phase -= shifts;
+        // Shifting a whole cycle's worth of times has no effect, so skip
+        // those to avoid spinning on absurdly high frequencies.
+        let shifts = (shifts as u64 % LFSR_CYCLE as u64) as u32;
+        for _ in 0..shifts {
+            self.shift();
+        }
+        Some(value)
+    }
+
+    fn reset(&mut self) {
+        self.rate.reset();
+        self.register = LFSR_INIT_STATE;
+        self.phase = 0.0;
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+}
+
+// ========================================================================= //
+
+/// Converts a fractional clock into a whole number of ticks per sample.
+#[derive(Clone, Default)]
+struct Ticker {
+    phase: f32,
+}
+
+impl Ticker {
+    /// Advances the clock by one sample at the given rate (in hertz),
+    /// returning the number of ticks that occurred.
+    fn advance(&mut self, rate: f32, step: f32) -> u32 {
+        self.phase += rate.max(0.0) * step;
+        let ticks = self.phase.floor();
+        self.phase -= ticks;
+        ticks as u32
+    }
+}
+
+/// An optional counter that counts down to zero, used for the length
+/// counters and the NES triangle's linear counter.
+#[derive(Clone)]
+struct Countdown {
+    remaining: Option<u16>,
+}
+
+impl Countdown {
+    fn new(initial: Option<u16>) -> Countdown {
+        Countdown { remaining: initial }
+    }
+
+    fn clock(&mut self) {
+        if let Some(ref mut remaining) = self.remaining {
+            *remaining = remaining.saturating_sub(1);
+        }
+    }
+
+    fn expired(&self) -> bool { self.remaining == Some(0) }
+}
+
+/// A channel's waveform sequencer, which steps through a fixed number of
+/// positions per cycle, starting from an initial phase and optionally offset
+/// by a phase offset waveform (both measured in cycles).
+#[derive(Clone)]
+struct Sequencer {
+    len: usize,
+    ticker: Ticker,
+    position: usize,
+    initial_phase: f32,
+    phase_offset: Option<Wave>,
+}
+
+impl Sequencer {
+    fn new(len: usize) -> Sequencer {
+        Sequencer {
+            len,
+            ticker: Ticker::default(),
+            position: 0,
+            initial_phase: 0.0,
+            phase_offset: None,
+        }
+    }
+
+    /// Returns the position to play for this sample (including any phase
+    /// offset), and then advances by one sample at the given rate (in
+    /// positions per second).  Returns `None` if the phase offset waveform
+    /// has finished.
+    fn advance(&mut self, rate: f32, step: f32) -> Option<usize> {
+        let position = match self.phase_offset {
+            Some(ref mut offset) => {
+                let len = self.len as f32;
+                let offset = offset.next(step)? * len;
+                let position = self.position as f32 + self.ticker.phase;
+                (position + offset).floor().rem_euclid(len) as usize %
+                self.len
+            }
+            None => self.position,
+        };
+        let ticks = self.ticker.advance(rate, step) as usize;
+        self.position = (self.position + ticks) % self.len;
+        Some(position)
+    }
+
+    fn set_initial_phase(&mut self, phase: f32) {
+        self.initial_phase = phase.rem_euclid(1.0);
+        self.restart();
+    }
+
+    /// Returns to the initial phase.
+    fn restart(&mut self) {
+        let steps = self.initial_phase * self.len as f32;
+        self.position = steps.floor() as usize % self.len;
+        self.ticker = Ticker { phase: steps - steps.floor() };
+    }
+
+    /// Returns a copy of this sequencer, reset back to the beginning.
+    fn restarted(&self) -> Sequencer {
+        let mut sequencer = self.clone();
+        sequencer.restart();
+        if let Some(ref mut offset) = sequencer.phase_offset {
+            offset.reset();
+        }
+        sequencer
+    }
+}
+
+// ========================================================================= //
+
+/// The NES's frame counter, which produces quarter- and half-frame clocks.
+#[derive(Clone, Default)]
+struct NesFrameCounter {
+    ticker: Ticker,
+    quarter_frames: u32,
+}
+
+impl NesFrameCounter {
+    /// Advances the frame counter by one sample, returning the number of
+    /// quarter frames and half frames that occurred.
+    fn advance(&mut self, step: f32) -> (u32, u32) {
+        let quarters = self.ticker.advance(NES_QUARTER_FRAME_RATE, step);
+        let halves = (self.quarter_frames + quarters) / 2 -
+                     self.quarter_frames / 2;
+        self.quarter_frames = (self.quarter_frames + quarters) % 2;
+        (quarters, halves)
+    }
+}
+
+/// An NES volume envelope.
+#[derive(Clone)]
+struct NesEnvelope {
+    volume: u8,
+    constant: bool,
+    looped: bool,
+    divider: u8,
+    decay: u8,
+}
+
+impl NesEnvelope {
+    fn new(volume: u8, constant: bool, looped: bool) -> NesEnvelope {
+        NesEnvelope {
+            volume: volume & 0xf,
+            constant,
+            looped,
+            divider: volume & 0xf,
+            decay: 15,
+        }
+    }
+
+    fn clock(&mut self) {
+        if self.divider == 0 {
+            self.divider = self.volume;
+            if self.decay > 0 {
+                self.decay -= 1;
+            } else if self.looped {
+                self.decay = 15;
+            }
+        } else {
+            self.divider -= 1;
+        }
+    }
+
+    fn level(&self) -> u8 {
+        if self.constant { self.volume } else { self.decay }
+    }
+}
+
+// ========================================================================= //
+
+/// An emulated NES pulse channel.
+#[derive(Clone)]
+struct NesPulseWave {
+    pulse: NesPulse,
+    period: u16,
+    sequencer: Sequencer,
+    frame_counter: NesFrameCounter,
+    envelope: NesEnvelope,
+    length: Countdown,
+    sweep_divider: u8,
+}
+
+impl NesPulseWave {
+    fn new(pulse: NesPulse) -> NesPulseWave {
+        let length = if pulse.envelope_loop { None } else { pulse.length };
+        NesPulseWave {
+            pulse,
+            period: pulse.period & 0x7ff,
+            sequencer: Sequencer::new(8),
+            frame_counter: NesFrameCounter::default(),
+            envelope: NesEnvelope::new(pulse.volume,
+                                       pulse.constant_volume,
+                                       pulse.envelope_loop),
+            length: Countdown::new(length.map(u16::from)),
+            sweep_divider: pulse.sweep.period & 7,
+        }
+    }
+
+    /// Returns the period that the sweep unit would change to.
+    fn sweep_target(&self) -> u16 {
+        let change = self.period >> (self.pulse.sweep.shift & 7);
+        if self.pulse.sweep.negate {
+            let extra = if self.pulse.first_channel { 1 } else { 0 };
+            self.period.saturating_sub(change + extra)
+        } else {
+            self.period + change
+        }
+    }
+
+    fn is_muted(&self) -> bool {
+        self.period < 8 || self.sweep_target() > 0x7ff
+    }
+
+    fn clock_sweep(&mut self) {
+        let sweep = self.pulse.sweep;
+        if self.sweep_divider == 0 {
+            self.sweep_divider = sweep.period & 7;
+            if sweep.enabled && sweep.shift & 7 > 0 && !self.is_muted() {
+                self.period = self.sweep_target();
+            }
+        } else {
+            self.sweep_divider -= 1;
+        }
+    }
+}
+
+impl WaveGen for NesPulseWave {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        if self.length.expired() {
+            return None;
+        }
+        // The sequencer advances once every `period + 1` APU cycles (which
+        // are half as fast as CPU cycles).
+        let rate = NES_CPU_CLOCK / (2.0 * (self.period + 1) as f32);
+        let position = self.sequencer.advance(rate, step)?;
+        let value = if self.is_muted() {
+            0.0
+        } else {
+            let level = self.envelope.level() as f32 / 15.0;
+            let sequence = NES_DUTY_SEQUENCES[(self.pulse.duty & 3) as usize];
+            if sequence[position] { level } else { -level }
+        };
+        let (quarters, halves) = self.frame_counter.advance(step);
+        for _ in 0..quarters {
+            self.envelope.clock();
+        }
+        for _ in 0..halves {
+            self.length.clock();
+            self.clock_sweep();
+        }
+        Some(value)
+    }
+
+    fn reset(&mut self) {
+        *self = NesPulseWave {
+            sequencer: self.sequencer.restarted(),
+            ..NesPulseWave::new(self.pulse)
+        };
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+
+    fn set_initial_phase(&mut self, phase: f32) {
+        self.sequencer.set_initial_phase(phase);
+    }
+
+    fn set_phase_offset(&mut self, offset: Wave) {
+        self.sequencer.phase_offset = Some(offset);
+    }
+}
+
+// ========================================================================= //
+
+/// An emulated NES triangle channel.
+#[derive(Clone)]
+struct NesTriangleWave {
+    triangle: NesTriangle,
+    sequencer: Sequencer,
+    frame_counter: NesFrameCounter,
+    linear: Countdown,
+    length: Countdown,
+}
+
+impl NesTriangleWave {
+    fn new(triangle: NesTriangle) -> NesTriangleWave {
+        NesTriangleWave {
+            triangle,
+            sequencer: Sequencer::new(32),
+            frame_counter: NesFrameCounter::default(),
+            linear: Countdown::new(triangle.linear
+                .map(|linear| (linear & 0x7f) as u16)),
+            length: Countdown::new(triangle.length.map(u16::from)),
+        }
+    }
+}
+
+impl WaveGen for NesTriangleWave {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        if self.linear.expired() || self.length.expired() {
+            return None;
+        }
+        // The sequencer advances once every `period + 1` CPU cycles.
+        let rate = NES_CPU_CLOCK / ((self.triangle.period & 0x7ff) + 1) as f32;
+        let position = self.sequencer.advance(rate, step)?;
+        // The 32-step sequence runs from 15 down to 0, then back up to 15.
+        let level = if position < 16 {
+            15 - position
+        } else {
+            position - 16
+        };
+        let value = (2.0 * level as f32 - 15.0) / 15.0;
+        let (quarters, halves) = self.frame_counter.advance(step);
+        for _ in 0..quarters {
+            self.linear.clock();
+        }
+        for _ in 0..halves {
+            self.length.clock();
+        }
+        Some(value)
+    }
+
+    fn reset(&mut self) {
+        *self = NesTriangleWave {
+            sequencer: self.sequencer.restarted(),
+            ..NesTriangleWave::new(self.triangle)
+        };
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+
+    fn set_initial_phase(&mut self, phase: f32) {
+        self.sequencer.set_initial_phase(phase);
+    }
+
+    fn set_phase_offset(&mut self, offset: Wave) {
+        self.sequencer.phase_offset = Some(offset);
+    }
+}
+
+// ========================================================================= //
+
+/// An emulated NES delta modulation channel.
+#[derive(Clone)]
+struct NesDmcWave {
+    data: Arc<Vec<u8>>,
+    rate: u8,
+    looped: bool,
+    initial_level: u8,
+    level: u8,
+    bit_index: usize,
+    ticker: Ticker,
+}
+
+impl NesDmcWave {
+    fn new(dmc: NesDmc) -> NesDmcWave {
+        let initial_level = dmc.initial_level.min(127);
+        NesDmcWave {
+            data: Arc::new(dmc.data),
+            rate: dmc.rate & 0xf,
+            looped: dmc.looped,
+            initial_level,
+            level: initial_level,
+            bit_index: 0,
+            ticker: Ticker::default(),
+        }
+    }
+}
+
+impl WaveGen for NesDmcWave {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        let num_bits = 8 * self.data.len();
+        if self.bit_index >= num_bits {
+            return None;
+        }
+        let value = self.level as f32 / 63.5 - 1.0;
+        let period = NES_DMC_PERIODS[self.rate as usize] as f32;
+        for _ in 0..self.ticker.advance(NES_CPU_CLOCK / period, step) {
+            let byte = self.data[self.bit_index / 8];
+            if byte & (1 << (self.bit_index % 8)) != 0 {
+                if self.level <= 125 {
+                    self.level += 2;
+                }
+            } else if self.level >= 2 {
+                self.level -= 2;
+            }
+            self.bit_index += 1;
+            if self.bit_index >= num_bits {
+                if !self.looped {
+                    break;
+                }
+                self.bit_index = 0;
+            }
+        }
+        Some(value)
+    }
+
+    fn reset(&mut self) {
+        self.level = self.initial_level;
+        self.bit_index = 0;
+        self.ticker = Ticker::default();
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+}
+
+// ========================================================================= //
+
+/// An emulated Game Boy wave channel.
+#[derive(Clone)]
+struct GbWaveWave {
+    channel: GbWaveChannel,
+    sequencer: Sequencer,
+    length_ticker: Ticker,
+    length: Countdown,
+}
+
+impl GbWaveWave {
+    fn new(channel: GbWaveChannel) -> GbWaveWave {
+        GbWaveWave {
+            channel,
+            sequencer: Sequencer::new(32),
+            length_ticker: Ticker::default(),
+            length: Countdown::new(channel.length
+                .map(|length| length.min(256))),
+        }
+    }
+}
+
+impl WaveGen for GbWaveWave {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        if self.length.expired() {
+            return None;
+        }
+        // The sequencer advances through 32 samples per cycle.
+        let period = 2048 - (self.channel.period & 0x7ff) as u32;
+        let rate = GB_CLOCK / (2 * period) as f32;
+        let position = self.sequencer.advance(rate, step)?;
+        // The volume code selects how far to shift the samples right.
+        let shift = [4, 0, 1, 2][(self.channel.volume & 3) as usize];
+        let sample = (self.channel.samples[position] & 0xf) >> shift;
+        let value = (2 * sample as i32 - (15 >> shift)) as f32 / 15.0;
+        for _ in 0..self.length_ticker.advance(GB_LENGTH_RATE, step) {
+            self.length.clock();
+        }
+        Some(value)
+    }
+
+    fn reset(&mut self) {
+        *self = GbWaveWave {
+            sequencer: self.sequencer.restarted(),
+            ..GbWaveWave::new(self.channel)
+        };
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+
+    fn set_initial_phase(&mut self, phase: f32) {
+        self.sequencer.set_initial_phase(phase);
+    }
+
+    fn set_phase_offset(&mut self, offset: Wave) {
+        self.sequencer.phase_offset = Some(offset);
+    }
+}
+
+// ========================================================================= //
+
+/// Returns the amplitude for an SN76489 attenuation setting.
+fn sn76489_amplitude(attenuation: u8) -> f32 {
+    let attenuation = attenuation & 0xf;
+    if attenuation == 15 {
+        0.0
+    } else {
+        10f32.powf(-0.1 * attenuation as f32)
+    }
+}
+
+/// Returns the frequency of an SN76489 tone channel with the given period.
+fn sn76489_tone_freq(period: u16) -> f32 {
+    let period = match period & 0x3ff {
+        0 => 1024,
+        period => period,
+    };
+    SN76489_CLOCK / (32 * period as u32) as f32
+}
+
+/// An emulated SN76489 tone channel.
+#[derive(Clone)]
+struct Sn76489ToneWave {
+    tone: Sn76489Tone,
+    /// The sequencer's two positions are the high and low halves of the
+    /// square wave.
+    sequencer: Sequencer,
+}
+
+impl Sn76489ToneWave {
+    fn new(tone: Sn76489Tone) -> Sn76489ToneWave {
+        Sn76489ToneWave {
+            tone,
+            sequencer: Sequencer::new(2),
+        }
+    }
+}
+
+impl WaveGen for Sn76489ToneWave {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        // The output flips twice per cycle.
+        let rate = 2.0 * sn76489_tone_freq(self.tone.period);
+        let amplitude = sn76489_amplitude(self.tone.attenuation);
+        if self.sequencer.advance(rate, step)? == 0 {
+            Some(amplitude)
+        } else {
+            Some(-amplitude)
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = Sn76489ToneWave {
+            sequencer: self.sequencer.restarted(),
+            ..Sn76489ToneWave::new(self.tone)
+        };
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+
+    fn set_initial_phase(&mut self, phase: f32) {
+        self.sequencer.set_initial_phase(phase);
+    }
+
+    fn set_phase_offset(&mut self, offset: Wave) {
+        self.sequencer.phase_offset = Some(offset);
+    }
+}
+
+/// An emulated SN76489 noise channel.
+#[derive(Clone)]
+struct Sn76489NoiseWave {
+    noise: Sn76489Noise,
+    ticker: Ticker,
+    register: u16,
+}
+
+impl Sn76489NoiseWave {
+    fn new(noise: Sn76489Noise) -> Sn76489NoiseWave {
+        Sn76489NoiseWave {
+            noise,
+            ticker: Ticker::default(),
+            register: SN76489_LFSR_INIT_STATE,
+        }
+    }
+
+    fn shift(&mut self) {
+        // White noise feeds back bit 0 XOR bit 1; periodic noise feeds back
+        // just bit 0, so the register simply rotates.
+        let feedback = if self.noise.white {
+            (self.register ^ (self.register >> 1)) & 1
+        } else {
+            self.register & 1
+        };
+        self.register = (self.register >> 1) | (feedback << 14);
+    }
+}
+
+impl WaveGen for Sn76489NoiseWave {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        let amplitude = sn76489_amplitude(self.noise.attenuation);
+        let value = if self.register & 1 != 0 {
+            amplitude
+        } else {
+            -amplitude
+        };
+        let rate = match self.noise.rate & 3 {
+            3 => sn76489_tone_freq(self.noise.tone_period),
+            rate => SN76489_CLOCK / (512 << rate) as f32,
+        };
+        // Skip whole cycles of the register (which always has a period of
+        // 15 or 32767 shifts, dividing 15 * 32767) to avoid spinning on
+        // absurdly high rates.
+        let shifts = self.ticker.advance(rate, step) % (15 * 32767);
+        for _ in 0..shifts {
+            self.shift();
+        }
+        Some(value)
+    }
+
+    fn reset(&mut self) { *self = Sn76489NoiseWave::new(self.noise); }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+}
+
+// ========================================================================= //
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Wave, WaveGen, render};
+    use super::{GbWaveChannel, LfsrMode, NES_CPU_CLOCK, NES_NOISE_PERIODS,
+                NesDmc, NesPulse, NesTriangle, Sn76489Noise, Sn76489Tone};
+
+    /// Returns the smallest period with which the samples repeat.
+    fn period(samples: &[f32]) -> usize {
+        (1..samples.len() / 2)
+            .find(|&period| {
+                samples[period..]
+                    .iter()
+                    .zip(samples.iter())
+                    .all(|(a, b)| a == b)
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn lfsr_noise_periods() {
+        let samples = render(Wave::lfsr_noise(1.0, LfsrMode::Long),
+                             1.0,
+                             70000);
+        assert!(samples.iter().all(|&s| s == 1.0 || s == -1.0));
+        assert_eq!(32767, period(&samples));
+        let samples = render(Wave::lfsr_noise(1.0, LfsrMode::NesShort),
+                             1.0,
+                             1000);
+        assert_eq!(93, period(&samples));
+        let mut wave = Wave::lfsr_noise(1.0, LfsrMode::GameBoyShort);
+        let samples = render(&mut wave, 1.0, 1000);
+        assert_eq!(127, period(&samples));
+    }
+
+    #[test]
+    fn lfsr_noise_rate() {
+        // Shifting every other sample just doubles up each output value.
+        let fast = render(Wave::lfsr_noise(1.0, LfsrMode::Long), 1.0, 100);
+        let slow = render(Wave::lfsr_noise(0.5, LfsrMode::Long), 1.0, 200);
+        for (index, &value) in fast.iter().enumerate() {
+            assert_eq!(value, slow[2 * index]);
+            assert_eq!(value, slow[2 * index + 1]);
+        }
+        // A very high frequency still produces output promptly.
+        let mut wave = Wave::lfsr_noise(1e12, LfsrMode::Long);
+        render(&mut wave, 1.0, 10);
+    }
+
+    #[test]
+    fn nes_noise() {
+        let step = 1.0 / 44100.0;
+        for (index, &period) in NES_NOISE_PERIODS.iter().enumerate() {
+            let freq = NES_CPU_CLOCK / period as f32;
+            let (mode, short) = if index % 2 == 0 {
+                (LfsrMode::NesShort, true)
+            } else {
+                (LfsrMode::Long, false)
+            };
+            let mut expected = Wave::lfsr_noise(freq, mode);
+            let mut wave = Wave::nes_noise(index as f32, short);
+            for _ in 0..1000 {
+                assert_eq!(expected.next(step), wave.next(step));
+            }
+        }
+        // Out-of-range indices are clamped.
+        let mut expected = Wave::nes_noise(15.0, false);
+        let mut wave = Wave::nes_noise(100.0, false);
+        for _ in 0..1000 {
+            assert_eq!(expected.next(step), wave.next(step));
+        }
+    }
+
+    #[test]
+    fn nes_pulse_duty() {
+        // Step once through the sequencer per sample.
+        let pulse = NesPulse::new(NES_CPU_CLOCK / 160.0, 2);
+        assert_eq!(9, pulse.period);
+        let step = 1.0001 * 20.0 / NES_CPU_CLOCK;
+        let samples = render(Wave::nes_pulse(pulse), step, 16);
+        let expected = [-1.0, 1.0, 1.0, 1.0, 1.0, -1.0, -1.0, -1.0];
+        assert_eq!(&expected[..], &samples[..8]);
+        assert_eq!(&expected[..], &samples[8..]);
+    }
+
+    #[test]
+    fn nes_pulse_envelope_and_length() {
+        // Step once through the frame counter per sample.
+        let step = 1.0001 / 240.0;
+        let pulse = NesPulse {
+            volume: 0,
+            constant_volume: false,
+            length: Some(10),
+            ..NesPulse::new(440.0, 2)
+        };
+        let samples = render(Wave::nes_pulse(pulse), step, 1000);
+        assert_eq!(20, samples.len());
+        for (index, sample) in samples.iter().enumerate() {
+            let level = 15usize.saturating_sub(index) as f32 / 15.0;
+            assert_approx!(level, sample.abs());
+        }
+        // If the envelope loops, the length counter is halted.
+        let pulse = NesPulse { envelope_loop: true, ..pulse };
+        let samples = render(Wave::nes_pulse(pulse), step, 1000);
+        assert_eq!(1000, samples.len());
+        assert_approx!(1.0, samples[16].abs());
+    }
+
+    #[test]
+    fn nes_pulse_sweep() {
+        let step = 1.0001 / 240.0;
+        // Without the negate flag, a zero sweep shift mutes low periods.
+        let pulse = NesPulse::from_registers([0xbf, 0x00, 0x00, 0x05], true);
+        assert_eq!(0x500, pulse.period);
+        assert_eq!(None, pulse.length);
+        let samples = render(Wave::nes_pulse(pulse), step, 100);
+        assert!(samples.iter().all(|&sample| sample == 0.0));
+        // Sweeping the pitch downwards eventually mutes the channel.
+        let pulse = NesPulse::from_registers([0xbf, 0x81, 0x00, 0x01], true);
+        assert!(pulse.sweep.enabled);
+        let samples = render(Wave::nes_pulse(pulse), step, 100);
+        assert!(samples[0] != 0.0);
+        assert!(samples[20..].iter().all(|&sample| sample == 0.0));
+    }
+
+    #[test]
+    fn nes_triangle() {
+        let step = 1.0001 / NES_CPU_CLOCK;
+        let triangle = NesTriangle { period: 0, ..NesTriangle::new(440.0) };
+        let samples = render(Wave::nes_triangle(triangle), step, 32);
+        for (index, &sample) in samples.iter().enumerate() {
+            let level = if index < 16 { 15 - index } else { index - 16 };
+            assert_approx!((2.0 * level as f32 - 15.0) / 15.0, sample);
+        }
+        // The linear counter stops the wave after 4 quarter-frames.
+        let triangle = NesTriangle::from_registers([0x04, 0x00, 0x08]);
+        assert_eq!(Some(4), triangle.linear);
+        let samples = render(Wave::nes_triangle(triangle),
+                                       1.0001 / 240.0,
+                                       100);
+        assert_eq!(4, samples.len());
+    }
+
+    #[test]
+    fn nes_dmc() {
+        let step = 1.0001 * 54.0 / NES_CPU_CLOCK;
+        let dmc = NesDmc {
+            data: vec![0xff, 0x00],
+            rate: 15,
+            looped: false,
+            initial_level: 64,
+        };
+        let samples = render(Wave::nes_dmc(dmc.clone()), step, 100);
+        assert_eq!(16, samples.len());
+        for (index, &sample) in samples.iter().enumerate() {
+            let level = if index <= 8 {
+                64 + 2 * index
+            } else {
+                96 - 2 * index
+            };
+            assert_approx!(level as f32 / 63.5 - 1.0, sample);
+        }
+        let dmc = NesDmc { looped: true, ..dmc };
+        let samples = render(Wave::nes_dmc(dmc), step, 100);
+        assert_eq!(100, samples.len());
+        assert_eq!(samples[..16], samples[16..32]);
+        // Encoding a rising ramp gives all 1 bits.
+        let ramp: Vec<f32> = (1..17).map(|n| n as f32 / 16.0).collect();
+        assert_eq!(vec![0xff, 0xff], NesDmc::encode(&ramp, 64));
+    }
+
+    #[test]
+    fn gb_wave() {
+        let mut samples = [0; 32];
+        for (index, sample) in samples.iter_mut().enumerate() {
+            *sample = index as u8 & 0xf;
+        }
+        let channel = GbWaveChannel {
+            period: 0,
+            volume: 2,
+            length: Some(2),
+            ..GbWaveChannel::new(samples, 440.0)
+        };
+        let output =
+            render(Wave::gb_wave(channel), 1.0 / 1024.0, 100);
+        assert_eq!(8, output.len());
+        for (index, &value) in output.iter().enumerate() {
+            let sample = (index as i32 & 0xf) >> 1;
+            assert_approx!((2 * sample - 7) as f32 / 15.0, value);
+        }
+        let mut wave_ram = [0; 16];
+        wave_ram[0] = 0x12;
+        let channel = GbWaveChannel::from_registers([0x80, 0xff, 0x60, 0x34,
+                                                     0xc2],
+                                                    wave_ram);
+        assert_eq!(1, channel.samples[0]);
+        assert_eq!(2, channel.samples[1]);
+        assert_eq!(0x234, channel.period);
+        assert_eq!(3, channel.volume);
+        assert_eq!(Some(1), channel.length);
+    }
+
+    #[test]
+    fn sn76489_tone() {
+        let step = 1.0001 * 16.0 / 3579545.0;
+        let tone = Sn76489Tone::new(3579545.0 / 32.0, 0);
+        assert_eq!(1, tone.period);
+        let samples = render(Wave::sn76489_tone(tone), step, 4);
+        assert_eq!(vec![1.0, -1.0, 1.0, -1.0], samples);
+        let tone = Sn76489Tone { attenuation: 5, ..tone };
+        let samples = render(Wave::sn76489_tone(tone), step, 1);
+        assert_approx!(10f32.powf(-0.5), samples[0]);
+        let tone = Sn76489Tone { attenuation: 15, ..tone };
+        let samples = render(Wave::sn76489_tone(tone), step, 1);
+        assert_eq!(0.0, samples[0].abs());
+    }
+
+    #[test]
+    fn sn76489_noise() {
+        let step = 1.0001 * 512.0 / 3579545.0;
+        let noise = Sn76489Noise {
+            rate: 0,
+            tone_period: 0,
+            white: false,
+            attenuation: 0,
+        };
+        let samples = render(Wave::sn76489_noise(noise), step, 100);
+        assert_eq!(15, period(&samples));
+        let noise = Sn76489Noise { white: true, ..noise };
+        let samples = render(Wave::sn76489_noise(noise), step, 1100);
+        assert!(samples[..1000] != samples[15..1015]);
+        assert!(samples.contains(&1.0) && samples.contains(&-1.0));
+    }
+}
+
+// ========================================================================= //
@@ -0,0 +1,234 @@
+//! Synthesized percussion: kick and snare drums, hi-hats, toms, and claps.
+
+use super::{Curve, Wave};
+
+// ========================================================================= //
+
+/// How many times higher than its final pitch a kick drum starts.
+const KICK_SWEEP_RATIO: f32 = 4.0;
+
+/// How long it takes a kick drum to sweep down to its final pitch, in
+/// seconds.
+const KICK_SWEEP_TIME: f32 = 0.06;
+
+/// How long a kick drum's click lasts, in seconds.
+const KICK_CLICK_TIME: f32 = 0.008;
+
+/// The cutoff frequency of the highpass filter on a kick drum's click.
+const KICK_CLICK_CUTOFF: f32 = 2000.0;
+
+/// The ratio between the frequencies of the two tones of a snare drum's
+/// shell.
+const SNARE_OVERTONE_RATIO: f32 = 1.6;
+
+/// The cutoff frequency of the highpass filter on a snare drum's noise.
+const SNARE_NOISE_CUTOFF: f32 = 1500.0;
+
+/// The frequencies (in hertz) of the square waves that make up a hi-hat's
+/// metallic tone, as used by the Roland TR-808.
+const HIHAT_FREQS: [f32; 6] = [205.3, 304.4, 369.6, 522.7, 540.0, 800.0];
+
+/// The cutoff frequency of the highpass filter on a hi-hat (before scaling
+/// by its tone).
+const HIHAT_CUTOFF: f32 = 7000.0;
+
+/// The decay times of closed and open hi-hats, in seconds.
+const CLOSED_HIHAT_DECAY: f32 = 0.05;
+const OPEN_HIHAT_DECAY: f32 = 0.4;
+
+/// How many times higher than its final pitch a tom starts.
+const TOM_SWEEP_RATIO: f32 = 1.5;
+
+/// The cutoff frequency of the lowpass filter on a tom's noise, as a
+/// multiple of its pitch.
+const TOM_NOISE_CUTOFF_RATIO: f32 = 4.0;
+
+/// The center frequency and bandwidth of the bandpass filter on a clap
+/// (before scaling by its tone).
+const CLAP_CENTER: f32 = 1200.0;
+const CLAP_BANDWIDTH: f32 = 1000.0;
+
+/// The gain applied to a clap's filtered noise, to make up for the energy
+/// removed by the filter.
+const CLAP_GAIN: f32 = 3.0;
+
+/// The time between the start of each of a clap's initial bursts, in
+/// seconds, and how many bursts there are before the tail.
+const CLAP_BURST_INTERVAL: f32 = 0.01;
+const CLAP_NUM_BURSTS: usize = 3;
+
+// ========================================================================= //
+
+impl Wave {
+    /// Creates a kick drum sound, whose pitch sweeps quickly down to `pitch`
+    /// (in hertz; typically 40 to 80) and which then dies away over `decay`
+    /// seconds.  The `click` level (from 0 to 1) adds a short burst of
+    /// high-frequency noise to the start, to help the kick cut through a
+    /// mix.
+    pub fn kick(pitch: f32, decay: f32, click: f32) -> Wave {
+        let sweep = sweep_down(pitch, KICK_SWEEP_RATIO, KICK_SWEEP_TIME);
+        let body = Wave::sine(sweep) * decay_envelope(1.0, decay);
+        let click = Wave::white_noise().highpass(KICK_CLICK_CUTOFF) *
+                    decay_envelope(click.clamp(0.0, 1.0), KICK_CLICK_TIME);
+        body + click
+    }
+
+    /// Creates a snare drum sound, mixing a pair of tones for the drum's
+    /// shell (the lower at `pitch` hertz, typically 150 to 250) with
+    /// highpass-filtered noise for the snares, and dying away over `decay`
+    /// seconds.  The `snappy` level (from 0 to 1) sets the balance between
+    /// the two, from all tone to all noise.
+    pub fn snare(pitch: f32, decay: f32, snappy: f32) -> Wave {
+        let snappy = snappy.clamp(0.0, 1.0);
+        let tone = Wave::sine(pitch) +
+                   Wave::sine(pitch * SNARE_OVERTONE_RATIO) * 0.5;
+        let tone = tone * decay_envelope(0.67 * (1.0 - snappy), 0.5 * decay);
+        let noise = Wave::white_noise().highpass(SNARE_NOISE_CUTOFF) *
+                    decay_envelope(snappy, decay);
+        tone + noise
+    }
+
+    /// Creates a hi-hat sound, made from a cluster of square waves at
+    /// inharmonic frequencies, passed through a highpass filter, and dying
+    /// away over `decay` seconds.  A `tone` of 1 gives the classic
+    /// drum-machine sound; larger values raise the pitch of the cluster and
+    /// the filter, and smaller values lower them.  See also `closed_hihat`
+    /// and `open_hihat`.
+    pub fn hihat(decay: f32, tone: f32) -> Wave {
+        let cluster = HIHAT_FREQS.iter()
+            .map(|&freq| Wave::pulse(freq * tone, 0.5))
+            .fold(Wave::from(0.0), |sum, square| sum + square);
+        cluster.highpass(HIHAT_CUTOFF * tone) *
+        decay_envelope(1.0 / HIHAT_FREQS.len() as f32, decay)
+    }
+
+    /// Creates a closed hi-hat sound, with the given tone (see `hihat`).
+    pub fn closed_hihat(tone: f32) -> Wave {
+        Wave::hihat(CLOSED_HIHAT_DECAY, tone)
+    }
+
+    /// Creates an open hi-hat sound, with the given tone (see `hihat`).
+    pub fn open_hihat(tone: f32) -> Wave {
+        Wave::hihat(OPEN_HIHAT_DECAY, tone)
+    }
+
+    /// Creates a tom sound, whose pitch drops slightly to `pitch` (in hertz;
+    /// typically 80 for a floor tom to 250 for a high tom) and which then
+    /// dies away over `decay` seconds, with a little lowpass-filtered noise
+    /// for the stick's attack.
+    pub fn tom(pitch: f32, decay: f32) -> Wave {
+        let sweep = sweep_down(pitch, TOM_SWEEP_RATIO, 0.25 * decay);
+        let body = Wave::sine(sweep) * decay_envelope(1.0, decay);
+        let cutoff = pitch * TOM_NOISE_CUTOFF_RATIO;
+        let noise = Wave::white_noise().lowpass(cutoff) *
+                    decay_envelope(0.3, 0.1 * decay);
+        body + noise
+    }
+
+    /// Creates a hand clap sound: several quick bursts of bandpass-filtered
+    /// noise, followed by a tail that dies away over `decay` seconds.  A
+    /// `tone` of 1 gives a typical clap; larger values raise the pitch of the
+    /// filter, and smaller values lower it.
+    pub fn clap(decay: f32, tone: f32) -> Wave {
+        let mut points = Vec::new();
+        for burst in 0..CLAP_NUM_BURSTS {
+            let start = burst as f32 * CLAP_BURST_INTERVAL;
+            points.push((start, 0.0, Curve::Linear));
+            points.push((start + 0.001, 1.0, Curve::Linear));
+            points.push((start + CLAP_BURST_INTERVAL, 0.2,
+                         Curve::Exponential));
+        }
+        let tail = CLAP_NUM_BURSTS as f32 * CLAP_BURST_INTERVAL;
+        points.push((tail + 0.001, 0.8, Curve::Linear));
+        points.push((tail + 0.001 + decay, 0.0, Curve::Exponential));
+        Wave::white_noise()
+            .bandpass(CLAP_CENTER * tone, CLAP_BANDWIDTH * tone) *
+        (Wave::envelope(points) * CLAP_GAIN)
+    }
+}
+
+// ========================================================================= //
+
+/// Returns a frequency waveform that sweeps exponentially down from `ratio`
+/// times `pitch` to `pitch` over `time` seconds, and then holds there.
+fn sweep_down(pitch: f32, ratio: f32, time: f32) -> Wave {
+    Wave::envelope(vec![(0.0, pitch * ratio, Curve::Linear),
+                        (time, pitch, Curve::Exponential)])
+        .then(pitch)
+}
+
+/// Returns an envelope that starts at `level` and decays exponentially to
+/// silence over `time` seconds, then finishes.
+fn decay_envelope(level: f32, time: f32) -> Wave {
+    Wave::envelope(vec![(0.0, level, Curve::Linear),
+                        (time, 0.0, Curve::Exponential)])
+}
+
+// ========================================================================= //
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Wave, WaveGen, render};
+
+    /// Renders the wave at 44.1 kHz, checking that it finishes within the
+    /// given time, and returns its peak amplitude.
+    fn finite_peak(mut wave: Wave, max_time: f32) -> f32 {
+        let step = 1.0 / 44100.0;
+        let mut peak = 0.0f32;
+        for _ in 0..(max_time / step) as usize {
+            match wave.next(step) {
+                Some(value) => peak = peak.max(value.abs()),
+                None => return peak,
+            }
+        }
+        panic!("wave did not finish within {} seconds", max_time);
+    }
+
+    #[test]
+    fn drums_finish() {
+        let drums = [(Wave::kick(50.0, 0.5, 0.5), 0.6),
+                     (Wave::snare(180.0, 0.2, 0.6), 0.3),
+                     (Wave::closed_hihat(1.0), 0.1),
+                     (Wave::open_hihat(1.0), 0.5),
+                     (Wave::tom(120.0, 0.3), 0.4),
+                     (Wave::clap(0.2, 1.0), 0.3)];
+        for (drum, max_time) in drums.iter().cloned() {
+            let peak = finite_peak(drum, max_time);
+            assert!(peak > 0.2 && peak < 1.5, "peak = {}", peak);
+        }
+    }
+
+    #[test]
+    fn kick_sweep() {
+        // Without a click, the kick starts at zero and rises quickly, since
+        // its pitch starts high.
+        let mut kick = Wave::kick(50.0, 0.5, 0.0);
+        let step = 1.0 / 1000.0;
+        assert_eq!(Some(0.0), kick.next(step));
+        assert!(kick.next(step).unwrap() > 0.5);
+        // It ends in silence, after the decay time.
+        let samples: Vec<f32> = (0..1000).map_while(|_| kick.next(step))
+            .collect();
+        assert_eq!(499, samples.len());
+        assert!(samples[489..].iter().all(|value| value.abs() < 0.01));
+    }
+
+    #[test]
+    fn snare_balance() {
+        // With no snappiness, the snare is pure tone, following its shell's
+        // two sine waves; with full snappiness, it is pure noise.
+        let step = 1.0 / 44100.0;
+        let tone = render(Wave::snare(200.0, 0.2, 0.0), step, 100);
+        let expected = render(Wave::sine(200.0) + Wave::sine(320.0) * 0.5,
+                              step,
+                              100);
+        assert_eq!(0.0, tone[0]);
+        assert!(tone.iter().zip(expected.iter()).all(|(a, b)| {
+            a.abs() <= b.abs() * 0.67 + 1e-6
+        }));
+        let noise = render(Wave::snare(200.0, 0.2, 1.0), step, 100);
+        assert!(noise.iter().any(|&value| value.abs() > 0.1));
+    }
+}
+
+// ========================================================================= //
@@ -0,0 +1,330 @@
+//! Randomized sound effects, in the style of sfxr's generator buttons.
+
+use super::{Rng, SfxrParams, SfxrWaveType, Wave};
+
+// ========================================================================= //
+
+/// A category of sound effect, for use with `Wave::random_effect`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EffectCategory {
+    /// A bright, short chime, for picking up coins or items.
+    Pickup,
+    /// A downward-sweeping zap, for shooting.
+    Laser,
+    /// A burst of rumbling noise.
+    Explosion,
+    /// A short upward-sweeping square wave, for jumping.
+    Jump,
+    /// A short, harsh downward sweep, for taking damage.
+    Hit,
+}
+
+// ========================================================================= //
+
+impl Wave {
+    /// Creates a random sound effect in the given category, using the given
+    /// seed.  The same category and seed always produce the same sound, while
+    /// different seeds give plausible variations on the category.  This is
+    /// equivalent to `Wave::sfxr(SfxrParams::random_effect(category,
+    /// seed))`; use that form to get at the parameters (e.g. to `mutate`
+    /// them, or to save them as a jsfxr hash).
+    pub fn random_effect(category: EffectCategory, seed: u64) -> Wave {
+        Wave::sfxr(SfxrParams::random_effect(category, seed))
+    }
+}
+
+impl SfxrParams {
+    /// Returns random sfxr parameters for a sound effect in the given
+    /// category, using the given seed.  These follow the recipes of the
+    /// generator buttons in DrPetter's original sfxr.
+    pub fn random_effect(category: EffectCategory, seed: u64) -> SfxrParams {
+        let mut rng = EffectRng(Rng::new(seed));
+        let mut p = SfxrParams::default();
+        match category {
+            EffectCategory::Pickup => {
+                p.base_freq = 0.4 + rng.frnd(0.5);
+                p.env_attack = 0.0;
+                p.env_sustain = rng.frnd(0.1);
+                p.env_decay = 0.1 + rng.frnd(0.4);
+                p.env_punch = 0.3 + rng.frnd(0.3);
+                if rng.chance() {
+                    p.arp_speed = 0.5 + rng.frnd(0.2);
+                    p.arp_mod = 0.2 + rng.frnd(0.4);
+                }
+            }
+            EffectCategory::Laser => {
+                p.wave_type = match rng.rnd(2) {
+                    0 => SfxrWaveType::Square,
+                    1 => SfxrWaveType::Sawtooth,
+                    _ if rng.chance() => {
+                        if rng.chance() {
+                            SfxrWaveType::Sawtooth
+                        } else {
+                            SfxrWaveType::Square
+                        }
+                    }
+                    _ => SfxrWaveType::Sine,
+                };
+                p.base_freq = 0.5 + rng.frnd(0.5);
+                p.freq_limit = (p.base_freq - 0.2 - rng.frnd(0.6)).max(0.2);
+                p.freq_ramp = -0.15 - rng.frnd(0.2);
+                if rng.rnd(2) == 0 {
+                    p.base_freq = 0.3 + rng.frnd(0.6);
+                    p.freq_limit = rng.frnd(0.1);
+                    p.freq_ramp = -0.35 - rng.frnd(0.3);
+                }
+                if rng.chance() {
+                    p.duty = rng.frnd(0.5);
+                    p.duty_ramp = rng.frnd(0.2);
+                } else {
+                    p.duty = 0.4 + rng.frnd(0.5);
+                    p.duty_ramp = -rng.frnd(0.7);
+                }
+                p.env_attack = 0.0;
+                p.env_sustain = 0.1 + rng.frnd(0.2);
+                p.env_decay = rng.frnd(0.4);
+                if rng.chance() {
+                    p.env_punch = rng.frnd(0.3);
+                }
+                if rng.rnd(2) == 0 {
+                    p.pha_offset = rng.frnd(0.2);
+                    p.pha_ramp = -rng.frnd(0.2);
+                }
+                if rng.chance() {
+                    p.hpf_freq = rng.frnd(0.3);
+                }
+            }
+            EffectCategory::Explosion => {
+                p.wave_type = SfxrWaveType::Noise;
+                if rng.chance() {
+                    p.base_freq = 0.1 + rng.frnd(0.4);
+                    p.freq_ramp = -0.1 + rng.frnd(0.4);
+                } else {
+                    p.base_freq = 0.2 + rng.frnd(0.7);
+                    p.freq_ramp = -0.2 - rng.frnd(0.2);
+                }
+                p.base_freq *= p.base_freq;
+                if rng.rnd(4) == 0 {
+                    p.freq_ramp = 0.0;
+                }
+                if rng.rnd(2) == 0 {
+                    p.repeat_speed = 0.3 + rng.frnd(0.5);
+                }
+                p.env_attack = 0.0;
+                p.env_sustain = 0.1 + rng.frnd(0.3);
+                p.env_decay = rng.frnd(0.5);
+                if rng.chance() {
+                    p.pha_offset = -0.3 + rng.frnd(0.9);
+                    p.pha_ramp = -rng.frnd(0.3);
+                }
+                p.env_punch = 0.2 + rng.frnd(0.6);
+                if rng.chance() {
+                    p.vib_strength = rng.frnd(0.7);
+                    p.vib_speed = rng.frnd(0.6);
+                }
+                if rng.rnd(2) == 0 {
+                    p.arp_speed = 0.6 + rng.frnd(0.3);
+                    p.arp_mod = 0.8 - rng.frnd(1.6);
+                }
+            }
+            EffectCategory::Jump => {
+                p.wave_type = SfxrWaveType::Square;
+                p.duty = rng.frnd(0.6);
+                p.base_freq = 0.3 + rng.frnd(0.3);
+                p.freq_ramp = 0.1 + rng.frnd(0.2);
+                p.env_attack = 0.0;
+                p.env_sustain = 0.1 + rng.frnd(0.3);
+                p.env_decay = 0.1 + rng.frnd(0.2);
+                if rng.chance() {
+                    p.hpf_freq = rng.frnd(0.3);
+                }
+                if rng.chance() {
+                    p.lpf_freq = 1.0 - rng.frnd(0.6);
+                }
+            }
+            EffectCategory::Hit => {
+                p.wave_type = match rng.rnd(2) {
+                    0 => SfxrWaveType::Square,
+                    1 => SfxrWaveType::Sawtooth,
+                    _ => SfxrWaveType::Noise,
+                };
+                if p.wave_type == SfxrWaveType::Square {
+                    p.duty = rng.frnd(0.6);
+                }
+                p.base_freq = 0.2 + rng.frnd(0.6);
+                p.freq_ramp = -0.3 - rng.frnd(0.4);
+                p.env_attack = 0.0;
+                p.env_sustain = rng.frnd(0.1);
+                p.env_decay = 0.1 + rng.frnd(0.2);
+                if rng.chance() {
+                    p.hpf_freq = rng.frnd(0.3);
+                }
+            }
+        }
+        p
+    }
+
+    /// Returns a close variation of these parameters, using the given seed:
+    /// as with sfxr's mutate button, about half of the parameters (other
+    /// than the wave type, frequency limit, and volume) are nudged by up to
+    /// 0.05 in either direction, and then kept within their valid ranges.
+    /// Repeatedly mutating with different seeds lets a designer audition
+    /// many variants of a sound.
+    pub fn mutate(&self, seed: u64) -> SfxrParams {
+        let mut rng = EffectRng(Rng::new(seed));
+        let mut params = self.clone();
+        {
+            let unsigned = [&mut params.base_freq,
+                            &mut params.duty,
+                            &mut params.vib_strength,
+                            &mut params.vib_speed,
+                            &mut params.env_attack,
+                            &mut params.env_sustain,
+                            &mut params.env_decay,
+                            &mut params.env_punch,
+                            &mut params.lpf_resonance,
+                            &mut params.lpf_freq,
+                            &mut params.hpf_freq,
+                            &mut params.repeat_speed,
+                            &mut params.arp_speed];
+            for param in unsigned {
+                *param = rng.nudge(*param).clamp(0.0, 1.0);
+            }
+            let signed = [&mut params.freq_ramp,
+                          &mut params.freq_dramp,
+                          &mut params.duty_ramp,
+                          &mut params.lpf_ramp,
+                          &mut params.hpf_ramp,
+                          &mut params.pha_offset,
+                          &mut params.pha_ramp,
+                          &mut params.arp_mod];
+            for param in signed {
+                *param = rng.nudge(*param).clamp(-1.0, 1.0);
+            }
+        }
+        params
+    }
+}
+
+// ========================================================================= //
+
+/// Wraps an `Rng` with the random functions used by sfxr's generators.
+struct EffectRng(Rng);
+
+impl EffectRng {
+    /// Returns a random value in the range [0, `range`).
+    fn frnd(&mut self, range: f32) -> f32 { self.0.next_range(0.0, range) }
+
+    /// Returns a random integer from 0 to `max` inclusive.
+    fn rnd(&mut self, max: u32) -> u32 {
+        (self.0.next_u64() >> 32) as u32 % (max + 1)
+    }
+
+    /// Returns true half of the time.
+    fn chance(&mut self) -> bool { self.rnd(1) == 1 }
+
+    /// Returns the value, nudged by up to 0.05 in either direction half of
+    /// the time.
+    fn nudge(&mut self, value: f32) -> f32 {
+        if self.chance() {
+            value + self.frnd(0.1) - 0.05
+        } else {
+            value
+        }
+    }
+}
+
+// ========================================================================= //
+
+#[cfg(test)]
+mod tests {
+    use super::EffectCategory;
+    use super::super::{SfxrParams, SfxrWaveType, Wave, WaveGen};
+
+    const CATEGORIES: [EffectCategory; 5] = [EffectCategory::Pickup,
+                                             EffectCategory::Laser,
+                                             EffectCategory::Explosion,
+                                             EffectCategory::Jump,
+                                             EffectCategory::Hit];
+
+    /// Renders the wave at 44.1 kHz (for at most ten seconds), returning its
+    /// length in samples and its peak amplitude.
+    fn measure(mut wave: Wave) -> (usize, f32) {
+        let mut peak = 0.0f32;
+        for len in 0..441000 {
+            match wave.next(1.0 / 44100.0) {
+                Some(value) => peak = peak.max(value.abs()),
+                None => return (len, peak),
+            }
+        }
+        panic!("sound did not finish");
+    }
+
+    #[test]
+    fn random_effects() {
+        for &category in CATEGORIES.iter() {
+            for seed in 0..10 {
+                let params = SfxrParams::random_effect(category, seed);
+                assert_eq!(params,
+                           SfxrParams::random_effect(category, seed));
+                assert!(params != SfxrParams::random_effect(category,
+                                                            seed + 100));
+                let (len, peak) = measure(Wave::random_effect(category,
+                                                              seed));
+                assert!(len > 100, "{:?} {}: len = {}", category, seed, len);
+                assert!(peak > 0.01,
+                        "{:?} {}: peak = {}",
+                        category,
+                        seed,
+                        peak);
+            }
+        }
+    }
+
+    #[test]
+    fn effect_categories() {
+        for seed in 0..20 {
+            let params = SfxrParams::random_effect(EffectCategory::Explosion,
+                                                   seed);
+            assert_eq!(SfxrWaveType::Noise, params.wave_type);
+            let params = SfxrParams::random_effect(EffectCategory::Jump,
+                                                   seed);
+            assert_eq!(SfxrWaveType::Square, params.wave_type);
+            assert!(params.freq_ramp > 0.0);
+            let params = SfxrParams::random_effect(EffectCategory::Laser,
+                                                   seed);
+            assert!(params.freq_ramp < 0.0);
+            let params = SfxrParams::random_effect(EffectCategory::Hit,
+                                                   seed);
+            assert!(params.wave_type != SfxrWaveType::Sine);
+        }
+    }
+
+    #[test]
+    fn mutate() {
+        let params = SfxrParams::random_effect(EffectCategory::Laser, 1);
+        let mutated = params.mutate(7);
+        assert_eq!(mutated, params.mutate(7));
+        assert!(mutated != params);
+        assert!(mutated != params.mutate(8));
+        assert_eq!(params.wave_type, mutated.wave_type);
+        assert_eq!(params.freq_limit, mutated.freq_limit);
+        assert!((mutated.base_freq - params.base_freq).abs() <= 0.05);
+        assert!((mutated.freq_ramp - params.freq_ramp).abs() <= 0.05);
+        // Parameters stay within their valid ranges.
+        let extreme = SfxrParams {
+            env_attack: 0.0,
+            lpf_freq: 1.0,
+            arp_mod: -1.0,
+            ..SfxrParams::default()
+        };
+        for seed in 0..20 {
+            let mutated = extreme.mutate(seed);
+            assert!(mutated.env_attack >= 0.0);
+            assert!(mutated.lpf_freq <= 1.0);
+            assert!(mutated.arp_mod >= -1.0);
+        }
+    }
+}
+
+// ========================================================================= //
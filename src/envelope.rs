@@ -0,0 +1,622 @@
+//! Envelope curves, breakpoint envelopes, and envelopes whose progress is
+//! controlled by a gate signal.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::f32;
+use super::{Sample, Wave, WaveGen};
+
+// ========================================================================= //
+
+/// The tension used for `Curve::Exponential` and `Curve::Logarithmic`; this
+/// makes a segment cover a 60 dB range with a constant rate in decibels.
+const EXP_CURVE_TENSION: f32 = 6.907755; // ln(1000)
+
+/// The shape of one segment of an envelope, as it moves from its starting
+/// value to its ending value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Curve {
+    /// A straight line.
+    Linear,
+    /// An exponential curve, which moves slowly at first and then speeds up
+    /// when rising, and moves quickly at first and then slows down when
+    /// falling (i.e. it approaches zero the way a natural decay does).
+    Exponential,
+    /// The mirror image of `Exponential`: it moves quickly at first when
+    /// rising, and slowly at first when falling.
+    Logarithmic,
+    /// A curve with the given tension, regardless of direction.  Positive
+    /// tension makes the segment start slowly and finish quickly; negative
+    /// tension makes it start quickly and finish slowly; zero tension is
+    /// linear.
+    Tension(f32),
+}
+
+impl Curve {
+    /// Returns the value of a segment with this curve that goes from `start`
+    /// to `end`, at the given `progress` through the segment (from 0 to 1).
+    pub fn interpolate(self, start: f32, end: f32, progress: f32) -> f32 {
+        let rising = end.abs() >= start.abs();
+        let tension = match self {
+            Curve::Linear => 0.0,
+            Curve::Exponential if rising => EXP_CURVE_TENSION,
+            Curve::Exponential => -EXP_CURVE_TENSION,
+            Curve::Logarithmic if rising => -EXP_CURVE_TENSION,
+            Curve::Logarithmic => EXP_CURVE_TENSION,
+            Curve::Tension(tension) => tension,
+        };
+        let progress = progress.clamp(0.0, 1.0);
+        let shaped = if tension.abs() < 1e-3 {
+            progress
+        } else {
+            (1.0 - (tension * progress).exp()) / (1.0 - tension.exp())
+        };
+        start + (end - start) * shaped
+    }
+}
+
+// ========================================================================= //
+
+/// One point in a breakpoint envelope.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Breakpoint {
+    /// The time of this point, in seconds since the start of the envelope.
+    pub time: f32,
+    /// The value of the envelope at this point.
+    pub value: f32,
+    /// The shape of the segment leading from the previous point to this one.
+    /// This is ignored for the first point.
+    pub curve: Curve,
+}
+
+impl From<(f32, f32)> for Breakpoint {
+    fn from((time, value): (f32, f32)) -> Breakpoint {
+        Breakpoint {
+            time,
+            value,
+            curve: Curve::Linear,
+        }
+    }
+}
+
+impl From<(f32, f32, Curve)> for Breakpoint {
+    fn from((time, value, curve): (f32, f32, Curve)) -> Breakpoint {
+        Breakpoint { time, value, curve }
+    }
+}
+
+// ========================================================================= //
+
+/// A shared handle for opening and closing a gate, such as a key on an
+/// instrument being pressed and released.
+///
+/// A `Gate` can be converted into a `Wave` (which is 1 while the gate is open
+/// and 0 while it is closed) and used to drive a gated envelope, while
+/// another clone of the `Gate` stays behind to control it (possibly from
+/// another thread).
+#[derive(Clone)]
+pub struct Gate {
+    // Incremented each time the gate opens or closes, so that the gate is
+    // open exactly when this is odd.
+    transitions: Arc<AtomicUsize>,
+}
+
+impl Gate {
+    /// Creates a new gate, initially closed.
+    pub fn new() -> Gate {
+        Gate { transitions: Arc::new(AtomicUsize::new(0)) }
+    }
+
+    /// Returns true if the gate is currently open.
+    pub fn is_open(&self) -> bool {
+        self.transitions.load(Ordering::SeqCst) % 2 == 1
+    }
+
+    /// Opens the gate, if it isn't already open.
+    pub fn open(&self) { self.set_open(true); }
+
+    /// Closes the gate, if it isn't already closed.
+    pub fn close(&self) { self.set_open(false); }
+
+    /// Closes and immediately reopens the gate, retriggering any envelope it
+    /// drives.
+    pub fn retrigger(&self) {
+        self.close();
+        self.open();
+    }
+
+    fn set_open(&self, open: bool) {
+        let mut current = self.transitions.load(Ordering::SeqCst);
+        while (current % 2 == 1) != open {
+            match self.transitions.compare_exchange(current,
+                                                    current + 1,
+                                                    Ordering::SeqCst,
+                                                    Ordering::SeqCst) {
+                Ok(_) => break,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+impl Default for Gate {
+    fn default() -> Gate { Gate::new() }
+}
+
+impl From<Gate> for Wave {
+    fn from(gate: Gate) -> Wave {
+        let seen = gate.transitions.load(Ordering::SeqCst);
+        Wave::new(Box::new(GateWave {
+            gate,
+            seen,
+            initial: seen,
+        }))
+    }
+}
+
+// ========================================================================= //
+
+impl Wave {
+    /// Creates a breakpoint envelope, which passes through each of the given
+    /// points in turn, and finishes after the last one.  The points should be
+    /// given in order of increasing time; before the first point, the
+    /// envelope holds the first point's value.  Can be used as an amplitude
+    /// envelope (by multiplying another waveform by it), or to control e.g.
+    /// the frequency of another wave.
+    pub fn envelope<I>(points: I) -> Wave
+        where I: IntoIterator,
+              I::Item: Into<Breakpoint>
+    {
+        let points = points.into_iter().map(Into::into).collect();
+        Wave::new(Box::new(BreakpointEnvelope::new(points, None, 1.0.into())))
+    }
+
+    /// Like `envelope`, but with a sustain loop: whenever the `gate` waveform
+    /// is positive, the portion of the envelope from point number
+    /// `loop_start` to point number `loop_end` (indices into the list of
+    /// points) repeats.  Once the gate closes, the envelope continues on past
+    /// the end of the loop.  If `loop_start == loop_end`, the envelope simply
+    /// holds that point's value while the gate is open.  Pass a constant gate
+    /// of 1 to loop forever.
+    ///
+    /// Panics if `loop_start > loop_end`, or if `loop_end` is out of range.
+    pub fn envelope_looped<I, G>(points: I, loop_start: usize,
+                                 loop_end: usize, gate: G)
+                                 -> Wave
+        where I: IntoIterator,
+              I::Item: Into<Breakpoint>,
+              G: Into<Wave>
+    {
+        let points: Vec<Breakpoint> =
+            points.into_iter().map(Into::into).collect();
+        assert!(loop_start <= loop_end,
+                "loop_start ({}) is after loop_end ({})",
+                loop_start,
+                loop_end);
+        assert!(loop_end < points.len(),
+                "loop_end ({}) is out of range for {} points",
+                loop_end,
+                points.len());
+        Wave::new(Box::new(BreakpointEnvelope::new(points,
+                                                   Some((loop_start,
+                                                         loop_end)),
+                                                   gate.into())))
+    }
+
+    /// Returns a new waveform by constraining this one with an ADSR (attack,
+    /// decay, sustain, release) envelope that is driven by the `gate`
+    /// waveform.  The gate is considered open whenever its value is positive;
+    /// the envelope attacks when the gate opens, and holds at the sustain
+    /// level for as long as it stays open.  When the gate closes, the
+    /// envelope releases from whatever level it has reached, even if it is
+    /// still in the attack or decay stage; reopening the gate retriggers the
+    /// envelope from its current level.  The new waveform finishes once the
+    /// gate waveform has finished and the release is complete.
+    pub fn gated_adsr<G: Into<Wave>>(self, gate: G, attack_time: f32,
+                                     decay_time: f32, sustain_level: f32,
+                                     release_time: f32)
+                                     -> Wave {
+        let envelope = GatedAdsr::new(gate.into(),
+                                      attack_time,
+                                      decay_time,
+                                      sustain_level,
+                                      release_time);
+        self * Wave::new(Box::new(envelope))
+    }
+}
+
+// ========================================================================= //
+
+/// A waveform that interpolates between a list of breakpoints, with an
+/// optional sustain loop.
+#[derive(Clone)]
+struct BreakpointEnvelope {
+    points: Vec<Breakpoint>,
+    sustain_loop: Option<(f32, f32)>,
+    gate: Wave,
+    time: f32,
+}
+
+impl BreakpointEnvelope {
+    fn new(mut points: Vec<Breakpoint>, sustain_loop: Option<(usize, usize)>,
+           gate: Wave)
+           -> BreakpointEnvelope {
+        // Ensure that the points' times never decrease.
+        let mut time = 0.0f32;
+        for point in points.iter_mut() {
+            time = time.max(point.time);
+            point.time = time;
+        }
+        let sustain_loop = sustain_loop.map(|(start, end)| {
+            (points[start].time, points[end].time)
+        });
+        BreakpointEnvelope {
+            points,
+            sustain_loop,
+            gate,
+            time: 0.0,
+        }
+    }
+
+    fn value_at(&self, time: f32) -> Option<f32> {
+        let first = self.points.first()?;
+        if time > self.points[self.points.len() - 1].time {
+            return None;
+        }
+        match self.points.iter().position(|point| point.time > time) {
+            Some(0) => Some(first.value),
+            Some(index) => {
+                let start = &self.points[index - 1];
+                let end = &self.points[index];
+                let progress = (time - start.time) / (end.time - start.time);
+                Some(end.curve.interpolate(start.value, end.value, progress))
+            }
+            None => Some(self.points[self.points.len() - 1].value),
+        }
+    }
+}
+
+impl WaveGen for BreakpointEnvelope {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        let gate_open = self.gate.next(step).is_some_and(|gate| gate > 0.0);
+        let value = self.value_at(self.time)?;
+        self.time += step;
+        if let Some((loop_start, loop_end)) = self.sustain_loop {
+            if gate_open && self.time >= loop_end {
+                let loop_length = loop_end - loop_start;
+                self.time = if loop_length > 0.0 {
+                    loop_start + (self.time - loop_start) % loop_length
+                } else {
+                    loop_end
+                };
+            }
+        }
+        Some(value)
+    }
+
+    fn reset(&mut self) {
+        self.gate.reset();
+        self.time = 0.0;
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+}
+
+// ========================================================================= //
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Stage {
+    Idle,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+/// A waveform representing an ADSR envelope driven by a gate waveform.
+#[derive(Clone)]
+struct GatedAdsr {
+    gate: Wave,
+    attack_time: f32,
+    decay_time: f32,
+    sustain_level: f32,
+    release_time: f32,
+    stage: Stage,
+    level: f32,
+    release_rate: f32,
+    gate_open: bool,
+}
+
+impl GatedAdsr {
+    fn new(gate: Wave, attack_time: f32, decay_time: f32,
+           sustain_level: f32, release_time: f32)
+           -> GatedAdsr {
+        GatedAdsr {
+            gate,
+            attack_time,
+            decay_time,
+            sustain_level,
+            release_time,
+            stage: Stage::Idle,
+            level: 0.0,
+            release_rate: 0.0,
+            gate_open: false,
+        }
+    }
+
+    /// Enters the release stage, at a rate that reaches zero after the
+    /// release time starting from the current level (which may be below or
+    /// above the sustain level).  With no release time, the envelope goes
+    /// silent at once.
+    fn release(&mut self) {
+        if self.release_time > 0.0 {
+            self.stage = Stage::Release;
+            self.release_rate = self.level / self.release_time;
+        } else {
+            self.stage = Stage::Idle;
+            self.level = 0.0;
+        }
+    }
+
+    fn advance(&mut self, step: f32) {
+        match self.stage {
+            Stage::Idle | Stage::Sustain => {}
+            Stage::Attack => {
+                if self.attack_time > 0.0 {
+                    self.level += step / self.attack_time;
+                } else {
+                    self.level = 1.0;
+                }
+                if self.level >= 1.0 {
+                    self.level = 1.0;
+                    self.stage = Stage::Decay;
+                }
+            }
+            Stage::Decay => {
+                if self.decay_time > 0.0 {
+                    self.level -= step * (1.0 - self.sustain_level) /
+                                  self.decay_time;
+                } else {
+                    self.level = self.sustain_level;
+                }
+                if self.level <= self.sustain_level {
+                    self.level = self.sustain_level;
+                    self.stage = Stage::Sustain;
+                }
+            }
+            Stage::Release => {
+                self.level -= step * self.release_rate;
+                if self.level <= 0.0 {
+                    self.level = 0.0;
+                    self.stage = Stage::Idle;
+                }
+            }
+        }
+    }
+}
+
+impl WaveGen for GatedAdsr {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        match self.gate.next(step) {
+            Some(gate) => {
+                let open = gate > 0.0;
+                if open && !self.gate_open {
+                    self.stage = Stage::Attack;
+                } else if !open && self.gate_open {
+                    self.release();
+                }
+                self.gate_open = open;
+            }
+            None => {
+                if self.gate_open {
+                    self.gate_open = false;
+                    self.release();
+                } else if self.stage == Stage::Idle {
+                    return None;
+                }
+            }
+        }
+        let value = self.level;
+        self.advance(step);
+        Some(value)
+    }
+
+    fn reset(&mut self) {
+        self.gate.reset();
+        self.stage = Stage::Idle;
+        self.level = 0.0;
+        self.release_rate = 0.0;
+        self.gate_open = false;
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+}
+
+// ========================================================================= //
+
+/// A waveform that follows the state of a `Gate`.
+#[derive(Clone)]
+struct GateWave {
+    gate: Gate,
+    seen: usize,
+    initial: usize,
+}
+
+impl WaveGen for GateWave {
+    fn next(&mut self, _: f32) -> Option<Sample> {
+        let current = self.gate.transitions.load(Ordering::SeqCst);
+        let previous = self.seen;
+        self.seen = current;
+        // If the gate was closed and reopened since the last sample, report
+        // it as closed for one sample so that the retrigger isn't lost.
+        let retriggered = previous % 2 == 1 && current != previous;
+        Some(if current % 2 == 1 && !retriggered {
+            1.0
+        } else {
+            0.0
+        })
+    }
+
+    fn reset(&mut self) { self.seen = self.initial; }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+}
+
+// ========================================================================= //
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Wave, WaveGen};
+    use super::{Curve, Gate};
+
+    #[test]
+    fn curve_interpolate() {
+        for &curve in [Curve::Linear,
+                       Curve::Exponential,
+                       Curve::Logarithmic,
+                       Curve::Tension(-3.0)]
+            .iter() {
+            assert_approx!(0.25, curve.interpolate(0.25, 0.75, 0.0));
+            assert_approx!(0.75, curve.interpolate(0.25, 0.75, 1.0));
+        }
+        assert_approx!(0.5, Curve::Linear.interpolate(0.25, 0.75, 0.5));
+        assert_approx!(0.5, Curve::Tension(0.0).interpolate(0.25, 0.75, 0.5));
+        // An exponential decay falls quickly at first, and an exponential
+        // attack rises slowly at first.
+        assert!(Curve::Exponential.interpolate(1.0, 0.0, 0.5) < 0.1);
+        assert!(Curve::Exponential.interpolate(0.0, 1.0, 0.5) < 0.1);
+        assert!(Curve::Logarithmic.interpolate(1.0, 0.0, 0.5) > 0.9);
+        assert!(Curve::Logarithmic.interpolate(0.0, 1.0, 0.5) > 0.9);
+        assert!(Curve::Tension(4.0).interpolate(1.0, 0.0, 0.5) > 0.5);
+        assert!(Curve::Tension(-4.0).interpolate(1.0, 0.0, 0.5) < 0.5);
+    }
+
+    #[test]
+    fn adshr_curved() {
+        let curves = (Curve::Linear, Curve::Exponential, Curve::Exponential);
+        let mut wave = Wave::from(1.0).adshr_curved(1.0, 2.0, 0.5, 1.0, 2.0,
+                                                    curves);
+        assert_approx!(0.0, wave.next(1.0).unwrap());
+        assert_approx!(1.0, wave.next(1.0).unwrap());
+        assert!(wave.next(1.0).unwrap() < 0.75);
+        assert_approx!(0.5, wave.next(1.0).unwrap());
+        assert_approx!(0.5, wave.next(1.0).unwrap());
+        assert!(wave.next(1.0).unwrap() < 0.25);
+        assert_eq!(None, wave.next(1.0));
+    }
+
+    #[test]
+    fn envelope() {
+        let mut wave = Wave::envelope(vec![(1.0, 0.0, Curve::Linear),
+                                           (3.0, 1.0, Curve::Linear),
+                                           (5.0, 0.0, Curve::Exponential)]);
+        assert_approx!(0.0, wave.next(1.0).unwrap());
+        assert_approx!(0.0, wave.next(1.0).unwrap());
+        assert_approx!(0.5, wave.next(1.0).unwrap());
+        assert_approx!(1.0, wave.next(1.0).unwrap());
+        assert!(wave.next(1.0).unwrap() < 0.1);
+        assert_approx!(0.0, wave.next(1.0).unwrap());
+        assert_eq!(None, wave.next(1.0));
+        // An envelope can also control the frequency of another wave.
+        let mut wave = Wave::pulse(Wave::envelope(vec![(0.0, 0.25),
+                                                       (1.0, 0.25)]),
+                                   0.5);
+        assert_eq!(Some(1.0), wave.next(1.0));
+        assert_eq!(Some(1.0), wave.next(1.0));
+        assert_eq!(None, wave.next(1.0));
+    }
+
+    #[test]
+    fn envelope_looped() {
+        let gate = Wave::from(1.0).take(5.0);
+        let mut wave = Wave::envelope_looped(vec![(0.0, 0.0),
+                                                  (1.0, 1.0),
+                                                  (3.0, 0.0),
+                                                  (4.0, 0.0)],
+                                             1,
+                                             2,
+                                             gate);
+        let expected = [0.0, 1.0, 0.5, 1.0, 0.5, 1.0, 0.5, 0.0, 0.0];
+        for &value in expected.iter() {
+            assert_approx!(value, wave.next(1.0).unwrap());
+        }
+        assert_eq!(None, wave.next(1.0));
+        wave.reset();
+        assert_approx!(0.0, wave.next(1.0).unwrap());
+        assert_approx!(1.0, wave.next(1.0).unwrap());
+    }
+
+    #[test]
+    fn gated_adsr() {
+        let gate = Wave::sequence(vec![Wave::from(1.0).take(6.0),
+                                       Wave::from(0.0).take(1.0)]);
+        let mut wave = Wave::from(1.0).gated_adsr(gate, 2.0, 2.0, 0.5, 2.0);
+        let expected = [0.0, 0.5, 1.0, 0.75, 0.5, 0.5, 0.5, 0.25];
+        for &value in expected.iter() {
+            assert_approx!(value, wave.next(1.0).unwrap());
+        }
+        assert_eq!(None, wave.next(1.0));
+    }
+
+    #[test]
+    fn gated_adsr_release_during_attack() {
+        let gate = Wave::from(1.0).take(2.0);
+        let mut wave = Wave::from(1.0).gated_adsr(gate, 4.0, 1.0, 0.5, 1.0);
+        assert_approx!(0.0, wave.next(1.0).unwrap());
+        assert_approx!(0.25, wave.next(1.0).unwrap());
+        // The release starts from the level reached so far, not from the
+        // sustain level.
+        assert_approx!(0.5, wave.next(1.0).unwrap());
+        assert_eq!(None, wave.next(1.0));
+    }
+
+    #[test]
+    fn gated_adsr_instant_release() {
+        let gate = Gate::new();
+        let mut wave = Wave::from(1.0).gated_adsr(gate.clone(),
+                                                  0.0,
+                                                  0.0,
+                                                  1.0,
+                                                  0.0);
+        gate.open();
+        assert_approx!(0.0, wave.next(1.0).unwrap());
+        assert_approx!(1.0, wave.next(0.0).unwrap());
+        // With no release time, closing the gate silences the envelope at
+        // once, even on a zero step.
+        gate.close();
+        assert_eq!(Some(0.0), wave.next(0.0));
+        assert_eq!(Some(0.0), wave.next(0.0));
+        // Retriggering attacks from silence.
+        gate.open();
+        assert_approx!(0.0, wave.next(0.0).unwrap());
+        assert_approx!(1.0, wave.next(1.0).unwrap());
+    }
+
+    #[test]
+    fn gate_handle() {
+        let gate = Gate::new();
+        let mut wave = Wave::from(1.0).gated_adsr(gate.clone(),
+                                                  1.0,
+                                                  0.0,
+                                                  1.0,
+                                                  2.0);
+        assert_approx!(0.0, wave.next(1.0).unwrap());
+        gate.open();
+        assert_approx!(0.0, wave.next(1.0).unwrap());
+        assert_approx!(1.0, wave.next(1.0).unwrap());
+        assert_approx!(1.0, wave.next(1.0).unwrap());
+        gate.close();
+        assert_approx!(1.0, wave.next(1.0).unwrap());
+        gate.open();
+        assert_approx!(0.5, wave.next(1.0).unwrap());
+        assert_approx!(1.0, wave.next(1.0).unwrap());
+        // Retriggering an open gate releases the envelope for one sample, and
+        // then attacks again.
+        gate.retrigger();
+        assert_approx!(1.0, wave.next(1.0).unwrap());
+        assert_approx!(0.5, wave.next(1.0).unwrap());
+        assert_approx!(1.0, wave.next(1.0).unwrap());
+    }
+}
+
+// ========================================================================= //
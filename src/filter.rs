@@ -0,0 +1,233 @@
+//! Resonant, lowpass, and highpass filters.
+
+use std::f32::consts::{FRAC_1_SQRT_2, PI};
+use super::{Sample, Wave, WaveGen};
+
+// ========================================================================= //
+
+/// The lowest cutoff frequency (in hertz) allowed for lowpass and highpass
+/// filters, below which the filters become unstable.
+const FILTER_MIN_CUTOFF: f32 = 1.0;
+
+// ========================================================================= //
+
+impl Wave {
+    /// Returns a new waveform that passes this one through a two-pole
+    /// bandpass resonator, whose center frequency (in hertz) and bandwidth
+    /// (in hertz) over time are controlled by the `center` and `bandwidth`
+    /// waveforms (either or both of which may be constants).  Frequencies at
+    /// the center pass through unchanged, while those further away are
+    /// attenuated; the narrower the bandwidth, the more the filter rings.
+    /// The new waveform finishes when any of the inputs does.
+    pub fn bandpass<C, B>(self, center: C, bandwidth: B) -> Wave
+        where C: Into<Wave>,
+              B: Into<Wave>
+    {
+        Wave::new(Box::new(Bandpass {
+            wave: self,
+            center: center.into(),
+            bandwidth: bandwidth.into(),
+            biquad: Biquad::default(),
+        }))
+    }
+
+    /// Returns a new waveform that passes this one through a two-pole
+    /// (12 dB per octave) Butterworth lowpass filter, whose cutoff frequency
+    /// (in hertz) over time is controlled by the `cutoff` waveform (which may
+    /// be a constant).  The new waveform finishes when either input does.
+    pub fn lowpass<C: Into<Wave>>(self, cutoff: C) -> Wave {
+        Wave::new(Box::new(PassFilter {
+            wave: self,
+            cutoff: cutoff.into(),
+            highpass: false,
+            biquad: Biquad::default(),
+        }))
+    }
+
+    /// Returns a new waveform that passes this one through a two-pole
+    /// (12 dB per octave) Butterworth highpass filter, whose cutoff frequency
+    /// (in hertz) over time is controlled by the `cutoff` waveform (which may
+    /// be a constant).  The new waveform finishes when either input does.
+    pub fn highpass<C: Into<Wave>>(self, cutoff: C) -> Wave {
+        Wave::new(Box::new(PassFilter {
+            wave: self,
+            cutoff: cutoff.into(),
+            highpass: true,
+            biquad: Biquad::default(),
+        }))
+    }
+}
+
+// ========================================================================= //
+
+/// The state of a two-pole ("biquad") filter.  The filter designs are from
+/// Robert Bristow-Johnson's "Audio EQ Cookbook", with their frequencies kept
+/// safely below the Nyquist frequency.
+#[derive(Clone, Default)]
+pub(crate) struct Biquad {
+    inputs: [f32; 2],
+    outputs: [f32; 2],
+}
+
+impl Biquad {
+    /// Filters the next input sample through a bandpass resonator with a peak
+    /// gain of 1, with the given center frequency and bandwidth (both in
+    /// hertz).
+    pub fn bandpass(&mut self, input: f32, center: f32, bandwidth: f32,
+                    step: f32)
+                    -> f32 {
+        let center = center.clamp(0.0, 0.99 * 0.5 / step);
+        let omega = 2.0 * PI * center * step;
+        let q = center / bandwidth.max(1e-3);
+        let alpha = omega.sin() / (2.0 * q.max(1e-3));
+        self.filter(input,
+                    [alpha, 0.0, -alpha],
+                    [1.0 + alpha, -2.0 * omega.cos(), 1.0 - alpha])
+    }
+
+    /// Filters the next input sample through a Butterworth lowpass filter
+    /// with the given cutoff frequency (in hertz).
+    pub fn lowpass(&mut self, input: f32, cutoff: f32, step: f32) -> f32 {
+        let (cos, alpha) = Biquad::butterworth(cutoff, step);
+        let b = [0.5 * (1.0 - cos), 1.0 - cos, 0.5 * (1.0 - cos)];
+        self.filter(input, b, [1.0 + alpha, -2.0 * cos, 1.0 - alpha])
+    }
+
+    /// Filters the next input sample through a Butterworth highpass filter
+    /// with the given cutoff frequency (in hertz).
+    pub fn highpass(&mut self, input: f32, cutoff: f32, step: f32) -> f32 {
+        let (cos, alpha) = Biquad::butterworth(cutoff, step);
+        let b = [0.5 * (1.0 + cos), -1.0 - cos, 0.5 * (1.0 + cos)];
+        self.filter(input, b, [1.0 + alpha, -2.0 * cos, 1.0 - alpha])
+    }
+
+    pub fn reset(&mut self) { *self = Biquad::default(); }
+
+    /// Returns the cosine of the angular cutoff frequency, and the alpha
+    /// value, for a Butterworth lowpass or highpass filter.
+    fn butterworth(cutoff: f32, step: f32) -> (f32, f32) {
+        let cutoff = cutoff.max(FILTER_MIN_CUTOFF).min(0.99 * 0.5 / step);
+        let omega = 2.0 * PI * cutoff * step;
+        (omega.cos(), omega.sin() * FRAC_1_SQRT_2)
+    }
+
+    /// Filters the next input sample using the given feedforward (`b`) and
+    /// feedback (`a`) coefficients.
+    fn filter(&mut self, input: f32, b: [f32; 3], a: [f32; 3]) -> f32 {
+        let output = (b[0] * input + b[1] * self.inputs[0] +
+                      b[2] * self.inputs[1] -
+                      a[1] * self.outputs[0] -
+                      a[2] * self.outputs[1]) / a[0];
+        self.inputs = [input, self.inputs[0]];
+        self.outputs = [output, self.outputs[0]];
+        output
+    }
+}
+
+// ========================================================================= //
+
+/// A waveform passed through a bandpass resonator.
+#[derive(Clone)]
+struct Bandpass {
+    wave: Wave,
+    center: Wave,
+    bandwidth: Wave,
+    biquad: Biquad,
+}
+
+impl WaveGen for Bandpass {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        let input = self.wave.next(step)?;
+        let center = self.center.next(step)?;
+        let bandwidth = self.bandwidth.next(step)?;
+        Some(self.biquad.bandpass(input, center, bandwidth, step))
+    }
+
+    fn reset(&mut self) {
+        self.wave.reset();
+        self.center.reset();
+        self.bandwidth.reset();
+        self.biquad.reset();
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+}
+
+// ========================================================================= //
+
+/// A waveform passed through a lowpass or highpass filter.
+#[derive(Clone)]
+struct PassFilter {
+    wave: Wave,
+    cutoff: Wave,
+    highpass: bool,
+    biquad: Biquad,
+}
+
+impl WaveGen for PassFilter {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        let input = self.wave.next(step)?;
+        let cutoff = self.cutoff.next(step)?;
+        Some(if self.highpass {
+            self.biquad.highpass(input, cutoff, step)
+        } else {
+            self.biquad.lowpass(input, cutoff, step)
+        })
+    }
+
+    fn reset(&mut self) {
+        self.wave.reset();
+        self.cutoff.reset();
+        self.biquad.reset();
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+}
+
+// ========================================================================= //
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Wave, settled_peak};
+
+    /// Returns the peak amplitude of the wave over the given number of
+    /// samples, after letting it settle for the same number of samples.
+    #[test]
+    fn bandpass() {
+        let step = 1.0 / 44100.0;
+        let filtered = Wave::sine(1000.0).bandpass(1000.0, 100.0);
+        let peak = settled_peak(filtered, step, 4410);
+        assert!((peak - 1.0).abs() < 0.01, "peak = {}", peak);
+        let filtered = Wave::sine(4000.0).bandpass(1000.0, 100.0);
+        assert!(settled_peak(filtered, step, 4410) < 0.05);
+        let filtered = Wave::sine(250.0).bandpass(1000.0, 100.0);
+        assert!(settled_peak(filtered, step, 4410) < 0.05);
+        // Center frequencies beyond the Nyquist frequency don't blow up.
+        let filtered = Wave::noise(10000.0).bandpass(30000.0, 100.0);
+        assert!(settled_peak(filtered, step, 4410).is_finite());
+    }
+
+    #[test]
+    fn lowpass_and_highpass() {
+        let step = 1.0 / 44100.0;
+        let filtered = Wave::sine(100.0).lowpass(1000.0);
+        assert!(settled_peak(filtered, step, 4410) > 0.99);
+        let filtered = Wave::sine(10000.0).lowpass(1000.0);
+        assert!(settled_peak(filtered, step, 4410) < 0.02);
+        let filtered = Wave::sine(100.0).highpass(1000.0);
+        assert!(settled_peak(filtered, step, 4410) < 0.02);
+        let filtered = Wave::sine(10000.0).highpass(1000.0);
+        assert!(settled_peak(filtered, step, 4410) > 0.99);
+        // At the cutoff frequency, the gain is -3 dB.
+        let filtered = Wave::sine(1000.0).lowpass(1000.0);
+        let peak = settled_peak(filtered, step, 4410);
+        assert!((peak - 0.5f32.sqrt()).abs() < 0.01, "peak = {}", peak);
+        // Cutoffs out of range don't blow up.
+        let filtered = Wave::noise(10000.0).highpass(0.0);
+        assert!(settled_peak(filtered, step, 4410).is_finite());
+        let filtered = Wave::noise(10000.0).lowpass(1e6);
+        assert!(settled_peak(filtered, step, 4410).is_finite());
+    }
+}
+
+// ========================================================================= //
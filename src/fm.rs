@@ -0,0 +1,340 @@
+//! FM (phase modulation) operators and voices.
+
+use std::f32::consts::PI;
+use super::{Phase, Sample, Wave, WaveGen};
+
+// ========================================================================= //
+
+/// The number of operators in an FM voice.
+pub const FM_NUM_OPERATORS: usize = 4;
+
+/// The number of available FM algorithms.
+pub const FM_NUM_ALGORITHMS: usize = 8;
+
+/// For each algorithm, the modulators of each operator (as a bitmask of
+/// operator indices), and which operators are carriers (also as a bitmask).
+/// Operators are only ever modulated by lower-numbered operators, so they can
+/// be computed in order.
+const FM_ALGORITHMS: [([u8; FM_NUM_OPERATORS], u8); FM_NUM_ALGORITHMS] = [
+    ([0b0000, 0b0001, 0b0010, 0b0100], 0b1000),
+    ([0b0000, 0b0000, 0b0011, 0b0100], 0b1000),
+    ([0b0000, 0b0000, 0b0010, 0b0101], 0b1000),
+    ([0b0000, 0b0001, 0b0000, 0b0110], 0b1000),
+    ([0b0000, 0b0001, 0b0000, 0b0100], 0b1010),
+    ([0b0000, 0b0001, 0b0001, 0b0001], 0b1110),
+    ([0b0000, 0b0001, 0b0000, 0b0000], 0b1110),
+    ([0b0000, 0b0000, 0b0000, 0b0000], 0b1111),
+];
+
+// ========================================================================= //
+
+/// One operator in an FM voice: a sine wave oscillator whose frequency is a
+/// fixed ratio of the voice's frequency.
+#[derive(Clone)]
+pub struct FmOperator {
+    ratio: f32,
+    level: Wave,
+}
+
+impl FmOperator {
+    /// Creates an operator that runs at `ratio` times the voice's frequency,
+    /// with its output level over time controlled by the `level` waveform
+    /// (which may be a constant, or e.g. an envelope).  For a carrier, the
+    /// level is the output amplitude; for a modulator, it is the modulation
+    /// index (in radians) that it applies to the operators it modulates.
+    pub fn new<L: Into<Wave>>(ratio: f32, level: L) -> FmOperator {
+        FmOperator {
+            ratio,
+            level: level.into(),
+        }
+    }
+}
+
+// ========================================================================= //
+
+impl Wave {
+    /// Creates a sine wave operator with self-feedback, whose frequency over
+    /// time is controlled by the `freq` waveform, and whose feedback amount
+    /// over time is controlled by the `feedback` waveform (either or both of
+    /// which may be constants).  The operator's previous output (averaged
+    /// over the last two samples, to avoid instability), times the feedback
+    /// amount, is used to modulate its own phase (in radians).  A feedback of
+    /// 0 gives a plain sine wave; increasing it gradually turns the wave into
+    /// something like a sawtooth.
+    pub fn sine_feedback<F: Into<Wave>, B: Into<Wave>>(freq: F, feedback: B)
+                                                       -> Wave {
+        Wave::new(Box::new(FeedbackSine {
+            freq: freq.into(),
+            feedback: feedback.into(),
+            phase: Phase::default(),
+            history: [0.0; 2],
+        }))
+    }
+
+    /// Creates a four-operator FM voice whose base frequency over time is
+    /// controlled by the `freq` waveform, and whose operators are connected
+    /// according to the given algorithm number (from 0 to 7, following the
+    /// classic four-operator FM chips).  The first operator also modulates
+    /// itself, by the `feedback` waveform (see `Wave::sine_feedback`).  In
+    /// the diagrams below, `a > b` means that operator `a` modulates operator
+    /// `b`, and `a + b` means that operators `a` and `b` are mixed together:
+    ///
+    /// * 0: `1 > 2 > 3 > 4`
+    /// * 1: `(1 + 2) > 3 > 4`
+    /// * 2: `(1 + (2 > 3)) > 4`
+    /// * 3: `((1 > 2) + 3) > 4`
+    /// * 4: `(1 > 2) + (3 > 4)`
+    /// * 5: `1 > (2 + 3 + 4)`
+    /// * 6: `(1 > 2) + 3 + 4`
+    /// * 7: `1 + 2 + 3 + 4`
+    ///
+    /// The outputs of the carriers (the operators at the end of each chain)
+    /// are averaged together to produce the output of the voice.  A
+    /// modulator whose level waveform finishes is treated as silent, and the
+    /// voice finishes once the `freq` waveform or all of the carriers' level
+    /// waveforms have finished.
+    ///
+    /// Panics if `algorithm` is not less than `FM_NUM_ALGORITHMS`.
+    pub fn fm_voice<F: Into<Wave>, B: Into<Wave>>(
+        freq: F, algorithm: usize, operators: [FmOperator; FM_NUM_OPERATORS],
+        feedback: B)
+        -> Wave {
+        assert!(algorithm < FM_NUM_ALGORITHMS,
+                "FM algorithm {} is out of range",
+                algorithm);
+        Wave::new(Box::new(FmVoice {
+            freq: freq.into(),
+            algorithm,
+            operators,
+            feedback: feedback.into(),
+            phases: [0.0; FM_NUM_OPERATORS],
+            initial_phase: 0.0,
+            phase_offset: None,
+            history: [0.0; 2],
+        }))
+    }
+}
+
+// ========================================================================= //
+
+/// A variable-frequency sine wave operator with self-feedback.
+#[derive(Clone)]
+struct FeedbackSine {
+    freq: Wave,
+    feedback: Wave,
+    phase: Phase,
+    history: [f32; 2],
+}
+
+impl WaveGen for FeedbackSine {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        let freq = self.freq.next(step)?;
+        let feedback = self.feedback.next(step)?;
+        let phase = self.phase.advance(freq, step)?;
+        let phase_mod = 0.5 * feedback * (self.history[0] + self.history[1]);
+        let value = (2.0 * PI * phase + phase_mod).sin();
+        self.history = [value, self.history[0]];
+        Some(value)
+    }
+
+    fn reset(&mut self) {
+        self.freq.reset();
+        self.feedback.reset();
+        self.phase.reset();
+        self.history = [0.0; 2];
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+
+    fn phase_wrap(&self) -> Option<f32> { self.phase.wrap }
+
+    fn sync(&mut self, elapsed: f32) { self.phase.sync(elapsed); }
+
+    fn set_initial_phase(&mut self, phase: f32) {
+        self.phase.set_initial(phase);
+    }
+
+    fn set_phase_offset(&mut self, offset: Wave) {
+        self.phase.offset = Some(offset);
+    }
+}
+
+// ========================================================================= //
+
+/// A four-operator FM voice.
+#[derive(Clone)]
+struct FmVoice {
+    freq: Wave,
+    algorithm: usize,
+    operators: [FmOperator; FM_NUM_OPERATORS],
+    feedback: Wave,
+    phases: [f32; FM_NUM_OPERATORS],
+    /// The initial phase and phase offset of the voice, in cycles of its
+    /// base frequency; each operator's are scaled by its frequency ratio.
+    initial_phase: f32,
+    phase_offset: Option<Wave>,
+    history: [f32; 2],
+}
+
+impl FmVoice {
+    /// Returns each operator to its initial phase.
+    fn restart_phases(&mut self) {
+        for (phase, operator) in self.phases.iter_mut()
+            .zip(self.operators.iter()) {
+            *phase = (operator.ratio * self.initial_phase).rem_euclid(1.0);
+        }
+    }
+}
+
+impl WaveGen for FmVoice {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        let freq = self.freq.next(step)?;
+        let offset = match self.phase_offset {
+            Some(ref mut offset) => offset.next(step)?,
+            None => 0.0,
+        };
+        let feedback = self.feedback.next(step).unwrap_or(0.0);
+        let (modulators, carriers) = FM_ALGORITHMS[self.algorithm];
+        let mut outputs = [0.0; FM_NUM_OPERATORS];
+        let mut total = 0.0;
+        let mut num_carriers = 0;
+        let mut any_carrier_playing = false;
+        for index in 0..FM_NUM_OPERATORS {
+            let is_carrier = (carriers & (1 << index)) != 0;
+            let level = match self.operators[index].level.next(step) {
+                Some(level) => {
+                    any_carrier_playing |= is_carrier;
+                    level
+                }
+                None => 0.0,
+            };
+            let mut phase_mod = 0.0;
+            for (other, &output) in outputs.iter().enumerate().take(index) {
+                if (modulators[index] & (1 << other)) != 0 {
+                    phase_mod += output;
+                }
+            }
+            if index == 0 {
+                phase_mod += 0.5 * feedback *
+                             (self.history[0] + self.history[1]);
+            }
+            let ratio = self.operators[index].ratio;
+            let phase = self.phases[index] + ratio * offset;
+            let output = level * (2.0 * PI * phase + phase_mod).sin();
+            outputs[index] = output;
+            if is_carrier {
+                total += output;
+                num_carriers += 1;
+            }
+            self.phases[index] = (self.phases[index] + freq * ratio * step) %
+                                 1.0;
+        }
+        self.history = [outputs[0], self.history[0]];
+        if any_carrier_playing {
+            Some(total / num_carriers as f32)
+        } else {
+            None
+        }
+    }
+
+    fn reset(&mut self) {
+        self.freq.reset();
+        self.feedback.reset();
+        for operator in self.operators.iter_mut() {
+            operator.level.reset();
+        }
+        self.restart_phases();
+        if let Some(ref mut offset) = self.phase_offset {
+            offset.reset();
+        }
+        self.history = [0.0; 2];
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+
+    fn set_initial_phase(&mut self, phase: f32) {
+        self.initial_phase = phase;
+        self.restart_phases();
+    }
+
+    fn set_phase_offset(&mut self, offset: Wave) {
+        self.phase_offset = Some(offset);
+    }
+}
+
+// ========================================================================= //
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+    use super::super::{Wave, WaveGen};
+    use super::FmOperator;
+
+    #[test]
+    fn sine_pm() {
+        let step = 1.0 / 16.0;
+        let mut wave = Wave::sine_pm(1.0, 0.5 * PI);
+        for index in 0..16 {
+            let expected = (2.0 * PI * index as f32 * step).cos();
+            assert_approx!(expected, wave.next(step).unwrap());
+        }
+        let modulator = Wave::sine(3.0) * 2.0;
+        let mut wave = Wave::sine_pm(1.0, modulator);
+        for index in 0..16 {
+            let time = index as f32 * step;
+            let expected = (2.0 * PI * time +
+                            2.0 * (6.0 * PI * time).sin())
+                .sin();
+            assert_approx!(expected, wave.next(step).unwrap());
+        }
+    }
+
+    #[test]
+    fn sine_feedback() {
+        let step = 1.0 / 16.0;
+        let mut wave = Wave::sine_feedback(1.0, 0.0);
+        let mut sine = Wave::sine(1.0);
+        for _ in 0..16 {
+            assert_approx!(sine.next(step).unwrap(), wave.next(step).unwrap());
+        }
+        // With feedback, the wave runs ahead of a plain sine wave.
+        let mut wave = Wave::sine_feedback(1.0, 1.0);
+        assert_approx!(0.0, wave.next(step).unwrap());
+        assert_approx!((0.125 * PI).sin(), wave.next(step).unwrap());
+        assert!(wave.next(step).unwrap() > (0.25 * PI).sin());
+    }
+
+    #[test]
+    fn fm_voice() {
+        let step = 1.0 / 16.0;
+        let operators = [FmOperator::new(1.0, 0.0),
+                         FmOperator::new(1.0, 0.0),
+                         FmOperator::new(1.0, 0.0),
+                         FmOperator::new(2.0, 1.0)];
+        let mut wave = Wave::fm_voice(1.0, 0, operators, 0.0);
+        let mut sine = Wave::sine(2.0);
+        for _ in 0..16 {
+            assert_approx!(sine.next(step).unwrap(), wave.next(step).unwrap());
+        }
+        // With algorithm 4, operator 3 modulates operator 4, and the result
+        // is mixed with operator 2.
+        let operators = [FmOperator::new(1.0, 0.0),
+                         FmOperator::new(1.0, 1.0),
+                         FmOperator::new(3.0, 2.0),
+                         FmOperator::new(1.0, Wave::from(1.0).take(0.5))];
+        let mut wave = Wave::fm_voice(1.0, 4, operators, 0.0);
+        for index in 0..16 {
+            let time = index as f32 * step;
+            let op2 = (2.0 * PI * time).sin();
+            let op3 = 2.0 * (6.0 * PI * time).sin();
+            let op4 = if time < 0.5 {
+                (2.0 * PI * time + op3).sin()
+            } else {
+                0.0
+            };
+            assert_approx!(0.5 * (op2 + op4), wave.next(step).unwrap());
+        }
+    }
+}
+
+// ========================================================================= //
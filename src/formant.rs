@@ -0,0 +1,196 @@
+//! Formant (vowel) synthesis.
+
+use super::{Sample, Wave, WaveGen};
+use super::filter::Biquad;
+
+// ========================================================================= //
+
+/// The number of formants used to synthesize each vowel.
+const NUM_FORMANTS: usize = 5;
+
+/// The center frequencies (in hertz), bandwidths (in hertz), and gains (in
+/// decibels) of the formants of each vowel, in the order of the `Vowel`
+/// enum.  These are the formants of a tenor voice, as tabulated in the
+/// appendix of the Csound manual.
+const FORMANT_TABLE: [[(f32, f32, f32); NUM_FORMANTS]; 5] = [
+    [(650.0, 80.0, 0.0),
+     (1080.0, 90.0, -6.0),
+     (2650.0, 120.0, -7.0),
+     (2900.0, 130.0, -8.0),
+     (3250.0, 140.0, -22.0)],
+    [(400.0, 70.0, 0.0),
+     (1700.0, 80.0, -14.0),
+     (2600.0, 100.0, -12.0),
+     (3200.0, 120.0, -14.0),
+     (3580.0, 120.0, -20.0)],
+    [(290.0, 40.0, 0.0),
+     (1870.0, 90.0, -15.0),
+     (2800.0, 100.0, -18.0),
+     (3250.0, 120.0, -20.0),
+     (3540.0, 120.0, -30.0)],
+    [(400.0, 40.0, 0.0),
+     (800.0, 80.0, -10.0),
+     (2600.0, 100.0, -12.0),
+     (2800.0, 120.0, -12.0),
+     (3000.0, 120.0, -26.0)],
+    [(350.0, 40.0, 0.0),
+     (600.0, 60.0, -20.0),
+     (2700.0, 100.0, -17.0),
+     (2900.0, 120.0, -14.0),
+     (3300.0, 120.0, -26.0)],
+];
+
+// ========================================================================= //
+
+/// A vowel sound, for use with `Wave::formant`.
+///
+/// A `Vowel` can be converted into a constant `Wave` whose value is the
+/// vowel's position (from 0 for `A` to 4 for `U`), so that it can be passed
+/// directly to `Wave::formant`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Vowel {
+    /// The "a" vowel, as in "father" (position 0).
+    A,
+    /// The "e" vowel, as in "bed" (position 1).
+    E,
+    /// The "i" vowel, as in "see" (position 2).
+    I,
+    /// The "o" vowel, as in "law" (position 3).
+    O,
+    /// The "u" vowel, as in "boot" (position 4).
+    U,
+}
+
+impl Vowel {
+    /// Returns this vowel's position, for use in morphing between vowels
+    /// with `Wave::formant`.
+    pub fn position(self) -> f32 {
+        match self {
+            Vowel::A => 0.0,
+            Vowel::E => 1.0,
+            Vowel::I => 2.0,
+            Vowel::O => 3.0,
+            Vowel::U => 4.0,
+        }
+    }
+}
+
+impl From<Vowel> for Wave {
+    fn from(vowel: Vowel) -> Wave { Wave::from(vowel.position()) }
+}
+
+// ========================================================================= //
+
+impl Wave {
+    /// Returns a new waveform that filters the `source` waveform through a
+    /// bank of parallel bandpass resonators (see `Wave::bandpass`) tuned to
+    /// the formants of a vowel, making it sound as though it were sung or
+    /// spoken.  Harmonically rich sources, such as `Wave::saw` or
+    /// `Wave::pulse`, work best.
+    ///
+    /// The `vowel` waveform gives the vowel's position over time, from 0
+    /// (`Vowel::A`) through 1 (`E`), 2 (`I`), and 3 (`O`), to 4 (`U`);
+    /// positions in between morph smoothly between neighboring vowels, and
+    /// positions outside this range are clamped to it.  A `Vowel` may be
+    /// passed directly for a fixed vowel.  The new waveform finishes when
+    /// either input does.
+    pub fn formant<S, V>(source: S, vowel: V) -> Wave
+        where S: Into<Wave>,
+              V: Into<Wave>
+    {
+        Wave::new(Box::new(Formant {
+            source: source.into(),
+            vowel: vowel.into(),
+            resonators: Default::default(),
+        }))
+    }
+}
+
+// ========================================================================= //
+
+/// A waveform filtered through a vowel's formants.
+#[derive(Clone)]
+struct Formant {
+    source: Wave,
+    vowel: Wave,
+    resonators: [Biquad; NUM_FORMANTS],
+}
+
+impl WaveGen for Formant {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        let input = self.source.next(step)?;
+        let position = self.vowel.next(step)?.clamp(0.0, 4.0);
+        let index = (position.floor() as usize).min(3);
+        let fraction = position - index as f32;
+        let mut output = 0.0;
+        for (formant, resonator) in self.resonators.iter_mut().enumerate() {
+            let (freq0, width0, gain0) = FORMANT_TABLE[index][formant];
+            let (freq1, width1, gain1) = FORMANT_TABLE[index + 1][formant];
+            let freq = freq0 + fraction * (freq1 - freq0);
+            let width = width0 + fraction * (width1 - width0);
+            let gain = decibels_to_gain(gain0) +
+                       fraction *
+                       (decibels_to_gain(gain1) - decibels_to_gain(gain0));
+            output += gain * resonator.bandpass(input, freq, width, step);
+        }
+        Some(output)
+    }
+
+    fn reset(&mut self) {
+        self.source.reset();
+        self.vowel.reset();
+        for resonator in self.resonators.iter_mut() {
+            resonator.reset();
+        }
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+}
+
+fn decibels_to_gain(decibels: f32) -> f32 { 10f32.powf(decibels / 20.0) }
+
+// ========================================================================= //
+
+#[cfg(test)]
+mod tests {
+    use super::Vowel;
+    use super::super::{Wave, WaveGen, settled_peak};
+
+    #[test]
+    fn formant_vowels() {
+        let step = 1.0 / 44100.0;
+        // A tone at the first formant of "a" passes through an "a" filter,
+        // but not an "i" filter, whose formants are all far away from it.
+        let a = Wave::formant(Wave::sine(650.0), Vowel::A);
+        assert!(settled_peak(a, step, 4410) > 0.9);
+        let i = Wave::formant(Wave::sine(650.0), Vowel::I);
+        assert!(settled_peak(i, step, 4410) < 0.2);
+        // Fractional positions morph between vowels.
+        let a = Wave::formant(Wave::sine(650.0), 0.0);
+        let half = Wave::formant(Wave::sine(650.0), 0.5);
+        let e = Wave::formant(Wave::sine(650.0), Vowel::E);
+        let peaks = [settled_peak(a, step, 4410),
+                     settled_peak(half, step, 4410),
+                     settled_peak(e, step, 4410)];
+        assert!(peaks[0] > peaks[1] && peaks[1] > peaks[2],
+                "peaks = {:?}",
+                peaks);
+        // Positions are clamped to the range of vowels.
+        let mut beyond = Wave::formant(Wave::saw(110.0), 7.0);
+        let mut u = Wave::formant(Wave::saw(110.0), Vowel::U);
+        for _ in 0..100 {
+            assert_eq!(u.next(step), beyond.next(step));
+        }
+    }
+
+    #[test]
+    fn formant_finishes() {
+        let vowel = Wave::from(0.0).take(2.0);
+        let mut wave = Wave::formant(Wave::saw(110.0), vowel);
+        assert!(wave.next(1.0).is_some());
+        assert!(wave.next(1.0).is_some());
+        assert_eq!(None, wave.next(1.0));
+    }
+}
+
+// ========================================================================= //
@@ -0,0 +1,359 @@
+//! Granular synthesis.
+
+use std::f32::consts::PI;
+use super::{Rng, Sample, SampleBuffer, Wave, WaveGen};
+
+// ========================================================================= //
+
+const GRANULAR_INIT_SEED: u64 = 246813579246813579;
+
+/// The maximum number of grains that can play at once; while this many are
+/// playing, newly-spawned grains are dropped.
+const GRANULAR_MAX_GRAINS: usize = 256;
+
+/// The standard deviation of the Gaussian grain envelope, as a fraction of
+/// the grain's duration.
+const GAUSSIAN_GRAIN_SIGMA: f32 = 1.0 / 6.0;
+
+/// The fraction of a trapezoidal grain's duration spent fading in (and,
+/// likewise, fading out).
+const TRAPEZOID_GRAIN_FADE: f32 = 0.25;
+
+// ========================================================================= //
+
+/// The shape of the amplitude envelope applied to each grain by
+/// `Wave::granular`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GrainEnvelope {
+    /// No envelope at all; grains start and stop abruptly, and so click.
+    Rectangular,
+    /// A linear fade in over the first half of the grain, and out over the
+    /// second half.
+    Triangle,
+    /// A raised-cosine (Hann) window, which gives smooth, even textures.
+    Hann,
+    /// A bell curve, which is narrower than the Hann window, and so gives
+    /// more separated grains.
+    Gaussian,
+    /// A linear fade in over the first quarter of the grain, and out over
+    /// the last quarter, which keeps more of the source's character.
+    Trapezoid,
+}
+
+impl GrainEnvelope {
+    /// Returns the envelope's gain at the given fraction of the way through
+    /// the grain.
+    fn gain(self, fraction: f32) -> f32 {
+        match self {
+            GrainEnvelope::Rectangular => 1.0,
+            GrainEnvelope::Triangle => 1.0 - (2.0 * fraction - 1.0).abs(),
+            GrainEnvelope::Hann => 0.5 - 0.5 * (2.0 * PI * fraction).cos(),
+            GrainEnvelope::Gaussian => {
+                let x = (fraction - 0.5) / GAUSSIAN_GRAIN_SIGMA;
+                (-0.5 * x * x).exp()
+            }
+            GrainEnvelope::Trapezoid => {
+                let edge = fraction.min(1.0 - fraction);
+                (edge / TRAPEZOID_GRAIN_FADE).min(1.0)
+            }
+        }
+    }
+}
+
+// ========================================================================= //
+
+/// The settings for a granular generator, for use with
+/// [`Wave::granular`](struct.Wave.html#method.granular).
+#[derive(Clone)]
+pub struct Grains {
+    density: Wave,
+    size: Wave,
+    position: Wave,
+    pitch: Wave,
+    jitter: Wave,
+    envelope: GrainEnvelope,
+    seed: u64,
+}
+
+impl Grains {
+    /// Creates settings for spawning `density` grains per second, each
+    /// `size` seconds long, read from the given `position` in the source,
+    /// from 0 (the start of the source) to 1 (the end of the source).  All
+    /// three may be constants or waveforms.  The grains are played at their
+    /// original pitch, without jitter, using a Hann envelope.
+    pub fn new<D, S, P>(density: D, size: S, position: P) -> Grains
+        where D: Into<Wave>,
+              S: Into<Wave>,
+              P: Into<Wave>
+    {
+        Grains {
+            density: density.into(),
+            size: size.into(),
+            position: position.into(),
+            pitch: Wave::from(1.0),
+            jitter: Wave::from(0.0),
+            envelope: GrainEnvelope::Hann,
+            seed: GRANULAR_INIT_SEED,
+        }
+    }
+
+    /// Sets the playback rate of each grain (which may be a constant or a
+    /// waveform), as a ratio of the source's original speed (and so pitch);
+    /// e.g. 2 plays grains an octave higher, and -1 plays them backwards.
+    pub fn pitch<W: Into<Wave>>(mut self, pitch: W) -> Grains {
+        self.pitch = pitch.into();
+        self
+    }
+
+    /// Sets the amount of random variation, from 0 (none) to 1, applied to
+    /// each grain (which may be a constant or a waveform).  Each grain's
+    /// start position is offset by a random amount of up to `jitter` times
+    /// the grain size in either direction, and the time until the next grain
+    /// is spawned is varied by up to half of `jitter` times the usual
+    /// interval in either direction.
+    pub fn jitter<W: Into<Wave>>(mut self, jitter: W) -> Grains {
+        self.jitter = jitter.into();
+        self
+    }
+
+    /// Sets the amplitude envelope applied to each grain.
+    pub fn envelope(mut self, envelope: GrainEnvelope) -> Grains {
+        self.envelope = envelope;
+        self
+    }
+
+    /// Sets the seed for the random variation, so that different granular
+    /// generators can vary differently from one another.
+    pub fn seed(mut self, seed: u64) -> Grains {
+        self.seed = seed;
+        self
+    }
+}
+
+// ========================================================================= //
+
+impl Wave {
+    /// Creates a granular synthesizer, which plays many short, overlapping
+    /// grains of the `source` buffer, each windowed by an envelope, under
+    /// control of the given settings.  Moving the position slowly through
+    /// the source stretches it in time without changing its pitch, while a
+    /// fixed position freezes it into a sustained texture.
+    ///
+    /// Each grain uses the values of the settings' waveforms at the moment it
+    /// is spawned, and keeps them for its whole duration.  Overlapping
+    /// grains are summed, so the output gets louder as the density and grain
+    /// size increase.  Once any of the settings' waveforms finishes, no more
+    /// grains are spawned, and the new waveform finishes as soon as the
+    /// remaining grains have played out.
+    pub fn granular<B: Into<SampleBuffer>>(source: B, grains: Grains)
+                                           -> Wave {
+        Wave::new(Box::new(Granular {
+            source: source.into(),
+            rng: Rng::new(grains.seed),
+            settings: grains,
+            until_next: 0.0,
+            active: Vec::new(),
+            spawning: true,
+        }))
+    }
+}
+
+// ========================================================================= //
+
+/// A granular synthesizer.
+#[derive(Clone)]
+struct Granular {
+    source: SampleBuffer,
+    settings: Grains,
+    rng: Rng,
+    until_next: f32,
+    active: Vec<Grain>,
+    spawning: bool,
+}
+
+impl Granular {
+    /// Advances the settings' waveforms by one step, returning their current
+    /// values, or `None` if any of them has finished.
+    fn controls(&mut self, step: f32) -> Option<Controls> {
+        Some(Controls {
+            density: self.settings.density.next(step)?,
+            size: self.settings.size.next(step)?,
+            position: self.settings.position.next(step)?,
+            pitch: self.settings.pitch.next(step)?,
+            jitter: self.settings.jitter.next(step)?.clamp(0.0, 1.0),
+        })
+    }
+
+    /// Spawns a new grain, returning the time until the next grain should be
+    /// spawned.
+    fn spawn(&mut self, controls: Controls, step: f32) -> f32 {
+        let Controls { density, size, position, pitch, jitter } = controls;
+        let offset = jitter * size * self.rng.next_signed();
+        let spread = 1.0 + 0.5 * jitter * self.rng.next_signed();
+        if size > 0.0 && self.active.len() < GRANULAR_MAX_GRAINS {
+            let start = position.clamp(0.0, 1.0) * self.source.duration() +
+                        offset;
+            self.active.push(Grain {
+                time: start as f64,
+                rate: pitch,
+                age: 0.0,
+                duration: size,
+            });
+        }
+        (spread / density).max(step)
+    }
+}
+
+impl WaveGen for Granular {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        if self.spawning {
+            match self.controls(step) {
+                Some(controls) => {
+                    if controls.density > 0.0 {
+                        while self.until_next <= 0.0 {
+                            self.until_next += self.spawn(controls, step);
+                            if step <= 0.0 {
+                                // Time isn't passing, so one grain will do.
+                                break;
+                            }
+                        }
+                    } else {
+                        // Spawn a grain as soon as the density is positive.
+                        self.until_next = 0.0;
+                    }
+                }
+                None => self.spawning = false,
+            }
+        }
+        if !self.spawning && self.active.is_empty() {
+            return None;
+        }
+        self.until_next -= step;
+        let mut output = 0.0;
+        for grain in self.active.iter_mut() {
+            let gain = self.settings.envelope.gain(grain.age / grain.duration);
+            output += gain * self.source.value_at(grain.time);
+            grain.time += (grain.rate * step) as f64;
+            grain.age += step;
+        }
+        self.active.retain(|grain| grain.age < grain.duration);
+        Some(output)
+    }
+
+    fn reset(&mut self) {
+        self.settings.density.reset();
+        self.settings.size.reset();
+        self.settings.position.reset();
+        self.settings.pitch.reset();
+        self.settings.jitter.reset();
+        self.rng = Rng::new(self.settings.seed);
+        self.until_next = 0.0;
+        self.active.clear();
+        self.spawning = true;
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+}
+
+/// The values of a granular synthesizer's settings at one step.
+#[derive(Clone, Copy)]
+struct Controls {
+    density: f32,
+    size: f32,
+    position: f32,
+    pitch: f32,
+    jitter: f32,
+}
+
+/// One grain of a granular synthesizer.
+#[derive(Clone)]
+struct Grain {
+    /// The current read position in the source, in seconds.
+    time: f64,
+    /// The playback rate.
+    rate: f32,
+    /// The time since the grain started, in seconds.
+    age: f32,
+    /// The grain's total duration, in seconds.
+    duration: f32,
+}
+
+// ========================================================================= //
+
+#[cfg(test)]
+mod tests {
+    use super::{GRANULAR_INIT_SEED, GrainEnvelope, Grains};
+    use super::super::{SampleBuffer, Wave, WaveGen, render};
+
+    #[test]
+    fn grain_envelopes() {
+        let envelopes = [GrainEnvelope::Rectangular,
+                         GrainEnvelope::Triangle,
+                         GrainEnvelope::Hann,
+                         GrainEnvelope::Gaussian,
+                         GrainEnvelope::Trapezoid];
+        for &envelope in envelopes.iter() {
+            assert_approx!(1.0, envelope.gain(0.5));
+            assert!(envelope.gain(0.25) <= 1.0);
+            assert_approx!(envelope.gain(0.2), envelope.gain(0.8));
+        }
+        assert_approx!(0.0, GrainEnvelope::Hann.gain(0.0));
+        assert_approx!(0.5, GrainEnvelope::Triangle.gain(0.25));
+        assert_approx!(1.0, GrainEnvelope::Trapezoid.gain(0.25));
+    }
+
+    #[test]
+    fn granular_grains() {
+        let source = SampleBuffer::new(vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0],
+                                       1.0);
+        // One rectangular grain every two seconds, each three seconds long,
+        // read from halfway through the source.
+        let mut grains = Grains::new(0.5, 3.0, 0.5)
+            .envelope(GrainEnvelope::Rectangular);
+        let wave = Wave::granular(source.clone(), grains.clone()).take(6.0);
+        assert_eq!(vec![4.0, 5.0, 10.0, 5.0, 10.0, 5.0],
+                   render(wave, 1.0, 100));
+        // Grains can be played at a different pitch, even backwards.
+        grains = grains.pitch(-1.0);
+        let wave = Wave::granular(source.clone(), grains.clone()).take(4.0);
+        assert_eq!(vec![4.0, 3.0, 6.0, 3.0], render(wave, 1.0, 100));
+        // Once a setting finishes, the remaining grains play out.
+        grains = grains.pitch(Wave::from(2.0).take(1.0));
+        let wave = Wave::granular(source, grains);
+        assert_eq!(vec![4.0, 6.0, 0.0], render(wave, 1.0, 100));
+        // With a density of zero, no grains are spawned.
+        let grains = Grains::new(0.0, 3.0, 0.5);
+        let wave = Wave::granular((vec![1.0; 4], 1.0), grains).take(3.0);
+        assert_eq!(vec![0.0, 0.0, 0.0], render(wave, 1.0, 100));
+        // A zero step doesn't spawn grains forever, even at any density.
+        let grains = Grains::new(f32::INFINITY, 3.0, 0.5);
+        let wave = Wave::granular((vec![1.0; 4], 1.0), grains);
+        assert_eq!(3, render(wave, 0.0, 3).len());
+    }
+
+    #[test]
+    fn granular_jitter() {
+        let source = SampleBuffer::render(Wave::sine(440.0), 8000.0, 1.0);
+        let mut grains = Grains::new(100.0, 0.05, 0.5);
+        let plain = render(Wave::granular(source.clone(), grains.clone()),
+                           1.0 / 8000.0,
+                           800);
+        grains = grains.jitter(1.0);
+        let jittered = render(Wave::granular(source.clone(), grains.clone()),
+                              1.0 / 8000.0,
+                              800);
+        assert!(plain != jittered);
+        // Jitter is deterministic for a given seed, and reset restores it.
+        let mut wave = Wave::granular(source.clone(), grains.clone());
+        let samples: Vec<f32> =
+            (0..800).map(|_| wave.next(1.0 / 8000.0).unwrap()).collect();
+        assert_eq!(jittered, samples);
+        wave.reset();
+        assert_eq!(jittered, render(wave, 1.0 / 8000.0, 800));
+        let grains = grains.seed(GRANULAR_INIT_SEED + 1);
+        assert!(jittered !=
+                render(Wave::granular(source, grains), 1.0 / 8000.0, 800));
+    }
+}
+
+// ========================================================================= //
@@ -0,0 +1,242 @@
+//! Low-frequency oscillators, for modulating other waveforms.
+
+use super::{Phase, Rng, Sample, Wave, WaveGen};
+
+// ========================================================================= //
+
+/// The shape of a low-frequency oscillator created by `Wave::lfo`.  Each
+/// shape starts its cycle at the same point as the corresponding ordinary
+/// oscillator (e.g. a sine LFO starts in the middle of its range, rising).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LfoShape {
+    /// A sine wave, starting in the middle of the range.
+    Sine,
+    /// A triangle wave, starting at the minimum.
+    Triangle,
+    /// A square wave, starting at the maximum.
+    Square,
+    /// A rising sawtooth wave, starting at the minimum.
+    Saw,
+    /// A falling sawtooth wave, starting at the maximum.
+    SawDown,
+    /// A random value, chosen uniformly within the range at the start of
+    /// each cycle and held until the next cycle (i.e. "sample and hold").
+    /// The values are pseudo-random, determined by the given seed.
+    SampleAndHold(u64),
+}
+
+// ========================================================================= //
+
+impl Wave {
+    /// Creates a low-frequency oscillator of the given shape, whose rate
+    /// (in cycles per second) over time is controlled by the `rate`
+    /// waveform (which may be a constant), and whose output ranges from
+    /// `min` to `max`.  For example, a bipolar LFO ranges from -1 to 1, and
+    /// a unipolar one from 0 to 1, while `Wave::lfo(LfoShape::Sine, 5.0,
+    /// 430.0, 450.0)` can be used directly as the frequency of another
+    /// oscillator.
+    pub fn lfo<R: Into<Wave>>(shape: LfoShape, rate: R, min: f32, max: f32)
+                              -> Wave {
+        let rate = rate.into();
+        let bipolar = match shape {
+            LfoShape::Sine => Wave::sine(rate),
+            LfoShape::Triangle => Wave::triangle(rate, 0.5),
+            LfoShape::Square => Wave::pulse(rate, 0.5),
+            LfoShape::Saw => Wave::saw(rate),
+            LfoShape::SawDown => Wave::saw_down(rate),
+            LfoShape::SampleAndHold(seed) => {
+                Wave::new(Box::new(SampleAndHold {
+                    rate,
+                    phase: Phase::default(),
+                    seed,
+                    rng: Rng::new(seed),
+                    value: None,
+                }))
+            }
+        };
+        scale_bipolar(bipolar, min, max)
+    }
+
+    /// Creates a low-frequency oscillator synced to a tempo of `bpm` beats
+    /// per minute, completing one cycle every `beats` beats (e.g. 0.25 for
+    /// a cycle every sixteenth note, in 4/4 time), and ranging from `min` to
+    /// `max`.  The first cycle starts on the first beat.
+    pub fn lfo_synced(shape: LfoShape, bpm: f32, beats: f32, min: f32,
+                      max: f32)
+                      -> Wave {
+        Wave::lfo(shape, bpm / (60.0 * beats), min, max)
+    }
+
+    /// Creates a frequency waveform for vibrato: the frequency given by the
+    /// `freq` waveform (which may be a constant), raised and lowered by up
+    /// to `depth_cents` cents by a sine LFO at `rate` hertz (which may also
+    /// be a waveform).  The result can be used as the frequency of an
+    /// oscillator, e.g. `Wave::sine(Wave::vibrato(440.0, 5.0, 20.0))`.
+    pub fn vibrato<F, R>(freq: F, rate: R, depth_cents: f32) -> Wave
+        where F: Into<Wave>,
+              R: Into<Wave>
+    {
+        let lfo = Wave::lfo(LfoShape::Sine, rate, -depth_cents, depth_cents);
+        freq.into().offset_cents(lfo)
+    }
+
+    /// Creates a gain waveform for tremolo: a sine LFO at `rate` hertz
+    /// (which may be a waveform), ranging from full volume (1) down to `1 -
+    /// depth` and starting at full volume.  Multiply another waveform by
+    /// the result to apply the tremolo, e.g. `Wave::sine(440.0) *
+    /// Wave::tremolo(6.0, 0.5)`.
+    pub fn tremolo<R: Into<Wave>>(rate: R, depth: f32) -> Wave {
+        let bipolar = Wave::sine(rate).with_initial_phase(0.25);
+        scale_bipolar(bipolar, 1.0 - depth, 1.0)
+    }
+}
+
+// ========================================================================= //
+
+/// Maps a waveform ranging from -1 to 1 onto the range from `min` to `max`.
+fn scale_bipolar(wave: Wave, min: f32, max: f32) -> Wave {
+    wave * (0.5 * (max - min)) + 0.5 * (max + min)
+}
+
+/// A sample-and-hold oscillator, with an amplitude of 1.
+#[derive(Clone)]
+struct SampleAndHold {
+    rate: Wave,
+    phase: Phase,
+    seed: u64,
+    rng: Rng,
+    /// The value for the current cycle, if one has been chosen yet.
+    value: Option<Sample>,
+}
+
+impl WaveGen for SampleAndHold {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        let rate = self.rate.next(step)?;
+        self.phase.advance(rate, step)?;
+        let value = match self.value {
+            Some(value) => value,
+            None => self.rng.next_signed(),
+        };
+        // Choose a new value at the start of each cycle.
+        self.value = if self.phase.wrap.is_some() {
+            None
+        } else {
+            Some(value)
+        };
+        Some(value)
+    }
+
+    fn reset(&mut self) {
+        self.rate.reset();
+        self.phase.reset();
+        self.rng = Rng::new(self.seed);
+        self.value = None;
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+
+    fn phase_wrap(&self) -> Option<f32> { self.phase.wrap }
+
+    fn sync(&mut self, elapsed: f32) {
+        self.phase.sync(elapsed);
+        self.value = None;
+    }
+
+    fn set_initial_phase(&mut self, phase: f32) {
+        self.phase.set_initial(phase);
+    }
+
+    fn set_phase_offset(&mut self, offset: Wave) {
+        self.phase.offset = Some(offset);
+    }
+}
+
+// ========================================================================= //
+
+#[cfg(test)]
+mod tests {
+    use super::LfoShape;
+    use super::super::{Wave, WaveGen, render};
+
+    #[test]
+    fn lfo_ranges() {
+        let shapes = [LfoShape::Sine,
+                      LfoShape::Triangle,
+                      LfoShape::Square,
+                      LfoShape::Saw,
+                      LfoShape::SawDown,
+                      LfoShape::SampleAndHold(1)];
+        for &shape in shapes.iter() {
+            let samples = render(Wave::lfo(shape, 2.0, 430.0, 450.0),
+                                 0.01,
+                                 500);
+            let min = samples.iter().cloned().fold(f32::MAX, f32::min);
+            let max = samples.iter().cloned().fold(f32::MIN, f32::max);
+            assert!(min >= 430.0 - 1e-3 && max <= 450.0 + 1e-3,
+                    "{:?}: {} to {}",
+                    shape,
+                    min,
+                    max);
+            assert!(max - min > 10.0, "{:?}: {} to {}", shape, min, max);
+        }
+        assert_eq!(vec![0.0, 0.5, 1.0, 0.5, 0.0],
+                   render(Wave::lfo(LfoShape::Triangle, 1.0, 0.0, 1.0),
+                          0.25,
+                          5));
+        assert_eq!(vec![1.0, 1.0, 1.0, 1.0, -1.0],
+                   render(Wave::lfo(LfoShape::Square, 1.0, -1.0, 1.0),
+                          0.125,
+                          5));
+        assert_eq!(vec![10.0, 7.5, 5.0, 2.5, 10.0],
+                   render(Wave::lfo(LfoShape::SawDown, 0.5, 0.0, 10.0),
+                          0.5,
+                          5));
+    }
+
+    #[test]
+    fn lfo_synced() {
+        // At 120 BPM, a one-beat LFO completes a cycle every half second.
+        let samples = render(Wave::lfo_synced(LfoShape::Saw,
+                                              120.0,
+                                              1.0,
+                                              0.0,
+                                              1.0),
+                             0.125,
+                             5);
+        assert_eq!(vec![0.0, 0.25, 0.5, 0.75, 0.0], samples);
+    }
+
+    #[test]
+    fn sample_and_hold() {
+        let shape = LfoShape::SampleAndHold(7);
+        let mut wave = Wave::lfo(shape, 1.0, 0.0, 1.0);
+        let samples = render(wave.clone(), 0.25, 12);
+        // Each value is held for a whole cycle.
+        for cycle in samples.chunks(4) {
+            assert!(cycle.iter().all(|&value| value == cycle[0]));
+        }
+        assert!(samples[0] != samples[4] && samples[4] != samples[8]);
+        // Reset restarts the same sequence, and other seeds differ.
+        wave.next(0.25);
+        wave.reset();
+        assert_eq!(samples, render(wave, 0.25, 12));
+        let other = Wave::lfo(LfoShape::SampleAndHold(8), 1.0, 0.0, 1.0);
+        assert!(samples != render(other, 0.25, 12));
+    }
+
+    #[test]
+    fn vibrato_and_tremolo() {
+        let samples = render(Wave::vibrato(440.0, 1.0, 100.0), 0.25, 4);
+        assert_approx!(440.0, samples[0]);
+        assert!((samples[1] - 440.0 * 2f32.powf(1.0 / 12.0)).abs() < 1e-3);
+        assert_approx!(440.0, samples[2]);
+        assert!((samples[3] - 440.0 * 2f32.powf(-1.0 / 12.0)).abs() < 1e-3);
+        let samples = render(Wave::tremolo(1.0, 0.5), 0.25, 4);
+        assert_approx!(1.0, samples[0]);
+        assert_approx!(0.75, samples[1]);
+        assert_approx!(0.5, samples[2]);
+        assert_approx!(0.75, samples[3]);
+    }
+}
+
+// ========================================================================= //
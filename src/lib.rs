@@ -2,9 +2,86 @@
 
 #![warn(missing_docs)]
 
+use std::f32;
 use std::f32::consts::PI;
 use std::ops::{Add, Mul};
 
+#[cfg(test)]
+macro_rules! assert_approx {
+    ($left:expr, $right:expr) => ({
+        match (&($left), &($right)) {
+            (left_val, right_val) => {
+                if (*left_val - *right_val).abs() > 1e-6 {
+                    panic!("assertion failed: `(left ~= right)` \
+                            (left: `{:?}`, right: `{:?}`)",
+                           left_val, right_val)
+                }
+            }
+        }
+    })
+}
+
+/// Renders up to `max` samples of the given waveform (which may be borrowed,
+/// to keep using it afterwards), stopping early if it finishes.
+#[cfg(test)]
+fn render<W: std::borrow::BorrowMut<Wave>>(mut wave: W, step: f32,
+                                           max: usize)
+                                           -> Vec<Sample> {
+    let wave = wave.borrow_mut();
+    (0..max).map_while(|_| wave.next(step)).collect()
+}
+
+/// Returns the peak amplitude of the given waveform over `count` samples,
+/// after first letting it settle for the same number of samples.
+#[cfg(test)]
+fn settled_peak(mut wave: Wave, step: f32, count: usize) -> f32 {
+    for _ in 0..count {
+        wave.next(step).unwrap();
+    }
+    (0..count)
+        .map(|_| wave.next(step).unwrap().abs())
+        .fold(0.0, f32::max)
+}
+
+mod buffer;
+mod chip;
+mod drums;
+mod effects;
+mod envelope;
+mod filter;
+mod fm;
+mod formant;
+mod granular;
+mod lfo;
+mod modulation;
+mod noise;
+mod pitch;
+mod pluck;
+mod rng;
+mod sfxr;
+mod transpose;
+mod unison;
+mod wavetable;
+
+pub use buffer::{REVERSED_MAX_DURATION, SampleBuffer};
+pub use chip::{GbWaveChannel, LfsrMode, NES_CPU_CLOCK, NES_NOISE_PERIODS,
+               NesDmc, NesPulse, NesSweep, NesTriangle, Sn76489Noise,
+               Sn76489Tone};
+pub use effects::EffectCategory;
+pub use envelope::{Breakpoint, Curve, Gate};
+pub use fm::{FM_NUM_ALGORITHMS, FM_NUM_OPERATORS, FmOperator};
+pub use formant::Vowel;
+pub use granular::{GrainEnvelope, Grains};
+pub use lfo::LfoShape;
+pub use noise::NoiseColor;
+pub use pitch::{A4_FREQ, A4_MIDI, PitchError, Tuning, cents_to_ratio,
+                freq_to_midi, midi_to_freq, note_to_freq, note_to_midi,
+                ratio_to_cents, semitones_to_ratio};
+pub use rng::Rng;
+pub use sfxr::{SfxrError, SfxrParams, SfxrWaveType};
+pub use transpose::PitchMode;
+pub use wavetable::Wavetable;
+
 // ========================================================================= //
 
 /// One sample value from a waveform.
@@ -24,6 +101,45 @@ pub trait WaveGen: Send {
 
     /// Clones the `WaveGen` and wraps it as a `Wave`.
     fn as_wave(&self) -> Wave;
+
+    /// If this is a periodic oscillator whose phase wrapped around to the
+    /// start of a new cycle during the most recent call to `next()`, returns
+    /// how long ago (in seconds) the wrap happened; otherwise, returns
+    /// `None`.  Combinators that play another waveform without changing its
+    /// timing (such as `delayed`, `looped`, `repeated`, `crop`, `then`,
+    /// `adshr`, and sums and products, which use their left-hand operand)
+    /// pass this on from the waveform they play.  The default implementation
+    /// always returns `None`.
+    fn phase_wrap(&self) -> Option<f32> { None }
+
+    /// If this is a periodic oscillator, resets its phase to the start of a
+    /// cycle, as though that had happened `elapsed` seconds ago (which should
+    /// be no more than the most recent step size).  This is used to implement
+    /// hard sync.  Combinators pass this on in the same way as `phase_wrap`.
+    /// The default implementation does nothing.
+    fn sync(&mut self, _elapsed: f32) {}
+
+    /// If this is a periodic oscillator, sets the phase (measured in cycles,
+    /// from 0 to 1) at which it starts, both now and whenever it is reset.
+    /// Combinators pass this on in the same way as `phase_wrap`, except that
+    /// a sequence passes it on to every waveform in it.  The default
+    /// implementation does nothing.
+    fn set_initial_phase(&mut self, _phase: f32) {}
+
+    /// If this is a periodic oscillator, makes its phase be offset at each
+    /// moment by the value (measured in cycles) of the given waveform.
+    /// Combinators pass this on in the same way as `set_initial_phase`.  The
+    /// default implementation does nothing.
+    fn set_phase_offset(&mut self, _offset: Wave) {}
+
+    /// If the most recent call to `next()` returned `None`, returns how long
+    /// ago (in seconds) the waveform actually finished, which may have been
+    /// partway through the previous step.  This is used by sequences to hand
+    /// the unused part of that step on to the next waveform.  Waveforms of a
+    /// known duration (such as `crop`, `take`, and `adshr` envelopes) report
+    /// this, and `delayed`, sequences, and products pass it on.  The default
+    /// implementation always returns 0.
+    fn overshoot(&self) -> f32 { 0.0 }
 }
 
 impl WaveGen for Sample {
@@ -50,11 +166,42 @@ impl Wave {
         Wave { generator: generator }
     }
 
+    /// Creates an additive synthesis wave, consisting of a sum of sine waves
+    /// (partials), whose base frequency over time is controlled by the `freq`
+    /// waveform (which may be a constant).  Each partial is given as a pair
+    /// of a frequency ratio (relative to the base frequency) and an amplitude
+    /// waveform (which may be a constant, or e.g. an envelope).  Partials
+    /// whose frequency is at or above the Nyquist frequency (given the
+    /// current step size) are silenced to avoid aliasing, and a partial whose
+    /// amplitude waveform finishes is treated as silent from then on.  The
+    /// wave finishes once `freq` finishes or all of the partials have
+    /// finished.
+    pub fn additive<F, I, A>(freq: F, partials: I) -> Wave
+        where F: Into<Wave>,
+              I: IntoIterator<Item = (f32, A)>,
+              A: Into<Wave>
+    {
+        let partials = partials.into_iter()
+            .map(|(ratio, amplitude)| (ratio, amplitude.into()))
+            .collect();
+        Wave::new(Box::new(AdditiveWave::new(freq.into(), partials)))
+    }
+
     /// Creates a noise wave, with an amplitude of 1, whose frequency over time
     /// is controlled by the input waveform (which may be a constant).  The
     /// input frequency values are measured in hertz (cycles per second).
     pub fn noise<F: Into<Wave>>(freq: F) -> Wave {
-        Wave::new(Box::new(NoiseWave::new(freq.into())))
+        Wave::new(Box::new(NoiseWave::new(freq.into(), NOISE_INIT_SEED)))
+    }
+
+    /// Like `noise`, but uses the given seed for the random sequence rather
+    /// than a fixed default, so that different noise waves can sound
+    /// different from one another.  For a given seed and sequence of step
+    /// sizes, the output is bit-identical on every platform (see
+    /// [`Rng`](struct.Rng.html)).
+    pub fn noise_seeded<F: Into<Wave>>(freq: F, seed: u64) -> Wave {
+        let init_bits = Rng::new(seed).next_u64();
+        Wave::new(Box::new(NoiseWave::new(freq.into(), init_bits)))
     }
 
     /// Creates a pulse wave whose frequency over time is controlled by the
@@ -62,15 +209,60 @@ impl Wave {
     /// `duty` waveform (either or both of which may be constants).  The input
     /// frequency values are measured in hertz (cycles per second); the input
     /// duty values should be between 0 and 1 (with 0.5 being a square wave).
+    /// A negative frequency plays the wave backwards.
     pub fn pulse<F: Into<Wave>, D: Into<Wave>>(freq: F, duty: D) -> Wave {
         Wave::new(Box::new(PulseWave::new(freq.into(), duty.into())))
     }
 
+    /// Creates a rising sawtooth wave, with an amplitude of 1, whose frequency
+    /// over time is controlled by the input waveform (which may be a
+    /// constant).  The input frequency values are measured in hertz (cycles
+    /// per second).
+    pub fn saw<F: Into<Wave>>(freq: F) -> Wave {
+        Wave::new(Box::new(SawWave::new(freq.into(), true)))
+    }
+
+    /// Creates a falling sawtooth (or ramp-down) wave, with an amplitude of 1,
+    /// whose frequency over time is controlled by the input waveform (which
+    /// may be a constant).  The input frequency values are measured in hertz
+    /// (cycles per second).
+    pub fn saw_down<F: Into<Wave>>(freq: F) -> Wave {
+        Wave::new(Box::new(SawWave::new(freq.into(), false)))
+    }
+
+    /// Creates a waveform that plays each of the given waveforms in turn,
+    /// starting each one as soon as the previous one finishes, even if that
+    /// was partway through a step (see `WaveGen::overshoot`).  The resulting
+    /// waveform finishes when the last one does.
+    pub fn sequence<I>(waves: I) -> Wave
+        where I: IntoIterator,
+              I::Item: Into<Wave>
+    {
+        let waves = waves.into_iter().map(Into::into).collect();
+        Wave::new(Box::new(Sequence::new(waves)))
+    }
+
     /// Creates a sine wave, with an amplitude of 1, whose frequency over time
     /// is controlled by the input waveform (which may be a constant).  The
     /// input frequency values are measured in hertz (cycles per second).
     pub fn sine<F: Into<Wave>>(freq: F) -> Wave {
-        Wave::new(Box::new(SineWave::new(freq.into())))
+        Wave::new(Box::new(SineWave::new(freq.into(), None)))
+    }
+
+    /// Creates a phase-modulated sine wave, with an amplitude of 1, whose
+    /// frequency over time is controlled by the `freq` waveform, and whose
+    /// phase is offset at each moment by the value of the `phase_mod`
+    /// waveform (either or both of which may be constants).  The input
+    /// frequency values are measured in hertz (cycles per second); the input
+    /// phase values are measured in radians, so that using another sine wave
+    /// multiplied by `I` as the `phase_mod` gives FM synthesis with a
+    /// modulation index of `I`.  Only the sine wave takes a phase
+    /// modulation input in radians; other oscillators can be
+    /// phase-modulated in cycles with `with_phase_offset`.
+    pub fn sine_pm<F: Into<Wave>, P: Into<Wave>>(freq: F, phase_mod: P)
+                                                 -> Wave {
+        let phase_mod = Some(phase_mod.into());
+        Wave::new(Box::new(SineWave::new(freq.into(), phase_mod)))
     }
 
     /// Creates a wave with the shape of a parabola; it's initial value is
@@ -87,11 +279,25 @@ impl Wave {
     /// `duty` waveform (either or both of which may be constants).  The input
     /// frequency values are measured in hertz (cycles per second); the input
     /// duty values should be between 0 and 1 (with 0.5 being a triangle wave
-    /// and 0 or 1 being a sawtooth wave).
+    /// and 0 or 1 being a sawtooth wave).  A negative frequency plays the
+    /// wave backwards.
     pub fn triangle<F: Into<Wave>, D: Into<Wave>>(freq: F, duty: D) -> Wave {
         Wave::new(Box::new(TriangleWave::new(freq.into(), duty.into())))
     }
 
+    /// Returns a new waveform consisting of the portion of this one between
+    /// `start` and `end` seconds.  The new waveform finishes after `end -
+    /// start` seconds, or sooner if this one finishes first.
+    pub fn crop(self, start: f32, end: f32) -> Wave {
+        self.crop_faded(start, end, 0.0)
+    }
+
+    /// Like `crop`, but also applies a linear fade-in and fade-out, each
+    /// lasting `fade_time` seconds, to avoid clicks at the edges.
+    pub fn crop_faded(self, start: f32, end: f32, fade_time: f32) -> Wave {
+        Wave::new(Box::new(Cropped::new(self, start, end, fade_time)))
+    }
+
     /// Returns a new waveform that delays this one for a duration.
     pub fn delayed(self, seconds: f32) -> Wave {
         Wave::new(Box::new(Delayed::new(self, seconds)))
@@ -105,19 +311,51 @@ impl Wave {
         Wave::new(Box::new(Repeated::new(self, num_times)))
     }
 
+    /// Returns a new waveform that starts partway into this one, skipping
+    /// over the first `seconds` of it.
+    pub fn skip(self, seconds: f32) -> Wave {
+        self.crop(seconds, f32::INFINITY)
+    }
+
+    /// Returns a new waveform that plays only the first `seconds` of this
+    /// one, and then finishes.
+    pub fn take(self, seconds: f32) -> Wave { self.crop(0.0, seconds) }
+
+    /// Returns a new waveform that plays this one, and then plays `other`
+    /// once this one finishes.
+    pub fn then<W: Into<Wave>>(self, other: W) -> Wave {
+        Wave::sequence(vec![self, other.into()])
+    }
+
     /// Returns a new waveform by constraining this one with an ADSHR (attack,
     /// decay, sustain, hold, release) envelope.
     pub fn adshr(self, attack_time: f32, decay_time: f32, sustain_level: f32,
                  hold_time: f32, release_time: f32)
                  -> Wave {
-        Wave::new(Box::new(Adshr {
+        self.adshr_curved(attack_time,
+                          decay_time,
+                          sustain_level,
+                          hold_time,
+                          release_time,
+                          (Curve::Linear, Curve::Linear, Curve::Linear))
+    }
+
+    /// Like `adshr`, but with the given curves (for the attack, decay, and
+    /// release segments, respectively) in place of straight linear ramps.
+    pub fn adshr_curved(self, attack_time: f32, decay_time: f32,
+                        sustain_level: f32, hold_time: f32,
+                        release_time: f32, curves: (Curve, Curve, Curve))
+                        -> Wave {
+        let envelope = Wave::new(Box::new(Adshr {
             attack_time: attack_time,
             decay_time: decay_time,
             sustain_level: sustain_level,
             hold_time: hold_time,
             release_time: release_time,
+            curves: curves,
             time: 0.0,
-        })) * self
+        }));
+        self * envelope
     }
 }
 
@@ -159,6 +397,123 @@ impl WaveGen for Wave {
     fn reset(&mut self) { self.generator.reset(); }
 
     fn as_wave(&self) -> Wave { self.generator.as_wave() }
+
+    fn phase_wrap(&self) -> Option<f32> { self.generator.phase_wrap() }
+
+    fn sync(&mut self, elapsed: f32) { self.generator.sync(elapsed); }
+
+    fn set_initial_phase(&mut self, phase: f32) {
+        self.generator.set_initial_phase(phase);
+    }
+
+    fn set_phase_offset(&mut self, offset: Wave) {
+        self.generator.set_phase_offset(offset);
+    }
+
+    fn overshoot(&self) -> f32 { self.generator.overshoot() }
+}
+
+// ========================================================================= //
+
+/// A variable-frequency sum of sine wave partials.
+#[derive(Clone)]
+struct AdditiveWave {
+    freq: Wave,
+    partials: Vec<Partial>,
+    /// The initial phase and phase offset of the fundamental, in cycles;
+    /// each partial's are scaled by its frequency ratio.
+    initial_phase: f32,
+    phase_offset: Option<Wave>,
+}
+
+/// One partial of an `AdditiveWave`.
+#[derive(Clone)]
+struct Partial {
+    ratio: f32,
+    amplitude: Wave,
+    phase: f32,
+    finished: bool,
+}
+
+impl AdditiveWave {
+    fn new(freq: Wave, partials: Vec<(f32, Wave)>) -> AdditiveWave {
+        let partials = partials.into_iter()
+            .map(|(ratio, amplitude)| {
+                Partial {
+                    ratio,
+                    amplitude,
+                    phase: 0.0,
+                    finished: false,
+                }
+            })
+            .collect();
+        AdditiveWave {
+            freq,
+            partials,
+            initial_phase: 0.0,
+            phase_offset: None,
+        }
+    }
+
+    /// Returns each partial to its initial phase.
+    fn restart_phases(&mut self) {
+        for partial in self.partials.iter_mut() {
+            let phase = partial.ratio * self.initial_phase;
+            partial.phase = phase.rem_euclid(1.0);
+        }
+    }
+}
+
+impl WaveGen for AdditiveWave {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        let freq = self.freq.next(step)?;
+        let offset = match self.phase_offset {
+            Some(ref mut offset) => offset.next(step)?,
+            None => 0.0,
+        };
+        let mut total = 0.0;
+        let mut any_playing = self.partials.is_empty();
+        for partial in self.partials.iter_mut().filter(|p| !p.finished) {
+            let amplitude = match partial.amplitude.next(step) {
+                Some(amplitude) => amplitude,
+                None => {
+                    partial.finished = true;
+                    continue;
+                }
+            };
+            any_playing = true;
+            let delta = freq * partial.ratio * step;
+            if delta.abs() < 0.5 {
+                let phase = partial.phase + partial.ratio * offset;
+                total += amplitude * (2.0 * PI * phase).sin();
+            }
+            partial.phase = (partial.phase + delta) % 1.0;
+        }
+        if any_playing { Some(total) } else { None }
+    }
+
+    fn reset(&mut self) {
+        self.freq.reset();
+        for partial in self.partials.iter_mut() {
+            partial.amplitude.reset();
+            partial.finished = false;
+        }
+        self.restart_phases();
+        if let Some(ref mut offset) = self.phase_offset {
+            offset.reset();
+        }
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+
+    fn set_initial_phase(&mut self, phase: f32) {
+        self.initial_phase = phase;
+        self.restart_phases();
+    }
+
+    fn set_phase_offset(&mut self, offset: Wave) {
+        self.phase_offset = Some(offset);
+    }
 }
 
 // ========================================================================= //
@@ -172,18 +527,22 @@ struct Adshr {
     sustain_level: f32,
     hold_time: f32,
     release_time: f32,
+    curves: (Curve, Curve, Curve),
     time: f32,
 }
 
 impl WaveGen for Adshr {
     fn next(&mut self, step: f32) -> Option<Sample> {
         let time = self.time;
+        let (attack_curve, decay_curve, release_curve) = self.curves;
         let value = if time < self.attack_time {
-            time / self.attack_time
+            attack_curve.interpolate(0.0, 1.0, time / self.attack_time)
         } else {
             let time = time - self.attack_time;
             if time < self.decay_time {
-                1.0 - (time / self.decay_time) * (1.0 - self.sustain_level)
+                decay_curve.interpolate(1.0,
+                                        self.sustain_level,
+                                        time / self.decay_time)
             } else {
                 let time = time - self.decay_time;
                 if time < self.hold_time {
@@ -191,7 +550,9 @@ impl WaveGen for Adshr {
                 } else {
                     let time = time - self.hold_time;
                     if time < self.release_time {
-                        (1.0 - time / self.release_time) * self.sustain_level
+                        release_curve.interpolate(self.sustain_level,
+                                                  0.0,
+                                                  time / self.release_time)
                     } else {
                         return None;
                     }
@@ -205,6 +566,122 @@ impl WaveGen for Adshr {
     fn reset(&mut self) { self.time = 0.0 }
 
     fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+
+    fn overshoot(&self) -> f32 {
+        let duration = self.attack_time + self.decay_time + self.hold_time +
+                       self.release_time;
+        (self.time - duration).max(0.0)
+    }
+}
+
+// ========================================================================= //
+
+/// A waveform consisting of a portion of some other waveform, with optional
+/// linear fades at either end.
+#[derive(Clone)]
+struct Cropped {
+    wave: Wave,
+    start: f32,
+    duration: f32,
+    fade_time: f32,
+    time: f32,
+    skipped: bool,
+}
+
+impl Cropped {
+    fn new(wave: Wave, start: f32, end: f32, fade_time: f32) -> Cropped {
+        let start = start.max(0.0);
+        // A waveform can never be skipped forever, so an infinite start
+        // leaves nothing to play.
+        let (start, duration) = if start.is_finite() {
+            (start, (end - start).max(0.0))
+        } else {
+            (0.0, 0.0)
+        };
+        Cropped {
+            wave,
+            start,
+            duration,
+            fade_time: fade_time.max(0.0).min(0.5 * duration),
+            time: 0.0,
+            skipped: false,
+        }
+    }
+
+    /// Advances the underlying wave past the skipped portion, using steps no
+    /// larger than `step`.  Returns false if the wave finished while
+    /// skipping.
+    fn skip(&mut self, step: f32) -> bool {
+        if step <= 0.0 {
+            // Small steps would never get anywhere, so skip in one go.
+            return self.start <= 0.0 || self.wave.next(self.start).is_some();
+        }
+        // Count whole steps, since adding up many small steps can stop
+        // making progress once the total is large enough.
+        let num_steps = (self.start as f64 / step as f64).floor() as u64;
+        for _ in 0..num_steps {
+            if self.wave.next(step).is_none() {
+                return false;
+            }
+        }
+        let remainder = self.start as f64 - num_steps as f64 * step as f64;
+        if remainder > 0.0 && self.wave.next(remainder as f32).is_none() {
+            return false;
+        }
+        true
+    }
+}
+
+impl WaveGen for Cropped {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        if !self.skipped {
+            self.skipped = true;
+            if !self.skip(step) {
+                self.time = self.duration;
+            }
+        }
+        let time = self.time;
+        if time >= self.duration {
+            return None;
+        }
+        let sample = self.wave.next(step)?;
+        self.time += step;
+        if self.fade_time > 0.0 {
+            let fade_in = time / self.fade_time;
+            let fade_out = (self.duration - time) / self.fade_time;
+            Some(sample * fade_in.min(fade_out).min(1.0))
+        } else {
+            Some(sample)
+        }
+    }
+
+    fn reset(&mut self) {
+        self.wave.reset();
+        self.time = 0.0;
+        self.skipped = false;
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+
+    fn phase_wrap(&self) -> Option<f32> { self.wave.phase_wrap() }
+
+    fn sync(&mut self, elapsed: f32) { self.wave.sync(elapsed); }
+
+    fn set_initial_phase(&mut self, phase: f32) {
+        self.wave.set_initial_phase(phase);
+    }
+
+    fn set_phase_offset(&mut self, offset: Wave) {
+        self.wave.set_phase_offset(offset);
+    }
+
+    fn overshoot(&self) -> f32 {
+        if self.time >= self.duration {
+            self.time - self.duration
+        } else {
+            self.wave.overshoot()
+        }
+    }
 }
 
 // ========================================================================= //
@@ -247,6 +724,20 @@ impl WaveGen for Delayed {
     }
 
     fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+
+    fn phase_wrap(&self) -> Option<f32> { self.wave.phase_wrap() }
+
+    fn sync(&mut self, elapsed: f32) { self.wave.sync(elapsed); }
+
+    fn set_initial_phase(&mut self, phase: f32) {
+        self.wave.set_initial_phase(phase);
+    }
+
+    fn set_phase_offset(&mut self, offset: Wave) {
+        self.wave.set_phase_offset(offset);
+    }
+
+    fn overshoot(&self) -> f32 { self.wave.overshoot() }
 }
 
 // ========================================================================= //
@@ -268,6 +759,18 @@ impl WaveGen for Looped {
     fn reset(&mut self) { self.wave.reset(); }
 
     fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+
+    fn phase_wrap(&self) -> Option<f32> { self.wave.phase_wrap() }
+
+    fn sync(&mut self, elapsed: f32) { self.wave.sync(elapsed); }
+
+    fn set_initial_phase(&mut self, phase: f32) {
+        self.wave.set_initial_phase(phase);
+    }
+
+    fn set_phase_offset(&mut self, offset: Wave) {
+        self.wave.set_phase_offset(offset);
+    }
 }
 
 // ========================================================================= //
@@ -278,15 +781,19 @@ const NOISE_INIT_SEED: u64 = 123456789123456789;
 #[derive(Clone)]
 struct NoiseWave {
     freq: Wave,
-    seed: u64,
+    init_bits: u64,
+    rng: Rng,
+    bits: u64,
     phase: f32,
 }
 
 impl NoiseWave {
-    fn new(freq: Wave) -> NoiseWave {
+    fn new(freq: Wave, init_bits: u64) -> NoiseWave {
         NoiseWave {
-            freq: freq,
-            seed: NOISE_INIT_SEED,
+            freq,
+            init_bits,
+            rng: Rng::from_state(init_bits),
+            bits: init_bits,
             phase: 0.0,
         }
     }
@@ -294,21 +801,15 @@ impl NoiseWave {
 
 impl WaveGen for NoiseWave {
     fn next(&mut self, step: f32) -> Option<Sample> {
-        let freq = match self.freq.next(step) {
-            Some(freq) => freq,
-            None => return None,
-        };
+        let freq = self.freq.next(step)?;
         let phase = self.phase;
-        let seed = self.seed;
+        let bits = self.bits;
         self.phase += 2.0 * freq * step;
         if self.phase >= 64.0 {
             self.phase %= 64.0;
-            // This is a simple linear congruential generator, using parameters
-            // suggested by http://nuclear.llnl.gov/CNP/rng/rngman/node4.html
-            self.seed = self.seed.overflowing_mul(2862933555777941757).0;
-            self.seed = self.seed.overflowing_add(3037000493u64).0;
+            self.bits = self.rng.next_u64();
         }
-        Some(if ((seed >> (phase as i32)) & 1) != 0 {
+        Some(if ((bits >> (phase as i32)) & 1) != 0 {
             1.0
         } else {
             -1.0
@@ -317,7 +818,8 @@ impl WaveGen for NoiseWave {
 
     fn reset(&mut self) {
         self.freq.reset();
-        self.seed = NOISE_INIT_SEED;
+        self.rng = Rng::from_state(self.init_bits);
+        self.bits = self.init_bits;
         self.phase = 0.0;
     }
 
@@ -326,6 +828,64 @@ impl WaveGen for NoiseWave {
 
 // ========================================================================= //
 
+/// The phase of a periodic oscillator, measured in cycles (from 0 to 1),
+/// which keeps track of when it wraps around.  A negative frequency runs the
+/// cycle backwards, wrapping from 0 around to 1.
+#[derive(Clone, Default)]
+struct Phase {
+    value: f32,
+    initial: f32,
+    offset: Option<Wave>,
+    freq: f32,
+    wrap: Option<f32>,
+}
+
+impl Phase {
+    /// Returns the current phase (including any phase offset), and then
+    /// advances it by one step at the given frequency.  Returns `None` if the
+    /// phase offset waveform has finished.
+    fn advance(&mut self, freq: f32, step: f32) -> Option<f32> {
+        let offset = match self.offset {
+            Some(ref mut offset) => offset.next(step)?,
+            None => 0.0,
+        };
+        let phase = self.value;
+        let next = phase + freq * step;
+        self.value = next.rem_euclid(1.0);
+        self.freq = freq;
+        self.wrap = if next >= 1.0 {
+            Some((next - next.floor()) / freq)
+        } else if next < 0.0 {
+            Some((self.value - 1.0) / freq)
+        } else {
+            None
+        };
+        Some((phase + offset).rem_euclid(1.0))
+    }
+
+    /// Restarts the cycle, as though that had happened `elapsed` seconds ago.
+    fn sync(&mut self, elapsed: f32) {
+        self.value = (self.freq * elapsed).rem_euclid(1.0);
+        self.wrap = Some(elapsed);
+    }
+
+    fn set_initial(&mut self, phase: f32) {
+        self.initial = phase.rem_euclid(1.0);
+        self.value = self.initial;
+    }
+
+    fn reset(&mut self) {
+        self.value = self.initial;
+        self.freq = 0.0;
+        self.wrap = None;
+        if let Some(ref mut offset) = self.offset {
+            offset.reset();
+        }
+    }
+}
+
+// ========================================================================= //
+
 /// A waveform consisting of the product of two other waveforms.
 #[derive(Clone)]
 struct Product {
@@ -352,6 +912,23 @@ impl WaveGen for Product {
     }
 
     fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+
+    fn phase_wrap(&self) -> Option<f32> { self.wave1.phase_wrap() }
+
+    fn sync(&mut self, elapsed: f32) { self.wave1.sync(elapsed); }
+
+    fn set_initial_phase(&mut self, phase: f32) {
+        self.wave1.set_initial_phase(phase);
+    }
+
+    fn set_phase_offset(&mut self, offset: Wave) {
+        self.wave1.set_phase_offset(offset);
+    }
+
+    fn overshoot(&self) -> f32 {
+        // Whichever input finished first did so longest ago.
+        self.wave1.overshoot().max(self.wave2.overshoot())
+    }
 }
 
 // ========================================================================= //
@@ -361,7 +938,7 @@ impl WaveGen for Product {
 struct PulseWave {
     freq: Wave,
     duty: Wave,
-    phase: f32,
+    phase: Phase,
 }
 
 impl PulseWave {
@@ -369,7 +946,7 @@ impl PulseWave {
         PulseWave {
             freq: freq,
             duty: duty,
-            phase: 0.0,
+            phase: Phase::default(),
         }
     }
 }
@@ -384,8 +961,7 @@ impl WaveGen for PulseWave {
             Some(duty) => duty,
             None => return None,
         };
-        let phase = self.phase;
-        self.phase = (self.phase + freq * step) % 1.0;
+        let phase = self.phase.advance(freq, step)?;
         Some(if phase < duty {
             1.0
         } else {
@@ -396,10 +972,22 @@ impl WaveGen for PulseWave {
     fn reset(&mut self) {
         self.freq.reset();
         self.duty.reset();
-        self.phase = 0.0;
+        self.phase.reset();
     }
 
     fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+
+    fn phase_wrap(&self) -> Option<f32> { self.phase.wrap }
+
+    fn sync(&mut self, elapsed: f32) { self.phase.sync(elapsed); }
+
+    fn set_initial_phase(&mut self, phase: f32) {
+        self.phase.set_initial(phase);
+    }
+
+    fn set_phase_offset(&mut self, offset: Wave) {
+        self.phase.offset = Some(offset);
+    }
 }
 
 // ========================================================================= //
@@ -441,43 +1029,197 @@ impl WaveGen for Repeated {
     }
 
     fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+
+    fn phase_wrap(&self) -> Option<f32> { self.wave.phase_wrap() }
+
+    fn sync(&mut self, elapsed: f32) { self.wave.sync(elapsed); }
+
+    fn set_initial_phase(&mut self, phase: f32) {
+        self.wave.set_initial_phase(phase);
+    }
+
+    fn set_phase_offset(&mut self, offset: Wave) {
+        self.wave.set_phase_offset(offset);
+    }
+}
+
+// ========================================================================= //
+
+/// A variable-frequency sawtooth wave, with an amplitude of 1.
+#[derive(Clone)]
+struct SawWave {
+    freq: Wave,
+    rising: bool,
+    phase: Phase,
+}
+
+impl SawWave {
+    fn new(freq: Wave, rising: bool) -> SawWave {
+        SawWave {
+            freq,
+            rising,
+            phase: Phase::default(),
+        }
+    }
+}
+
+impl WaveGen for SawWave {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        let freq = self.freq.next(step)?;
+        let phase = self.phase.advance(freq, step)?;
+        let value = 2.0 * phase - 1.0;
+        Some(if self.rising { value } else { -value })
+    }
+
+    fn reset(&mut self) {
+        self.freq.reset();
+        self.phase.reset();
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+
+    fn phase_wrap(&self) -> Option<f32> { self.phase.wrap }
+
+    fn sync(&mut self, elapsed: f32) { self.phase.sync(elapsed); }
+
+    fn set_initial_phase(&mut self, phase: f32) {
+        self.phase.set_initial(phase);
+    }
+
+    fn set_phase_offset(&mut self, offset: Wave) {
+        self.phase.offset = Some(offset);
+    }
+}
+
+// ========================================================================= //
+
+/// A waveform consisting of several other waveforms, played one after another.
+#[derive(Clone)]
+struct Sequence {
+    waves: Vec<Wave>,
+    index: usize,
+    /// How long ago the most recently finished wave finished.
+    leftover: f32,
+}
+
+impl Sequence {
+    fn new(waves: Vec<Wave>) -> Sequence {
+        Sequence {
+            waves,
+            index: 0,
+            leftover: 0.0,
+        }
+    }
+}
+
+impl WaveGen for Sequence {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        let mut leftover = 0.0;
+        while self.index < self.waves.len() {
+            // When one wave finishes, the next one starts where it left off,
+            // partway through the previous step, so that no gap is left
+            // between them.
+            let wave = &mut self.waves[self.index];
+            if leftover <= 0.0 || wave.next(leftover).is_some() {
+                if let Some(sample) = wave.next(step) {
+                    return Some(sample);
+                }
+                leftover = wave.overshoot();
+            }
+            self.leftover = leftover;
+            self.index += 1;
+        }
+        None
+    }
+
+    fn reset(&mut self) {
+        for wave in self.waves.iter_mut() {
+            wave.reset();
+        }
+        self.index = 0;
+        self.leftover = 0.0;
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+
+    fn phase_wrap(&self) -> Option<f32> {
+        self.waves.get(self.index).and_then(|wave| wave.phase_wrap())
+    }
+
+    fn sync(&mut self, elapsed: f32) {
+        if let Some(wave) = self.waves.get_mut(self.index) {
+            wave.sync(elapsed);
+        }
+    }
+
+    fn set_initial_phase(&mut self, phase: f32) {
+        for wave in self.waves.iter_mut() {
+            wave.set_initial_phase(phase);
+        }
+    }
+
+    fn set_phase_offset(&mut self, offset: Wave) {
+        for wave in self.waves.iter_mut() {
+            wave.set_phase_offset(offset.clone());
+        }
+    }
+
+    fn overshoot(&self) -> f32 { self.leftover }
 }
 
 // ========================================================================= //
 
-/// A variable-frequency sine wave, with an amplitude of 1.
+/// A variable-frequency, optionally phase-modulated sine wave, with an
+/// amplitude of 1.
 #[derive(Clone)]
 struct SineWave {
     freq: Wave,
-    phase: f32,
+    phase_mod: Option<Wave>,
+    phase: Phase,
 }
 
 impl SineWave {
-    fn new(freq: Wave) -> SineWave {
+    fn new(freq: Wave, phase_mod: Option<Wave>) -> SineWave {
         SineWave {
-            freq: freq,
-            phase: 0.0,
+            freq,
+            phase_mod,
+            phase: Phase::default(),
         }
     }
 }
 
 impl WaveGen for SineWave {
     fn next(&mut self, step: f32) -> Option<Sample> {
-        let freq = match self.freq.next(step) {
-            Some(freq) => freq,
-            None => return None,
+        let freq = self.freq.next(step)?;
+        let phase_mod = match self.phase_mod {
+            Some(ref mut phase_mod) => phase_mod.next(step)?,
+            None => 0.0,
         };
-        let phase = self.phase;
-        self.phase = (self.phase + freq * step) % 1.0;
-        Some((2.0 * PI * phase).sin())
+        let phase = self.phase.advance(freq, step)?;
+        Some((2.0 * PI * phase + phase_mod).sin())
     }
 
     fn reset(&mut self) {
         self.freq.reset();
-        self.phase = 0.0;
+        if let Some(ref mut phase_mod) = self.phase_mod {
+            phase_mod.reset();
+        }
+        self.phase.reset();
     }
 
     fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+
+    fn phase_wrap(&self) -> Option<f32> { self.phase.wrap }
+
+    fn sync(&mut self, elapsed: f32) { self.phase.sync(elapsed); }
+
+    fn set_initial_phase(&mut self, phase: f32) {
+        self.phase.set_initial(phase);
+    }
+
+    fn set_phase_offset(&mut self, offset: Wave) {
+        self.phase.offset = Some(offset);
+    }
 }
 
 // ========================================================================= //
@@ -542,6 +1284,18 @@ impl WaveGen for Sum {
     }
 
     fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+
+    fn phase_wrap(&self) -> Option<f32> { self.wave1.phase_wrap() }
+
+    fn sync(&mut self, elapsed: f32) { self.wave1.sync(elapsed); }
+
+    fn set_initial_phase(&mut self, phase: f32) {
+        self.wave1.set_initial_phase(phase);
+    }
+
+    fn set_phase_offset(&mut self, offset: Wave) {
+        self.wave1.set_phase_offset(offset);
+    }
 }
 
 // ========================================================================= //
@@ -551,7 +1305,7 @@ impl WaveGen for Sum {
 struct TriangleWave {
     freq: Wave,
     duty: Wave,
-    phase: f32,
+    phase: Phase,
 }
 
 impl TriangleWave {
@@ -559,7 +1313,7 @@ impl TriangleWave {
         TriangleWave {
             freq: freq,
             duty: duty,
-            phase: 0.0,
+            phase: Phase::default(),
         }
     }
 }
@@ -571,12 +1325,11 @@ impl WaveGen for TriangleWave {
             None => return None,
         };
         let duty = match self.duty.next(step) {
-            Some(duty) => duty,
+            Some(duty) => duty.clamp(0.0, 1.0),
             None => return None,
         };
-        let phase = self.phase;
-        self.phase = (self.phase + freq * step) % 1.0;
-        Some(if phase < duty {
+        let phase = self.phase.advance(freq, step)?;
+        Some(if phase < duty || duty >= 1.0 {
             2.0 * phase / duty - 1.0
         } else {
             1.0 - 2.0 * (phase - duty) / (1.0 - duty)
@@ -586,10 +1339,22 @@ impl WaveGen for TriangleWave {
     fn reset(&mut self) {
         self.freq.reset();
         self.duty.reset();
-        self.phase = 0.0;
+        self.phase.reset();
     }
 
     fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+
+    fn phase_wrap(&self) -> Option<f32> { self.phase.wrap }
+
+    fn sync(&mut self, elapsed: f32) { self.phase.sync(elapsed); }
+
+    fn set_initial_phase(&mut self, phase: f32) {
+        self.phase.set_initial(phase);
+    }
+
+    fn set_phase_offset(&mut self, offset: Wave) {
+        self.phase.offset = Some(offset);
+    }
 }
 
 // ========================================================================= //
@@ -599,18 +1364,130 @@ mod tests {
     use std::f32::consts::SQRT_2;
     use super::*;
 
-    macro_rules! assert_approx {
-        ($left:expr, $right:expr) => ({
-            match (&($left), &($right)) {
-                (left_val, right_val) => {
-                    if (*left_val - *right_val).abs() > 1e-6 {
-                        panic!("assertion failed: `(left ~= right)` \
-                                (left: `{:?}`, right: `{:?}`)",
-                               left_val, right_val)
-                    }
-                }
-            }
-        })
+    #[test]
+    fn additive() {
+        let step = 1.0 / 16.0;
+        let mut wave = Wave::additive(1.0, vec![(1.0, Wave::from(1.0)),
+                                                (2.0, Wave::from(0.5)),
+                                                (3.0, Wave::from(0.25)
+                                                    .take(0.5))]);
+        let mut sum = Wave::sine(1.0) + Wave::sine(2.0) * 0.5 +
+                      (Wave::sine(3.0) * 0.25).take(0.5);
+        for _ in 0..16 {
+            assert_approx!(sum.next(step).unwrap(), wave.next(step).unwrap());
+        }
+        // With a step of 0.3 seconds, the Nyquist frequency is 1.67 Hz, so
+        // only the first partial is played.
+        let mut wave = Wave::additive(1.0, vec![(1.0, 1.0), (2.0, 1.0)]);
+        let mut sine = Wave::sine(1.0);
+        for _ in 0..10 {
+            assert_approx!(sine.next(0.3).unwrap(), wave.next(0.3).unwrap());
+        }
+        let mut wave = Wave::additive(1.0, vec![(1.0, Wave::from(1.0)
+                                                     .take(2.0))]);
+        assert!(wave.next(1.0).is_some());
+        assert!(wave.next(1.0).is_some());
+        assert_eq!(None, wave.next(1.0));
+    }
+
+    #[test]
+    fn crop() {
+        let mut wave = Wave::slide(0.0, 1.0, 0.0).crop(2.0, 5.0);
+        assert_approx!(2.0, wave.next(1.0).unwrap());
+        assert_approx!(3.0, wave.next(1.0).unwrap());
+        assert_approx!(4.0, wave.next(1.0).unwrap());
+        assert_eq!(None, wave.next(1.0));
+        wave.reset();
+        assert_approx!(2.0, wave.next(1.0).unwrap());
+        let mut wave = Wave::slide(0.0, 1.0, 0.0).skip(1.5).take(2.0).looped();
+        assert_approx!(1.5, wave.next(1.0).unwrap());
+        assert_approx!(2.5, wave.next(1.0).unwrap());
+        assert_approx!(1.5, wave.next(1.0).unwrap());
+        assert_approx!(2.5, wave.next(1.0).unwrap());
+        let mut wave = Wave::from(1.0).crop_faded(10.0, 14.0, 2.0);
+        assert_approx!(0.0, wave.next(1.0).unwrap());
+        assert_approx!(0.5, wave.next(1.0).unwrap());
+        assert_approx!(1.0, wave.next(1.0).unwrap());
+        assert_approx!(0.5, wave.next(1.0).unwrap());
+        assert_eq!(None, wave.next(1.0));
+        // A zero step still skips (rather than looping forever).
+        let mut wave = Wave::slide(0.0, 1.0, 0.0).skip(1.5);
+        assert_approx!(1.5, wave.next(0.0).unwrap());
+        assert_approx!(1.5, wave.next(1.0).unwrap());
+        let mut wave = Wave::slide(0.0, 1.0, 0.0).skip(0.0);
+        assert_approx!(0.0, wave.next(0.0).unwrap());
+        // Long skips at small steps still finish.
+        let mut wave = Wave::sine(100.0).skip(600.0);
+        assert!(wave.next(1.0 / 44100.0).is_some());
+        // An infinite start skips everything, and an undefined one nothing.
+        let mut wave = Wave::from(1.0).skip(f32::INFINITY);
+        assert_eq!(None, wave.next(1.0));
+        let mut wave = Wave::slide(0.0, 1.0, 0.0).skip(f32::NAN);
+        assert_approx!(0.0, wave.next(1.0).unwrap());
+    }
+
+    #[test]
+    fn negative_frequency() {
+        // A negative frequency runs each oscillator's cycle backwards,
+        // staying within its usual range.
+        let step = 0.25;
+        let mut pulse = Wave::pulse(-1.0, 0.5);
+        let mut triangle = Wave::triangle(-1.0, 0.5);
+        let mut saw = Wave::saw(-1.0);
+        for &(p, t, w) in [(1.0, -1.0, -1.0),
+                           (-1.0, 0.0, 0.5),
+                           (-1.0, 1.0, 0.0),
+                           (1.0, 0.0, -0.5),
+                           (1.0, -1.0, -1.0)]
+            .iter() {
+            assert_approx!(p, pulse.next(step).unwrap());
+            assert_approx!(t, triangle.next(step).unwrap());
+            assert_approx!(w, saw.next(step).unwrap());
+        }
+    }
+
+    #[test]
+    fn saw_wave() {
+        let step = 0.25;
+        let mut wave = Wave::saw(1.0);
+        let mut down = Wave::saw_down(1.0);
+        for &value in [-1.0, -0.5, 0.0, 0.5, -1.0, -0.5].iter() {
+            assert_approx!(value, wave.next(step).unwrap());
+            assert_approx!(-value, down.next(step).unwrap());
+        }
+    }
+
+    #[test]
+    fn sequence() {
+        let hold = |value: f32, time: f32| {
+            Wave::from(value).adshr(0.0, 0.0, 1.0, time, 0.0)
+        };
+        let mut wave = hold(1.0, 0.0).then(2.0);
+        // The first wave is empty, so the second one starts immediately.
+        assert_eq!(Some(2.0), wave.next(1.0));
+        assert_eq!(Some(2.0), wave.next(1.0));
+        let mut wave = Wave::sequence(vec![hold(1.0, 2.0),
+                                           hold(2.0, 0.0),
+                                           hold(3.0, 1.0)]);
+        assert_eq!(Some(1.0), wave.next(1.0));
+        assert_eq!(Some(1.0), wave.next(1.0));
+        assert_eq!(Some(3.0), wave.next(1.0));
+        assert_eq!(None, wave.next(1.0));
+        wave.reset();
+        assert_eq!(Some(1.0), wave.next(1.0));
+        // The next wave starts partway through the step in which the
+        // previous one finished.
+        let mut wave = Wave::sequence(vec![Wave::from(1.0).take(0.3),
+                                           Wave::slide(0.0, 1.0, 0.0)
+                                               .take(0.3),
+                                           Wave::slide(0.0, -1.0, 0.0)
+                                               .take(0.3)]);
+        assert_eq!(Some(1.0), wave.next(0.25));
+        assert_eq!(Some(1.0), wave.next(0.25));
+        assert_approx!(0.2, wave.next(0.25).unwrap());
+        assert_approx!(-0.15, wave.next(0.25).unwrap());
+        assert_eq!(None, wave.next(0.25));
+        assert_approx!(0.1, wave.overshoot());
     }
 
     #[test]
@@ -650,6 +1527,26 @@ mod tests {
         assert_approx!(-0.75, wave.next(step).unwrap());
     }
 
+    #[test]
+    fn triangle_wave_extreme_duty() {
+        // A duty of 1 gives a rising sawtooth, and a duty of 0 a falling one;
+        // out-of-range duties are clamped.
+        let step = 0.25;
+        let mut rising = Wave::triangle(1.0, 1.0);
+        let mut clamped = Wave::triangle(1.0, 1.5);
+        let mut falling = Wave::triangle(1.0, -1.0);
+        for &value in [-1.0, -0.5, 0.0, 0.5, -1.0].iter() {
+            assert_approx!(value, rising.next(step).unwrap());
+            assert_approx!(value, clamped.next(step).unwrap());
+            assert_approx!(-value, falling.next(step).unwrap());
+        }
+        // Even at the very end of a cycle (here reached by running
+        // backwards), the value is finite.
+        let mut wave = Wave::triangle(-1.0, 1.0);
+        wave.next(1e-9);
+        assert!(wave.next(step).unwrap().is_finite());
+    }
+
     #[test]
     fn wave_clone() {
         let step = 1.0 / 500.0;
@@ -0,0 +1,421 @@
+//! Oscillator phase control, and interactions between oscillators: hard
+//! sync, and ring and amplitude modulation.
+
+use super::{Sample, Wave, WaveGen};
+
+// ========================================================================= //
+
+impl Wave {
+    /// Sets the phase (measured in cycles, from 0 to 1) at which this
+    /// oscillator starts, both now and whenever it is reset, and returns it.
+    /// For example, a sine wave with an initial phase of 0.25 is a cosine
+    /// wave.  Every periodic oscillator supports this: the sine, pulse,
+    /// triangle, sawtooth, wavetable, and LFO oscillators; `additive` and
+    /// `fm_voice` (whose partials or operators each start at the matching
+    /// point in their own cycles); `unison` (whose voices are all shifted
+    /// from their starting phases); and the emulated chip tone channels.  It
+    /// also reaches an oscillator inside the combinators listed under
+    /// `WaveGen::phase_wrap`, such as `(Wave::sine(f) * 0.5)` or
+    /// `Wave::sine(f).adshr(..)`.  Noise, envelopes, and other waveforms
+    /// without a repeating cycle have no phase to set.
+    pub fn with_initial_phase(mut self, phase: f32) -> Wave {
+        self.set_initial_phase(phase);
+        self
+    }
+
+    /// Makes this oscillator's phase be offset at each moment by the value
+    /// of the `offset` waveform (which may be a constant), measured in
+    /// cycles, and returns it.  For example, two copies of an LFO with a
+    /// phase offset of 0.25 between them are in quadrature.  The oscillator
+    /// finishes if the `offset` waveform does.  This is supported by the same
+    /// oscillators and combinators as `with_initial_phase`.
+    pub fn with_phase_offset<W: Into<Wave>>(mut self, offset: W) -> Wave {
+        self.set_phase_offset(offset.into());
+        self
+    }
+
+    /// Returns a new waveform that hard-syncs this oscillator (the slave) to
+    /// the `master` oscillator: whenever the master's phase wraps around to
+    /// the start of a new cycle, the slave's phase is reset to the start of
+    /// its cycle too.  The master's output is otherwise ignored.  This works
+    /// with any oscillators that report phase wraps and support syncing (see
+    /// `WaveGen::phase_wrap` and `WaveGen::sync`), such as `Wave::sine`,
+    /// `Wave::pulse`, `Wave::triangle`, and `Wave::wavetable`.  The new
+    /// waveform finishes when the slave does; if the master finishes first,
+    /// no more syncs happen.
+    pub fn hard_sync<W: Into<Wave>>(self, master: W) -> Wave {
+        Wave::new(Box::new(HardSync {
+            slave: self,
+            master: master.into(),
+        }))
+    }
+
+    /// Returns a new waveform that is 1 for the first sample of each cycle of
+    /// this oscillator (including the very first sample), and 0 otherwise.
+    /// This can be used e.g. as a gate to retrigger an envelope once per
+    /// cycle of a low-frequency oscillator.  The new waveform finishes when
+    /// this one does.
+    pub fn cycle_triggers(self) -> Wave {
+        Wave::new(Box::new(CycleTriggers {
+            wave: self,
+            triggered: true,
+        }))
+    }
+
+    /// Returns a new waveform that ring-modulates this one with the
+    /// `modulator` waveform (which may be a constant), by multiplying the
+    /// two together.  Ring modulating two tones produces their sum and
+    /// difference frequencies, without either original frequency.  The new
+    /// waveform finishes when either input does.
+    pub fn ring_mod<W: Into<Wave>>(self, modulator: W) -> Wave {
+        self * modulator
+    }
+
+    /// Returns a new waveform that amplitude-modulates this one with the
+    /// `modulator` waveform, which should range from -1 to 1, and whose
+    /// modulation depth over time is controlled by the `depth` waveform
+    /// (either or both of which may be constants).  At a depth of 0, this
+    /// wave is unchanged; at a depth of 1, its amplitude is scaled by a
+    /// factor varying from 0 (when the modulator is at -1) to 1 (when the
+    /// modulator is at 1).  Unlike ring modulation, the original frequencies
+    /// are kept, along with the sum and difference frequencies.  The new
+    /// waveform finishes when any of the inputs does.
+    pub fn amplitude_mod<M, D>(self, modulator: M, depth: D) -> Wave
+        where M: Into<Wave>,
+              D: Into<Wave>
+    {
+        Wave::new(Box::new(AmplitudeMod {
+            wave: self,
+            modulator: modulator.into(),
+            depth: depth.into(),
+        }))
+    }
+}
+
+// ========================================================================= //
+
+/// An oscillator that is hard-synced to another.
+#[derive(Clone)]
+struct HardSync {
+    slave: Wave,
+    master: Wave,
+}
+
+impl WaveGen for HardSync {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        let value = self.slave.next(step)?;
+        if self.master.next(step).is_some() {
+            if let Some(elapsed) = self.master.phase_wrap() {
+                self.slave.sync(elapsed);
+            }
+        }
+        Some(value)
+    }
+
+    fn reset(&mut self) {
+        self.slave.reset();
+        self.master.reset();
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+
+    fn phase_wrap(&self) -> Option<f32> { self.slave.phase_wrap() }
+
+    fn sync(&mut self, elapsed: f32) { self.slave.sync(elapsed); }
+
+    fn set_initial_phase(&mut self, phase: f32) {
+        self.slave.set_initial_phase(phase);
+    }
+
+    fn set_phase_offset(&mut self, offset: Wave) {
+        self.slave.set_phase_offset(offset);
+    }
+}
+
+// ========================================================================= //
+
+/// A waveform that is 1 at the start of each cycle of an oscillator.
+#[derive(Clone)]
+struct CycleTriggers {
+    wave: Wave,
+    triggered: bool,
+}
+
+impl WaveGen for CycleTriggers {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        self.wave.next(step)?;
+        let value = if self.triggered { 1.0 } else { 0.0 };
+        self.triggered = self.wave.phase_wrap().is_some();
+        Some(value)
+    }
+
+    fn reset(&mut self) {
+        self.wave.reset();
+        self.triggered = true;
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+}
+
+// ========================================================================= //
+
+/// A waveform amplitude-modulated by another.
+#[derive(Clone)]
+struct AmplitudeMod {
+    wave: Wave,
+    modulator: Wave,
+    depth: Wave,
+}
+
+impl WaveGen for AmplitudeMod {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        let value = self.wave.next(step)?;
+        let modulator = self.modulator.next(step)?;
+        let depth = self.depth.next(step)?;
+        Some(value * (1.0 + 0.5 * depth * (modulator - 1.0)))
+    }
+
+    fn reset(&mut self) {
+        self.wave.reset();
+        self.modulator.reset();
+        self.depth.reset();
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+
+    fn phase_wrap(&self) -> Option<f32> { self.wave.phase_wrap() }
+
+    fn sync(&mut self, elapsed: f32) { self.wave.sync(elapsed); }
+
+    fn set_initial_phase(&mut self, phase: f32) {
+        self.wave.set_initial_phase(phase);
+    }
+
+    fn set_phase_offset(&mut self, offset: Wave) {
+        self.wave.set_phase_offset(offset);
+    }
+}
+
+// ========================================================================= //
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{PI, SQRT_2};
+    use super::super::{FmOperator, NesTriangle, Sn76489Tone, Wave, WaveGen,
+                       render};
+
+    #[test]
+    fn phase_wrap() {
+        let mut wave = Wave::pulse(0.3, 0.5);
+        let mut wraps = Vec::new();
+        for _ in 0..10 {
+            wave.next(1.0);
+            wraps.push(wave.phase_wrap());
+        }
+        // The phase goes 0, 0.3, 0.6, 0.9, 0.2 (wrapping 0.2/0.3 seconds
+        // ago), and so on.
+        assert_eq!(None, wraps[2]);
+        assert_approx!(0.2 / 0.3, wraps[3].unwrap());
+        assert_eq!(3, wraps.iter().filter(|wrap| wrap.is_some()).count());
+        // Waves that aren't oscillators never wrap.
+        let mut wave = Wave::from(1.0);
+        wave.next(1.0);
+        assert_eq!(None, wave.phase_wrap());
+    }
+
+    #[test]
+    fn initial_phase() {
+        let step = 1.0 / 8.0;
+        let mut wave = Wave::sine(1.0).with_initial_phase(0.25);
+        let expected = [1.0, 0.5 * SQRT_2, 0.0, -0.5 * SQRT_2, -1.0];
+        for &value in expected.iter() {
+            assert_approx!(value, wave.next(step).unwrap());
+        }
+        // The initial phase is kept across resets.
+        wave.reset();
+        assert_approx!(1.0, wave.next(step).unwrap());
+        // It reaches oscillators inside combinators.
+        let cosine = render(Wave::sine(1.0).with_initial_phase(0.25), step, 8);
+        let samples = render((Wave::sine(1.0) * 0.5).with_initial_phase(0.25),
+                             step,
+                             8);
+        for (&expected, &actual) in cosine.iter().zip(samples.iter()) {
+            assert_approx!(0.5 * expected, actual);
+        }
+        let wave = Wave::sine(1.0).adshr(0.0, 0.0, 1.0, 2.0, 0.0).delayed(0.0);
+        assert_eq!(cosine, render(wave.with_initial_phase(0.25), step, 8));
+        let wave = Wave::sine(1.0).take(0.5).then(Wave::sine(1.0)).looped();
+        let samples = render(wave.with_initial_phase(0.25), step, 8);
+        assert_eq!(cosine[..4], samples[..4]);
+        assert_eq!(cosine[..4], samples[4..]);
+    }
+
+    #[test]
+    fn initial_phase_oscillators() {
+        let step = 1.0 / 8.0;
+        // Each partial of an additive wave or operator of an FM voice starts
+        // at the matching point in its own cycle.
+        let partials = vec![(1.0, 1.0), (2.0, 0.5)];
+        let expected: Vec<f32> = (0..8)
+            .map(|index| {
+                let time = index as f32 * step + 0.125;
+                (2.0 * PI * time).sin() + 0.5 * (4.0 * PI * time).sin()
+            })
+            .collect();
+        let wave = Wave::additive(1.0, partials).with_initial_phase(0.125);
+        for (&expected, actual) in expected.iter()
+            .zip(render(wave, step, 8)) {
+            assert_approx!(expected, actual);
+        }
+        let operators = [FmOperator::new(1.0, 0.0),
+                         FmOperator::new(1.0, 0.0),
+                         FmOperator::new(1.0, 0.0),
+                         FmOperator::new(2.0, 1.0)];
+        let wave = Wave::fm_voice(1.0, 0, operators, 0.0)
+            .with_initial_phase(0.125);
+        let cosine = Wave::sine(2.0).with_initial_phase(0.25);
+        for (expected, actual) in render(cosine, step, 8)
+            .into_iter()
+            .zip(render(wave, step, 8)) {
+            assert_approx!(expected, actual);
+        }
+        // Unison voices are all shifted from their own starting phases.
+        let wave = Wave::unison(1.0, 3, 0.0, |freq, phase| {
+            Wave::saw(freq).with_initial_phase(phase)
+        });
+        let shifted = render(wave.with_initial_phase(0.5), step, 8);
+        let expected = render(Wave::unison(1.0, 3, 0.0, |freq, phase| {
+                                  Wave::saw(freq)
+                                      .with_initial_phase(phase + 0.5)
+                              }),
+                              step,
+                              8);
+        assert_eq!(expected, shifted);
+        // The chip channels start partway through their sequences.
+        let triangle = NesTriangle::new(440.0);
+        let wave = Wave::nes_triangle(triangle).with_initial_phase(0.5);
+        let samples = render(wave, 1e-9, 2);
+        assert_approx!(-1.0, samples[0]);
+        let tone = Sn76489Tone::new(440.0, 0);
+        let mut wave = Wave::sn76489_tone(tone).with_initial_phase(0.5);
+        assert!(wave.next(1e-9).unwrap() < 0.0);
+        wave.reset();
+        assert!(wave.next(1e-9).unwrap() < 0.0);
+        let mut wave = Wave::sn76489_tone(tone).with_phase_offset(0.5);
+        assert!(wave.next(1e-9).unwrap() < 0.0);
+    }
+
+    #[test]
+    fn phase_offset() {
+        let step = 1.0 / 8.0;
+        // Offsetting an LFO by a quarter cycle puts it in quadrature.
+        let mut sine = Wave::sine(1.0).with_phase_offset(0.25);
+        let mut cosine = Wave::sine(1.0).with_initial_phase(0.25);
+        for _ in 0..16 {
+            assert_approx!(cosine.next(step).unwrap(),
+                           sine.next(step).unwrap());
+        }
+        // The offset can vary over time.
+        let offset = Wave::slide(0.0, 1.0, 0.0);
+        let mut saw = Wave::saw(1.0).with_phase_offset(offset);
+        let expected = [-1.0, -0.5, 0.0, 0.5, -1.0];
+        for &value in expected.iter() {
+            assert_approx!(value, saw.next(step).unwrap());
+        }
+        // The oscillator finishes when the offset does.
+        let mut wave = Wave::pulse(1.0, 0.5)
+            .with_phase_offset(Wave::from(0.0).take(0.25));
+        assert!(wave.next(step).is_some());
+        assert!(wave.next(step).is_some());
+        assert_eq!(None, wave.next(step));
+    }
+
+    #[test]
+    fn hard_sync() {
+        let step = 1.0 / 128.0;
+        // A 20 Hz sawtooth synced to an 8 Hz master restarts every 16
+        // samples, even though its own period is 6.4 samples.
+        let slave = Wave::triangle(20.0, 1.0);
+        let samples = render(slave.hard_sync(Wave::sine(8.0)), step, 64);
+        for cycle in 1..4 {
+            for index in 0..16 {
+                assert_eq!(samples[index], samples[16 * cycle + index]);
+            }
+        }
+        let unsynced = render(Wave::triangle(20.0, 1.0), step, 64);
+        assert_eq!(samples[..16], unsynced[..16]);
+        assert!(samples[16..] != unsynced[16..]);
+        // Syncing takes into account how long ago the master wrapped: here,
+        // the master wraps halfway through the fourth step, so the slave
+        // restarts half a step before the fifth sample.
+        let slave = Wave::triangle(0.25, 1.0);
+        let samples = render(slave.hard_sync(Wave::sine(1.0 / 3.5)), 1.0, 5);
+        assert_approx!(0.5, samples[3]);
+        assert_approx!(-0.75, samples[4]);
+    }
+
+    #[test]
+    fn hard_sync_through_combinators() {
+        let step = 1.0 / 128.0;
+        let slave = Wave::triangle(20.0, 1.0);
+        let expected = render(slave.clone().hard_sync(Wave::sine(8.0)),
+                              step,
+                              64);
+        // Both the slave and the master may be wrapped in combinators that
+        // don't change their timing.
+        let samples = render(slave.clone().hard_sync(Wave::sine(8.0) * 0.5),
+                             step,
+                             64);
+        assert_eq!(expected, samples);
+        let samples = render((slave.clone() * 0.5)
+                                 .hard_sync(Wave::sine(8.0)),
+                             step,
+                             64);
+        for (&expected, &actual) in expected.iter().zip(samples.iter()) {
+            assert_approx!(0.5 * expected, actual);
+        }
+        let samples = render(slave.adshr(0.0, 0.0, 1.0, 1.0, 0.0)
+                                 .hard_sync(Wave::sine(8.0).delayed(0.0)),
+                             step,
+                             64);
+        assert_eq!(expected, samples);
+    }
+
+    #[test]
+    fn cycle_triggers() {
+        let triggers = Wave::sine(0.25).cycle_triggers();
+        assert_eq!(vec![1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+                   render(triggers, 1.0, 9));
+    }
+
+    #[test]
+    fn ring_and_amplitude_mod() {
+        let carrier = Wave::slide(0.0, 1.0, 0.0);
+        let samples = render(carrier.clone().ring_mod(-0.5), 1.0, 3);
+        assert_eq!(vec![0.0, -0.5, -1.0], samples);
+        let samples = render(carrier.clone()
+                                 .amplitude_mod(Wave::from(-1.0), 1.0),
+                             1.0,
+                             3);
+        assert_eq!(vec![0.0, 0.0, 0.0], samples);
+        let samples = render(carrier.clone().amplitude_mod(1.0, 1.0), 1.0, 3);
+        assert_eq!(vec![0.0, 1.0, 2.0], samples);
+        let samples = render(carrier.clone().amplitude_mod(-1.0, 0.5), 1.0, 3);
+        assert_eq!(vec![0.0, 0.5, 1.0], samples);
+        // The new waveform finishes as soon as the modulator or depth does.
+        let mut wave = carrier.clone()
+            .amplitude_mod(Wave::from(-1.0).take(2.0), 0.5);
+        assert_eq!(Some(0.0), wave.next(1.0));
+        assert_eq!(Some(0.5), wave.next(1.0));
+        assert_eq!(None, wave.next(1.0));
+        let mut wave = carrier.amplitude_mod(1.0, Wave::from(1.0).take(1.0));
+        assert_eq!(Some(0.0), wave.next(1.0));
+        assert_eq!(None, wave.next(1.0));
+        // A stateful depth input is only stepped once per sample.
+        let depth = Wave::slide(0.0, 1.0, 0.0);
+        let wave = Wave::from(1.0).amplitude_mod(-1.0, depth);
+        assert_eq!(vec![1.0, 0.5, 0.0], render(wave, 0.5, 3));
+    }
+}
+
+// ========================================================================= //
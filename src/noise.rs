@@ -0,0 +1,360 @@
+//! Colored noise generators.
+
+use std::f32::consts::PI;
+use super::{Rng, Sample, Wave, WaveGen};
+
+// ========================================================================= //
+
+const WHITE_NOISE_INIT_SEED: u64 = 111111111111111111;
+const GAUSSIAN_NOISE_INIT_SEED: u64 = 222222222222222222;
+const PINK_NOISE_INIT_SEED: u64 = 333333333333333333;
+const BROWN_NOISE_INIT_SEED: u64 = 444444444444444444;
+const BLUE_NOISE_INIT_SEED: u64 = 555555555555555555;
+const VELVET_NOISE_INIT_SEED: u64 = 666666666666666666;
+
+/// The number of octave rows used by the Voss-McCartney pink noise algorithm.
+const PINK_NOISE_ROWS: usize = 16;
+
+/// The standard deviation targeted by the noise generators that aren't
+/// naturally bounded, so that nearly all of their samples (beyond three
+/// standard deviations) fall between -1 and 1.
+const NOISE_STD_DEV: f32 = 1.0 / 3.0;
+
+// ========================================================================= //
+
+impl Wave {
+    /// Creates a white noise wave, whose samples are independent and
+    /// uniformly distributed between -1 and 1.  Unlike `Wave::noise`, the
+    /// noise has no frequency; a new random value is used for every sample.
+    pub fn white_noise() -> Wave {
+        Wave::colored_noise(NoiseColor::White, WHITE_NOISE_INIT_SEED)
+    }
+
+    /// Creates a Gaussian white noise wave, whose samples are independent and
+    /// normally distributed with a mean of 0 and a standard deviation of 1/3,
+    /// clipped to lie between -1 and 1.
+    pub fn gaussian_noise() -> Wave {
+        Wave::colored_noise(NoiseColor::Gaussian, GAUSSIAN_NOISE_INIT_SEED)
+    }
+
+    /// Creates a pink noise wave, whose power falls off by 3 dB per octave
+    /// (i.e. it has equal power in each octave).  The samples have a standard
+    /// deviation of roughly 1/3, and are clipped to lie between -1 and 1.
+    pub fn pink_noise() -> Wave {
+        Wave::colored_noise(NoiseColor::Pink, PINK_NOISE_INIT_SEED)
+    }
+
+    /// Creates a brown (or red) noise wave, whose power falls off by 6 dB per
+    /// octave, giving a deep rumbling sound.  The samples have a standard
+    /// deviation of roughly 1/3, and are clipped to lie between -1 and 1.
+    pub fn brown_noise() -> Wave {
+        Wave::colored_noise(NoiseColor::Brown, BROWN_NOISE_INIT_SEED)
+    }
+
+    /// Creates a blue noise wave, whose power rises by 3 dB per octave,
+    /// giving a hissing sound.  The samples have a standard deviation of
+    /// roughly 1/3, and are clipped to lie between -1 and 1.
+    pub fn blue_noise() -> Wave {
+        Wave::colored_noise(NoiseColor::Blue, BLUE_NOISE_INIT_SEED)
+    }
+
+    /// Creates a velvet noise wave, consisting of sparse impulses of 1 or -1
+    /// (with all other samples being 0), with an average density over time
+    /// controlled by the `density` waveform (which may be a constant),
+    /// measured in impulses per second.  Time is divided into equal cells,
+    /// each containing exactly one impulse at a random position.  At high
+    /// enough densities, velvet noise sounds smoother than white noise.
+    pub fn velvet_noise<D: Into<Wave>>(density: D) -> Wave {
+        Wave::velvet_noise_seeded(density, VELVET_NOISE_INIT_SEED)
+    }
+
+    /// Like `velvet_noise`, but uses the given seed for the random sequence
+    /// rather than a fixed default.
+    pub fn velvet_noise_seeded<D: Into<Wave>>(density: D, seed: u64) -> Wave {
+        Wave::new(Box::new(VelvetNoise::new(density.into(), seed)))
+    }
+
+    /// Creates a noise wave of the given color, using the given seed for the
+    /// random sequence.  The `white_noise`, `gaussian_noise`, `pink_noise`,
+    /// `brown_noise`, and `blue_noise` methods are equivalent to this method
+    /// with fixed default seeds.  For a given color and seed, the output is
+    /// bit-identical on every platform (see [`Rng`](struct.Rng.html)),
+    /// except for Gaussian noise, which relies on transcendental functions
+    /// whose precision may vary slightly from platform to platform.
+    pub fn colored_noise(color: NoiseColor, seed: u64) -> Wave {
+        Wave::new(Box::new(ColoredNoise::new(color, seed)))
+    }
+}
+
+// ========================================================================= //
+
+/// The spectral color of a noise wave, for use with
+/// [`Wave::colored_noise`](struct.Wave.html#method.colored_noise).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NoiseColor {
+    /// Uniformly-distributed white noise (see `Wave::white_noise`).
+    White,
+    /// Normally-distributed white noise (see `Wave::gaussian_noise`).
+    Gaussian,
+    /// Pink noise, with 3 dB less power per octave (see `Wave::pink_noise`).
+    Pink,
+    /// Brown noise, with 6 dB less power per octave (see
+    /// `Wave::brown_noise`).
+    Brown,
+    /// Blue noise, with 3 dB more power per octave (see `Wave::blue_noise`).
+    Blue,
+}
+
+/// A noise wave with a particular spectral color.
+#[derive(Clone)]
+struct ColoredNoise {
+    color: NoiseColor,
+    seed: u64,
+    rng: Rng,
+    /// The Voss-McCartney rows (for pink and blue noise).
+    rows: [f32; PINK_NOISE_ROWS],
+    /// The number of samples generated so far (for pink and blue noise).
+    counter: u32,
+    /// The previous filter output (for brown and blue noise).
+    previous: f32,
+}
+
+impl ColoredNoise {
+    fn new(color: NoiseColor, seed: u64) -> ColoredNoise {
+        let mut noise = ColoredNoise {
+            color,
+            seed,
+            rng: Rng::new(seed),
+            rows: [0.0; PINK_NOISE_ROWS],
+            counter: 0,
+            previous: 0.0,
+        };
+        noise.reset();
+        noise
+    }
+
+    /// Generates pink noise using the Voss-McCartney algorithm: each row
+    /// holds a random value that is updated half as often as the previous
+    /// row's, and the rows are summed along with a fresh white noise value.
+    fn next_pink(&mut self) -> f32 {
+        self.counter = self.counter.wrapping_add(1);
+        let row = self.counter.trailing_zeros() as usize;
+        if row < PINK_NOISE_ROWS {
+            self.rows[row] = self.rng.next_signed();
+        }
+        let sum: f32 = self.rows.iter().sum::<f32>() + self.rng.next_signed();
+        // Each uniform value has a variance of 1/3, so the sum has a variance
+        // of (ROWS + 1) / 3.
+        sum * NOISE_STD_DEV / ((PINK_NOISE_ROWS + 1) as f32 / 3.0).sqrt()
+    }
+}
+
+impl WaveGen for ColoredNoise {
+    fn next(&mut self, _: f32) -> Option<Sample> {
+        let value = match self.color {
+            NoiseColor::White => self.rng.next_signed(),
+            NoiseColor::Gaussian => {
+                // Use the Box-Muller transform.
+                let u1 = 1.0 - self.rng.next_unit();
+                let u2 = self.rng.next_unit();
+                NOISE_STD_DEV * (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+            }
+            NoiseColor::Pink => self.next_pink(),
+            NoiseColor::Brown => {
+                // Integrate white noise, with a slight leak to keep the
+                // output from wandering off.
+                let white = self.rng.next_signed();
+                self.previous = (self.previous + 0.02 * white) / 1.02;
+                3.5 * self.previous
+            }
+            NoiseColor::Blue => {
+                // Differentiate pink noise.
+                let pink = self.next_pink();
+                let value = pink - self.previous;
+                self.previous = pink;
+                2.0 * value
+            }
+        };
+        Some(value.clamp(-1.0, 1.0))
+    }
+
+    fn reset(&mut self) {
+        self.rng = Rng::new(self.seed);
+        for row in 0..PINK_NOISE_ROWS {
+            self.rows[row] = self.rng.next_signed();
+        }
+        self.counter = 0;
+        self.previous = 0.0;
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+}
+
+// ========================================================================= //
+
+/// A variable-density velvet noise wave.
+#[derive(Clone)]
+struct VelvetNoise {
+    density: Wave,
+    seed: u64,
+    rng: Rng,
+    /// How far through the current cell we are (from 0 to 1).
+    position: f32,
+    /// Where in the current cell the impulse is (from 0 to 1).
+    impulse_position: f32,
+    impulse_sign: f32,
+    impulse_done: bool,
+}
+
+impl VelvetNoise {
+    fn new(density: Wave, seed: u64) -> VelvetNoise {
+        let mut noise = VelvetNoise {
+            density,
+            seed,
+            rng: Rng::new(seed),
+            position: 0.0,
+            impulse_position: 0.0,
+            impulse_sign: 1.0,
+            impulse_done: false,
+        };
+        noise.start_cell();
+        noise
+    }
+
+    fn start_cell(&mut self) {
+        self.impulse_position = self.rng.next_unit();
+        self.impulse_sign = if self.rng.next_unit() < 0.5 {
+            -1.0
+        } else {
+            1.0
+        };
+        self.impulse_done = false;
+    }
+}
+
+impl WaveGen for VelvetNoise {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        let density = self.density.next(step)?;
+        // This sample covers the portion of the current cell from `start` to
+        // `end`, possibly extending into later cells.
+        let start = self.position;
+        let end = start + (density * step).max(0.0);
+        let mut value = 0.0;
+        if !self.impulse_done && self.impulse_position < end {
+            self.impulse_done = true;
+            value = self.impulse_sign;
+        }
+        self.position = end;
+        while self.position >= 1.0 {
+            self.position -= 1.0;
+            self.start_cell();
+            // Only one impulse can be output per sample, so if this sample
+            // also covers the next cell's impulse, skip it.
+            if self.impulse_position < self.position {
+                self.impulse_done = true;
+            }
+        }
+        Some(value)
+    }
+
+    fn reset(&mut self) {
+        self.density.reset();
+        self.rng = Rng::new(self.seed);
+        self.position = 0.0;
+        self.start_cell();
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+}
+
+// ========================================================================= //
+
+#[cfg(test)]
+mod tests {
+    use super::NoiseColor;
+    use super::super::{Wave, WaveGen, render};
+
+    /// Returns the standard deviation of the samples, and the ratio of the
+    /// variance of their first difference to their variance (which is about
+    /// 2 for white noise, and smaller or larger for noise whose power is
+    /// concentrated at lower or higher frequencies, respectively).
+    fn statistics(samples: &[f32]) -> (f32, f32) {
+        let variance = |values: &[f32]| {
+            let mean = values.iter().sum::<f32>() / values.len() as f32;
+            values.iter().map(|v| (v - mean) * (v - mean)).sum::<f32>() /
+            values.len() as f32
+        };
+        let diffs: Vec<f32> = samples.windows(2)
+            .map(|pair| pair[1] - pair[0])
+            .collect();
+        let var = variance(samples);
+        (var.sqrt(), variance(&diffs) / var)
+    }
+
+    #[test]
+    fn colored_noise() {
+        let cases = [("white", Wave::white_noise(), 0.45, 1.8, 2.2),
+                     ("gaussian", Wave::gaussian_noise(), 0.3, 1.8, 2.2),
+                     ("pink", Wave::pink_noise(), 0.25, 0.1, 1.0),
+                     ("brown", Wave::brown_noise(), 0.15, 0.0, 0.05),
+                     ("blue", Wave::blue_noise(), 0.25, 2.2, 4.0)];
+        for &(name, ref wave, min_std_dev, min_ratio, max_ratio) in
+            cases.iter() {
+            let mut wave = wave.clone();
+            let samples = render(&mut wave, 1.0 / 44100.0, 100000);
+            assert!(samples.iter().all(|sample| sample.abs() <= 1.0));
+            let (std_dev, ratio) = statistics(&samples);
+            assert!(std_dev > min_std_dev && std_dev < 0.6,
+                    "{} noise std dev: {}",
+                    name,
+                    std_dev);
+            assert!(ratio > min_ratio && ratio < max_ratio,
+                    "{} noise difference ratio: {}",
+                    name,
+                    ratio);
+            // Resetting the noise reproduces the same samples.
+            wave.reset();
+            assert_eq!(samples, render(&mut wave, 1.0 / 44100.0, 100000));
+        }
+    }
+
+    #[test]
+    fn velvet_noise() {
+        // With a step that divides the cell length exactly, there is
+        // exactly one impulse in each cell of 16 samples.
+        let mut wave = Wave::velvet_noise(64.0);
+        let samples: Vec<f32> =
+            (0..16000).map(|_| wave.next(1.0 / 1024.0).unwrap()).collect();
+        for cell in samples.chunks(16) {
+            let count = cell.iter().filter(|&&s| s != 0.0).count();
+            assert_eq!(1, count);
+        }
+        let mut wave = Wave::velvet_noise(2205.0);
+        let samples = render(&mut wave, 1.0 / 44100.0, 44100);
+        assert!(samples.iter().all(|&s| s == 0.0 || s == 1.0 || s == -1.0));
+        let count = samples.iter().filter(|&&s| s != 0.0).count();
+        assert!((2200..2206).contains(&count), "count = {}", count);
+        wave.reset();
+        assert_eq!(samples, render(&mut wave, 1.0 / 44100.0, 44100));
+    }
+
+    #[test]
+    fn seeded_noise() {
+        let seeded = |seed: u64| {
+            vec![Wave::noise_seeded(1000.0, seed),
+                 Wave::colored_noise(NoiseColor::White, seed),
+                 Wave::colored_noise(NoiseColor::Pink, seed),
+                 Wave::velvet_noise_seeded(2205.0, seed)]
+        };
+        for (mut wave1, (mut wave2, mut wave3)) in
+            seeded(1).into_iter().zip(seeded(1).into_iter().zip(seeded(2))) {
+            let samples = render(&mut wave1, 1.0 / 44100.0, 1000);
+            // The same seed gives the same samples, while a different seed
+            // (even a similar one) gives different samples.
+            assert_eq!(samples, render(&mut wave2, 1.0 / 44100.0, 1000));
+            assert!(samples != render(&mut wave3, 1.0 / 44100.0, 1000));
+        }
+    }
+}
+
+// ========================================================================= //
@@ -0,0 +1,582 @@
+//! Musical pitch: note names, MIDI note numbers, cents, and tunings.
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use super::{Sample, Wave, WaveGen};
+
+// ========================================================================= //
+
+/// The standard concert pitch for the note A4, in hertz.
+pub const A4_FREQ: f32 = 440.0;
+
+/// The MIDI note number of the note A4.
+pub const A4_MIDI: i32 = 69;
+
+/// The MIDI note number of middle C (C4), which is the default middle note
+/// of a Scala keyboard mapping.
+const MIDDLE_C_MIDI: i32 = 60;
+
+/// The number of cents in an octave.
+const CENTS_PER_OCTAVE: f64 = 1200.0;
+
+// ========================================================================= //
+
+/// An error encountered while parsing a note name or a Scala tuning file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PitchError {
+    /// The string wasn't a valid note name, such as "A4", "C#3", or "Bb5".
+    InvalidNoteName(String),
+    /// The note isn't mapped to any frequency by the tuning.
+    UnmappedNote(i32),
+    /// The Scala scale (`.scl`) file was malformed at the given line number
+    /// (counting from 1).
+    InvalidScl(usize),
+    /// The Scala keyboard mapping (`.kbm`) file was malformed at the given
+    /// line number (counting from 1).
+    InvalidKbm(usize),
+}
+
+impl fmt::Display for PitchError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PitchError::InvalidNoteName(ref name) => {
+                write!(formatter, "invalid note name {:?}", name)
+            }
+            PitchError::UnmappedNote(note) => {
+                write!(formatter, "MIDI note {} is not mapped", note)
+            }
+            PitchError::InvalidScl(line) => {
+                write!(formatter, "invalid Scala scale file (line {})", line)
+            }
+            PitchError::InvalidKbm(line) => {
+                write!(formatter,
+                       "invalid Scala keyboard mapping file (line {})",
+                       line)
+            }
+        }
+    }
+}
+
+impl Error for PitchError {}
+
+// ========================================================================= //
+
+/// Returns the MIDI note number of the given note name, such as "A4" (69),
+/// "C#3" (49), or "Bb5" (82).  A name consists of a letter from A to G
+/// (in either case), any number of sharps (`#`) or flats (`b`), and an
+/// octave number, which may be negative; octaves start at C, so "B3" is just
+/// below "C4" (middle C, 60), and "C-1" is note 0.
+pub fn note_to_midi(name: &str) -> Result<i32, PitchError> {
+    let error = || PitchError::InvalidNoteName(name.to_string());
+    let mut chars = name.chars();
+    let mut note = match chars.next().map(|chr| chr.to_ascii_uppercase()) {
+        Some('C') => 0,
+        Some('D') => 2,
+        Some('E') => 4,
+        Some('F') => 5,
+        Some('G') => 7,
+        Some('A') => 9,
+        Some('B') => 11,
+        _ => return Err(error()),
+    };
+    let rest = chars.as_str();
+    let octave = rest.trim_start_matches(['#', 'b']);
+    for chr in rest[..rest.len() - octave.len()].chars() {
+        note += if chr == '#' { 1 } else { -1 };
+    }
+    if !octave.trim_start_matches('-').starts_with(|chr: char| {
+        chr.is_ascii_digit()
+    }) {
+        return Err(error());
+    }
+    let octave: i32 = octave.parse().map_err(|_| error())?;
+    octave.checked_add(1)
+        .and_then(|octave| octave.checked_mul(12))
+        .and_then(|midi| midi.checked_add(note))
+        .ok_or_else(error)
+}
+
+/// Returns the frequency (in hertz) of the given note name (see
+/// `note_to_midi`), in twelve-tone equal temperament with A4 at 440 Hz.  For
+/// other tunings, use `Tuning::note_freq`.
+pub fn note_to_freq(name: &str) -> Result<f32, PitchError> {
+    Ok(midi_to_freq(note_to_midi(name)? as f32))
+}
+
+/// Returns the frequency (in hertz) of the given MIDI note number, in
+/// twelve-tone equal temperament with A4 at 440 Hz.  Fractional note numbers
+/// lie between semitones, so e.g. 69.5 is a quarter tone above A4.
+pub fn midi_to_freq(note: f32) -> f32 {
+    A4_FREQ * semitones_to_ratio(note - A4_MIDI as f32)
+}
+
+/// Returns the (fractional) MIDI note number of the given frequency (in
+/// hertz), in twelve-tone equal temperament with A4 at 440 Hz.  This is the
+/// inverse of `midi_to_freq`.
+pub fn freq_to_midi(freq: f32) -> f32 {
+    A4_MIDI as f32 + ratio_to_cents(freq / A4_FREQ) / 100.0
+}
+
+/// Returns the frequency ratio of an interval of the given number of cents
+/// (hundredths of an equal-tempered semitone); e.g. 1200 cents gives 2.
+pub fn cents_to_ratio(cents: f32) -> f32 { 2f32.powf(cents / 1200.0) }
+
+/// Returns the size, in cents, of the interval with the given frequency
+/// ratio.  This is the inverse of `cents_to_ratio`.
+pub fn ratio_to_cents(ratio: f32) -> f32 { 1200.0 * ratio.log2() }
+
+/// Returns the frequency ratio of an interval of the given number of
+/// equal-tempered semitones; e.g. 12 semitones gives 2.
+pub fn semitones_to_ratio(semitones: f32) -> f32 {
+    cents_to_ratio(100.0 * semitones)
+}
+
+// ========================================================================= //
+
+/// A tuning, which maps MIDI note numbers to frequencies.
+///
+/// The default tuning is twelve-tone equal temperament with A4 at 440 Hz;
+/// `Tuning::equal` allows a different reference pitch for A4, and
+/// `Tuning::from_scala` loads an arbitrary scale and keyboard mapping from
+/// [Scala](http://www.huygens-fokker.org/scala/) tuning files.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tuning {
+    /// The pitches of the scale's degrees (after the implicit 0 cents of the
+    /// first degree), in cents; the last of these is the scale's period.
+    scale: Vec<f64>,
+    /// The number of keys in one repetition of the keyboard mapping; zero
+    /// maps successive keys to successive degrees.
+    map_size: i32,
+    /// The scale degree played by each key within one repetition of the
+    /// keyboard mapping, starting from the middle key; keys past the end of
+    /// this are unmapped.
+    mapping: Vec<Option<i32>>,
+    first_key: i32,
+    last_key: i32,
+    middle_key: i32,
+    reference_key: i32,
+    reference_freq: f64,
+    /// The scale degree reached by moving up one repetition of the mapping.
+    octave_degree: i32,
+}
+
+impl Tuning {
+    /// Returns twelve-tone equal temperament, with A4 tuned to the given
+    /// frequency (in hertz).
+    pub fn equal(a4_freq: f32) -> Tuning {
+        Tuning {
+            scale: (1..13).map(|degree| 100.0 * degree as f64).collect(),
+            map_size: 0,
+            mapping: Vec::new(),
+            first_key: i32::MIN,
+            last_key: i32::MAX,
+            middle_key: MIDDLE_C_MIDI,
+            reference_key: A4_MIDI,
+            reference_freq: a4_freq as f64,
+            octave_degree: 12,
+        }
+    }
+
+    /// Loads a tuning from the contents of a Scala scale (`.scl`) file and,
+    /// optionally, a Scala keyboard mapping (`.kbm`) file.  Without a
+    /// keyboard mapping, successive MIDI notes play successive degrees of
+    /// the scale, with the scale's first degree on middle C (note 60), tuned
+    /// to the same frequency as in twelve-tone equal temperament.
+    pub fn from_scala(scl: &str, kbm: Option<&str>)
+                      -> Result<Tuning, PitchError> {
+        let scale = parse_scl(scl)?;
+        let mut tuning = Tuning {
+            octave_degree: scale.len() as i32,
+            scale,
+            map_size: 0,
+            mapping: Vec::new(),
+            first_key: i32::MIN,
+            last_key: i32::MAX,
+            middle_key: MIDDLE_C_MIDI,
+            reference_key: MIDDLE_C_MIDI,
+            reference_freq: midi_to_freq(MIDDLE_C_MIDI as f32) as f64,
+        };
+        if let Some(kbm) = kbm {
+            tuning.parse_kbm(kbm)?;
+        }
+        Ok(tuning)
+    }
+
+    /// Returns the frequency (in hertz) of the given MIDI note number in
+    /// this tuning, or `None` if the keyboard mapping leaves that note
+    /// unmapped.
+    pub fn key_freq(&self, key: i32) -> Option<f32> {
+        let cents = self.key_cents(key)? -
+                    self.key_cents(self.reference_key).unwrap();
+        let ratio = 2f64.powf(cents / CENTS_PER_OCTAVE);
+        Some((self.reference_freq * ratio) as f32)
+    }
+
+    /// Returns the frequency (in hertz) of the given note name (see
+    /// `note_to_midi`) in this tuning.
+    pub fn note_freq(&self, name: &str) -> Result<f32, PitchError> {
+        let key = note_to_midi(name)?;
+        self.key_freq(key).ok_or(PitchError::UnmappedNote(key))
+    }
+
+    /// Returns the pitch of the given key, in cents relative to the first
+    /// degree of the scale on the middle key.
+    fn key_cents(&self, key: i32) -> Option<f64> {
+        if key < self.first_key || key > self.last_key {
+            return None;
+        }
+        let offset = key.checked_sub(self.middle_key)?;
+        if self.map_size == 0 {
+            return Some(self.degree_cents(offset));
+        }
+        let index = offset.rem_euclid(self.map_size) as usize;
+        let degree = self.mapping.get(index).cloned().unwrap_or(None)?;
+        let octave = offset.div_euclid(self.map_size) as f64;
+        Some(octave * self.degree_cents(self.octave_degree) +
+             self.degree_cents(degree))
+    }
+
+    /// Returns the pitch of the given scale degree, in cents, repeating the
+    /// scale at its period for degrees outside of a single period.
+    fn degree_cents(&self, degree: i32) -> f64 {
+        let size = self.scale.len() as i32;
+        let period = self.scale[self.scale.len() - 1];
+        let index = degree.rem_euclid(size);
+        let base = if index == 0 {
+            0.0
+        } else {
+            self.scale[index as usize - 1]
+        };
+        degree.div_euclid(size) as f64 * period + base
+    }
+
+    /// Applies the contents of a Scala keyboard mapping file.
+    fn parse_kbm(&mut self, kbm: &str) -> Result<(), PitchError> {
+        let mut lines = scala_lines(kbm);
+        let size: i32 = kbm_value(&mut lines)?;
+        if size < 0 {
+            return Err(PitchError::InvalidKbm(lines.number));
+        }
+        self.first_key = kbm_value(&mut lines)?;
+        self.last_key = kbm_value(&mut lines)?;
+        self.middle_key = kbm_value(&mut lines)?;
+        self.reference_key = kbm_value(&mut lines)?;
+        self.reference_freq = kbm_value(&mut lines)?;
+        if self.reference_freq <= 0.0 {
+            return Err(PitchError::InvalidKbm(lines.number));
+        }
+        let octave_degree = kbm_value(&mut lines)?;
+        if size > 0 {
+            self.octave_degree = octave_degree;
+        }
+        self.map_size = size;
+        self.mapping.clear();
+        // Keys without a mapping entry at the end of the file are unmapped.
+        while self.mapping.len() < size as usize {
+            let degree = match lines.next() {
+                Some((_, line)) if first_token(line) == "x" => None,
+                Some((number, line)) => {
+                    Some(first_token(line)
+                        .parse()
+                        .map_err(|_| PitchError::InvalidKbm(number))?)
+                }
+                None => break,
+            };
+            self.mapping.push(degree);
+        }
+        if self.key_cents(self.reference_key).is_none() {
+            return Err(PitchError::InvalidKbm(lines.end));
+        }
+        Ok(())
+    }
+}
+
+impl Default for Tuning {
+    fn default() -> Tuning { Tuning::equal(A4_FREQ) }
+}
+
+// ========================================================================= //
+
+/// Parses the contents of a Scala scale file, returning the pitches of its
+/// degrees in cents.
+fn parse_scl(scl: &str) -> Result<Vec<f64>, PitchError> {
+    let mut lines = scala_lines(scl);
+    lines.next().ok_or(PitchError::InvalidScl(lines.end))?; // description
+    let (number, line) = lines.next()
+        .ok_or(PitchError::InvalidScl(lines.end))?;
+    let count: usize = match first_token(line).parse() {
+        Ok(count) if count > 0 => count,
+        _ => return Err(PitchError::InvalidScl(number)),
+    };
+    let mut scale = Vec::with_capacity(count);
+    for _ in 0..count {
+        let (number, line) = lines.next()
+            .ok_or(PitchError::InvalidScl(lines.end))?;
+        scale.push(parse_scl_pitch(first_token(line))
+            .ok_or(PitchError::InvalidScl(number))?);
+    }
+    Ok(scale)
+}
+
+/// Parses one pitch from a Scala scale file, which is either a number of
+/// cents (if it contains a period) or a ratio such as "3/2" or "2".
+fn parse_scl_pitch(token: &str) -> Option<f64> {
+    if token.contains('.') {
+        return token.parse().ok();
+    }
+    let mut parts = token.splitn(2, '/');
+    let numerator: f64 = parts.next()?.parse().ok()?;
+    let denominator: f64 = match parts.next() {
+        Some(part) => part.parse().ok()?,
+        None => 1.0,
+    };
+    if numerator > 0.0 && denominator > 0.0 {
+        Some(CENTS_PER_OCTAVE * (numerator / denominator).log2())
+    } else {
+        None
+    }
+}
+
+/// Parses the next value from a Scala keyboard mapping file.
+fn kbm_value<T: FromStr>(lines: &mut ScalaLines) -> Result<T, PitchError> {
+    let (number, line) = lines.next()
+        .ok_or(PitchError::InvalidKbm(lines.end))?;
+    first_token(line).parse().map_err(|_| PitchError::InvalidKbm(number))
+}
+
+/// Returns the first whitespace-separated token of a line from a Scala file
+/// (any further text on the line being a comment).
+fn first_token(line: &str) -> &str {
+    line.split_whitespace().next().unwrap_or("")
+}
+
+/// Returns an iterator over the non-comment lines of a Scala file.
+fn scala_lines<'a>(text: &'a str) -> ScalaLines<'a> {
+    ScalaLines {
+        lines: text.lines().enumerate(),
+        number: 0,
+        end: text.lines().count() + 1,
+    }
+}
+
+/// An iterator over the (1-based) line numbers and trimmed contents of the
+/// non-comment lines of a Scala file.
+struct ScalaLines<'a> {
+    lines: ::std::iter::Enumerate<::std::str::Lines<'a>>,
+    /// The line number of the most recently returned line.
+    number: usize,
+    /// The line number just past the end of the file, for reporting missing
+    /// lines.
+    end: usize,
+}
+
+impl<'a> Iterator for ScalaLines<'a> {
+    type Item = (usize, &'a str);
+
+    fn next(&mut self) -> Option<(usize, &'a str)> {
+        for (index, line) in self.lines.by_ref() {
+            if !line.starts_with('!') {
+                self.number = index + 1;
+                return Some((self.number, line.trim()));
+            }
+        }
+        None
+    }
+}
+
+// ========================================================================= //
+
+impl Wave {
+    /// Treats this wave as a frequency, and raises it by the number of
+    /// equal-tempered semitones given by the `semitones` waveform (which may
+    /// be a constant, and may be negative to lower the frequency).  The new
+    /// waveform finishes as soon as either input waveform does.  (To play a
+    /// whole sound at a different pitch, use `transpose` instead.)
+    pub fn offset_semitones<S: Into<Wave>>(self, semitones: S) -> Wave {
+        Wave::new(Box::new(Offset {
+            freq: self,
+            offset: semitones.into(),
+            cents_per_unit: 100.0,
+        }))
+    }
+
+    /// Treats this wave as a frequency, and raises it by the number of cents
+    /// given by the `cents` waveform (which may be a constant, and may be
+    /// negative to lower the frequency).  The new waveform finishes as soon
+    /// as either input waveform does.
+    pub fn offset_cents<C: Into<Wave>>(self, cents: C) -> Wave {
+        Wave::new(Box::new(Offset {
+            freq: self,
+            offset: cents.into(),
+            cents_per_unit: 1.0,
+        }))
+    }
+}
+
+// ========================================================================= //
+
+/// A frequency waveform, offset by an interval waveform.
+#[derive(Clone)]
+struct Offset {
+    freq: Wave,
+    offset: Wave,
+    cents_per_unit: f32,
+}
+
+impl WaveGen for Offset {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        let freq = self.freq.next(step)?;
+        let offset = self.offset.next(step)?;
+        Some(freq * cents_to_ratio(offset * self.cents_per_unit))
+    }
+
+    fn reset(&mut self) {
+        self.freq.reset();
+        self.offset.reset();
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+}
+
+// ========================================================================= //
+
+#[cfg(test)]
+mod tests {
+    use super::{PitchError, Tuning, cents_to_ratio, freq_to_midi,
+                midi_to_freq, note_to_freq, note_to_midi, ratio_to_cents};
+    use super::super::{Wave, WaveGen};
+
+    fn assert_freq(expected: f32, actual: f32) {
+        assert!((expected - actual).abs() < 1e-3 * expected,
+                "expected {} Hz, got {} Hz",
+                expected,
+                actual);
+    }
+
+    #[test]
+    fn note_names() {
+        assert_eq!(Ok(69), note_to_midi("A4"));
+        assert_eq!(Ok(60), note_to_midi("C4"));
+        assert_eq!(Ok(49), note_to_midi("C#3"));
+        assert_eq!(Ok(82), note_to_midi("Bb5"));
+        assert_eq!(Ok(59), note_to_midi("Cb4"));
+        assert_eq!(Ok(62), note_to_midi("c##4"));
+        assert_eq!(Ok(0), note_to_midi("C-1"));
+        assert_eq!(Ok(127), note_to_midi("G9"));
+        for &name in ["", "H4", "C", "C#", "4", "C4x", "Cx4", "C--1",
+                      "C300000000", "C2147483647"]
+            .iter() {
+            assert_eq!(Err(PitchError::InvalidNoteName(name.to_string())),
+                       note_to_midi(name));
+        }
+    }
+
+    #[test]
+    fn midi_freqs() {
+        assert_eq!(440.0, midi_to_freq(69.0));
+        assert_freq(261.6256, midi_to_freq(60.0));
+        assert_freq(880.0, midi_to_freq(81.0));
+        assert_freq(8.175799, midi_to_freq(0.0));
+        assert_approx!(69.0, freq_to_midi(440.0));
+        assert!((60.5 - freq_to_midi(midi_to_freq(60.5))).abs() < 1e-4);
+        assert_freq(466.1638, note_to_freq("Bb4").unwrap());
+        assert_approx!(2.0, cents_to_ratio(1200.0));
+        assert!((701.955 - ratio_to_cents(1.5)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn offset_freq_waves() {
+        let mut wave = Wave::from(440.0).offset_semitones(12.0);
+        assert_freq(880.0, wave.next(0.1).unwrap());
+        let mut wave = Wave::from(440.0).offset_cents(-1200.0);
+        assert_freq(220.0, wave.next(0.1).unwrap());
+        let semitones = Wave::slide(0.0, 120.0, 0.0).take(0.25);
+        let mut wave = Wave::from(100.0).offset_semitones(semitones);
+        assert_freq(100.0, wave.next(0.1).unwrap());
+        assert_freq(200.0, wave.next(0.1).unwrap());
+        assert_freq(400.0, wave.next(0.1).unwrap());
+        assert_eq!(None, wave.next(0.1));
+    }
+
+    #[test]
+    fn equal_tuning() {
+        let tuning = Tuning::default();
+        for key in 0..128 {
+            assert_freq(midi_to_freq(key as f32),
+                        tuning.key_freq(key).unwrap());
+        }
+        let tuning = Tuning::equal(432.0);
+        assert_freq(432.0, tuning.note_freq("A4").unwrap());
+        assert_freq(216.0, tuning.note_freq("A3").unwrap());
+    }
+
+    #[test]
+    fn scala_scale() {
+        // Twelve-tone equal temperament matches the default tuning.
+        let scl = "! 12tet.scl\n!\n12-TET\n 12\n!\n 100.0\n 200.\n 300.0\n \
+                   400.0\n 500.0\n 600.0\n 700.0\n 800.0\n 900.0\n 1000.0\n \
+                   1100.0\n 2/1\n";
+        let tuning = Tuning::from_scala(scl, None).unwrap();
+        for key in 0..128 {
+            assert_freq(midi_to_freq(key as f32),
+                        tuning.key_freq(key).unwrap());
+        }
+        // A just-intonation pentatonic scale, with C4 at 261.6256 Hz.
+        let scl = "Pentatonic\n5\n9/8\n5/4\n3/2 fifth\n5/3\n2\n";
+        let tuning = Tuning::from_scala(scl, None).unwrap();
+        assert_freq(261.6256, tuning.key_freq(60).unwrap());
+        assert_freq(261.6256 * 1.5, tuning.key_freq(63).unwrap());
+        assert_freq(261.6256 * 2.0 * 1.125, tuning.key_freq(66).unwrap());
+        assert_freq(261.6256 * 5.0 / 6.0, tuning.key_freq(59).unwrap());
+        // Malformed files report the line of the problem.
+        assert_eq!(Err(PitchError::InvalidScl(3)),
+                   Tuning::from_scala("!\nName\nfive\n", None));
+        assert_eq!(Err(PitchError::InvalidScl(4)),
+                   Tuning::from_scala("Name\n2\n3/2\n-1/2\n", None));
+        assert_eq!(Err(PitchError::InvalidScl(4)),
+                   Tuning::from_scala("Name\n2\n3/2\n", None));
+    }
+
+    #[test]
+    fn scala_keyboard_mapping() {
+        let scl = "Pentatonic\n5\n9/8\n5/4\n3/2\n5/3\n2/1\n";
+        // Map the white keys of each octave onto the pentatonic scale,
+        // leaving F and B unmapped, with A4 at 432 Hz.
+        let kbm = "! white keys\n12\n0\n127\n60\n69\n432.0\n5\n\
+                   0\nx\n1\nx\n2\nx\nx\n3\nx\n4\nx\nx\n";
+        let tuning = Tuning::from_scala(scl, Some(kbm)).unwrap();
+        assert_freq(432.0, tuning.note_freq("A4").unwrap());
+        assert_freq(432.0 * 0.6, tuning.note_freq("C4").unwrap());
+        assert_freq(432.0 * 0.9, tuning.note_freq("G4").unwrap());
+        assert_freq(432.0 * 0.3, tuning.note_freq("C3").unwrap());
+        assert_eq!(Err(PitchError::UnmappedNote(65)),
+                   tuning.note_freq("F4"));
+        assert_eq!(None, tuning.key_freq(61));
+        // A linear mapping over a limited range of keys.
+        let kbm = "0\n60\n72\n60\n60\n200\n0\n";
+        let tuning = Tuning::from_scala(scl, Some(kbm)).unwrap();
+        assert_freq(300.0, tuning.key_freq(63).unwrap());
+        assert_freq(800.0, tuning.key_freq(70).unwrap());
+        assert_eq!(None, tuning.key_freq(59));
+        assert_eq!(None, tuning.key_freq(73));
+        // The reference key must be mapped.
+        let kbm = "1\n0\n127\n60\n61\n440\n1\nx\n";
+        assert_eq!(Err(PitchError::InvalidKbm(9)),
+                   Tuning::from_scala(scl, Some(kbm)));
+        assert_eq!(Err(PitchError::InvalidKbm(6)),
+                   Tuning::from_scala(scl, Some("0\n0\n127\n60\n69\n-1\n")));
+        // Missing entries at the end of the mapping are unmapped, even when
+        // the mapping claims to be huge.
+        let kbm = "2147483647\n0\n127\n60\n60\n440\n5\n0\n";
+        let tuning = Tuning::from_scala(scl, Some(kbm)).unwrap();
+        assert_freq(440.0, tuning.key_freq(60).unwrap());
+        assert_eq!(None, tuning.key_freq(61));
+        assert_eq!(None, tuning.key_freq(59));
+        let kbm = "-1\n0\n127\n60\n60\n440\n0\n";
+        assert_eq!(Err(PitchError::InvalidKbm(1)),
+                   Tuning::from_scala(scl, Some(kbm)));
+    }
+}
+
+// ========================================================================= //
@@ -0,0 +1,241 @@
+//! Physically-modelled plucked strings, using the Karplus-Strong algorithm.
+
+use super::{Rng, Sample, Wave, WaveGen};
+
+// ========================================================================= //
+
+const PLUCK_INIT_SEED: u64 = 987654321987654321;
+
+/// Once the string's peak amplitude over a full period falls below this
+/// level (about -80 dB), the string is considered silent.
+const PLUCK_SILENCE_THRESHOLD: f32 = 1e-4;
+
+/// The loop gain that is reached after `decay` seconds (i.e. -60 dB).
+const PLUCK_DECAY_LEVEL: f32 = 0.001;
+
+// ========================================================================= //
+
+impl Wave {
+    /// Creates a plucked string sound, using the Karplus-Strong algorithm: a
+    /// burst of noise circulates through a delay line whose length matches
+    /// the period of the frequency `freq` (in hertz), with a lowpass filter
+    /// in the loop.  The `decay` is the time, in seconds, that it takes the
+    /// string to die away by 60 dB; the `brightness` (from 0 to 1) controls
+    /// how much high-frequency content the pluck has, and how quickly it
+    /// dies away relative to the fundamental.  The wave finishes once the
+    /// string has fallen silent.
+    ///
+    /// Because the delay line's length depends on the sample rate, the
+    /// string is tuned for the step size used on the first call to `next()`
+    /// (or the first call after a reset), and should always be played back
+    /// with that same step size.
+    pub fn pluck(freq: f32, decay: f32, brightness: f32) -> Wave {
+        Wave::new(Box::new(PluckWave::new(freq, decay, brightness)))
+    }
+}
+
+// ========================================================================= //
+
+/// A Karplus-Strong plucked string.
+#[derive(Clone)]
+struct PluckWave {
+    freq: f32,
+    decay: f32,
+    brightness: f32,
+    string: Option<PluckString>,
+    finished: bool,
+}
+
+impl PluckWave {
+    fn new(freq: f32, decay: f32, brightness: f32) -> PluckWave {
+        PluckWave {
+            freq,
+            decay,
+            brightness: brightness.clamp(0.0, 1.0),
+            string: None,
+            finished: false,
+        }
+    }
+}
+
+impl WaveGen for PluckWave {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        if self.finished {
+            return None;
+        }
+        if self.string.is_none() {
+            self.string = PluckString::new(self.freq,
+                                           self.decay,
+                                           self.brightness,
+                                           step);
+        }
+        let value = self.string.as_mut().and_then(PluckString::next);
+        if value.is_none() {
+            self.finished = true;
+        }
+        value
+    }
+
+    fn reset(&mut self) {
+        self.string = None;
+        self.finished = false;
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+}
+
+// ========================================================================= //
+
+/// The state of a plucked string, tuned for a particular sample rate.
+#[derive(Clone)]
+struct PluckString {
+    delay_line: Vec<f32>,
+    position: usize,
+    loop_gain: f32,
+    lowpass_coeff: f32,
+    lowpass_prev: f32,
+    allpass_coeff: f32,
+    allpass_prev_in: f32,
+    allpass_prev_out: f32,
+    period: usize,
+    period_count: usize,
+    period_peak: f32,
+}
+
+impl PluckString {
+    fn new(freq: f32, decay: f32, brightness: f32, step: f32)
+           -> Option<PluckString> {
+        // The total delay around the loop, in samples.
+        let period = 1.0 / (freq * step);
+        if !period.is_finite() || period < 2.0 {
+            return None;
+        }
+        // The lowpass filter averages each sample with the previous one,
+        // which delays the signal by `lowpass_coeff` samples.
+        let lowpass_coeff = 0.5 * (1.0 - brightness);
+        // Use an integer-length delay line for most of the delay, and a
+        // first-order allpass filter for the remaining fraction (which is
+        // kept between 0.1 and 1.1 samples, where the allpass filter behaves
+        // well).
+        let remaining = period - lowpass_coeff;
+        let length = ((remaining - 0.1).floor() as usize).max(1);
+        let fraction = remaining - length as f32;
+        let allpass_coeff = (1.0 - fraction) / (1.0 + fraction);
+        let loop_gain = if decay > 0.0 {
+            PLUCK_DECAY_LEVEL.powf(1.0 / (freq * decay))
+        } else {
+            0.0
+        };
+        // Excite the string with a burst of noise, smoothed more for darker
+        // plucks.
+        let mut rng = Rng::new(PLUCK_INIT_SEED);
+        let mut previous = 0.0;
+        let delay_line = (0..length)
+            .map(|_| {
+                let noise = rng.next_signed();
+                previous = noise + (previous - noise) * lowpass_coeff;
+                previous
+            })
+            .collect();
+        Some(PluckString {
+            delay_line,
+            position: 0,
+            loop_gain,
+            lowpass_coeff,
+            lowpass_prev: 0.0,
+            allpass_coeff,
+            allpass_prev_in: 0.0,
+            allpass_prev_out: 0.0,
+            period: period.ceil() as usize,
+            period_count: 0,
+            period_peak: 0.0,
+        })
+    }
+
+    fn next(&mut self) -> Option<Sample> {
+        let value = self.delay_line[self.position];
+        let lowpassed = value +
+                        (self.lowpass_prev - value) * self.lowpass_coeff;
+        self.lowpass_prev = value;
+        let allpassed = self.allpass_coeff * lowpassed + self.allpass_prev_in -
+                        self.allpass_coeff * self.allpass_prev_out;
+        self.allpass_prev_in = lowpassed;
+        self.allpass_prev_out = allpassed;
+        self.delay_line[self.position] = self.loop_gain * allpassed;
+        self.position = (self.position + 1) % self.delay_line.len();
+        self.period_peak = self.period_peak.max(value.abs());
+        self.period_count += 1;
+        if self.period_count >= self.period {
+            if self.period_peak < PLUCK_SILENCE_THRESHOLD {
+                return None;
+            }
+            self.period_count = 0;
+            self.period_peak = 0.0;
+        }
+        Some(value)
+    }
+}
+
+// ========================================================================= //
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Wave, WaveGen, render};
+
+    #[test]
+    fn pluck_finishes() {
+        let step = 1.0 / 44100.0;
+        let samples = render(Wave::pluck(441.0, 0.1, 0.5), step, 1000000);
+        // The string should last a bit longer than its 60 dB decay time,
+        // since it only finishes once it is 80 dB down.
+        let duration = samples.len() as f32 * step;
+        assert!(duration > 0.1 && duration < 0.3, "duration = {}", duration);
+        assert!(samples.iter().all(|sample| sample.abs() <= 1.0));
+        // Plucks are deterministic.
+        assert_eq!(samples,
+                   render(Wave::pluck(441.0, 0.1, 0.5), step, 1000000));
+    }
+
+    #[test]
+    fn pluck_tuning() {
+        // Estimate the period (in samples) by finding the lag that maximizes
+        // the autocorrelation, interpolated to a fraction of a sample.
+        fn period(samples: &[f32], min_lag: usize, max_lag: usize) -> f32 {
+            let correlation = |lag: usize| -> f32 {
+                samples[..samples.len() - lag]
+                    .iter()
+                    .zip(&samples[lag..])
+                    .map(|(a, b)| a * b)
+                    .sum()
+            };
+            let best = (min_lag..max_lag + 1)
+                .max_by(|&a, &b| {
+                    correlation(a).partial_cmp(&correlation(b)).unwrap()
+                })
+                .unwrap();
+            let (left, mid, right) = (correlation(best - 1),
+                                      correlation(best),
+                                      correlation(best + 1));
+            best as f32 + 0.5 * (left - right) / (left - 2.0 * mid + right)
+        }
+        let step = 1.0 / 44100.0;
+        let cases = [(441.0, 1.0), (440.0, 0.0), (330.0, 0.5), (523.25, 0.8)];
+        for &(freq, brightness) in cases.iter() {
+            let mut wave = Wave::pluck(freq, 2.0, brightness);
+            let samples: Vec<f32> = (0..4000)
+                .map(|_| wave.next(step).unwrap())
+                .collect();
+            let expected = 44100.0 / freq;
+            let actual = period(&samples[1000..],
+                                expected as usize - 5,
+                                expected as usize + 5);
+            assert!((actual - expected).abs() < 0.1,
+                    "freq {}: expected period {}, got {}",
+                    freq,
+                    expected,
+                    actual);
+        }
+    }
+}
+
+// ========================================================================= //
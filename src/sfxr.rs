@@ -0,0 +1,1047 @@
+//! An sfxr-compatible sound effect generator, with import and export of
+//! sfxr/jsfxr presets.
+
+use std::error::Error;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+use super::{Rng, Sample, Wave, WaveGen};
+
+// ========================================================================= //
+
+const SFXR_INIT_SEED: u64 = 123123123456456456;
+
+/// The sample rate at which sfxr synthesizes sound, in hertz.
+const SFXR_SAMPLE_RATE: f64 = 44100.0;
+
+/// The number of sub-samples that sfxr computes for each output sample.
+const SFXR_SUPERSAMPLING: usize = 8;
+
+/// The length of sfxr's phaser delay line, in sub-samples.
+const SFXR_PHASER_LEN: usize = 1024;
+
+/// The number of random values in sfxr's noise buffer, which is refilled on
+/// each cycle of the noise waveform.
+const SFXR_NOISE_LEN: usize = 32;
+
+/// The order in which parameters appear in a jsfxr hash, after the wave type.
+const SFXR_HASH_ORDER: [&str; 22] = [
+    "p_env_attack", "p_env_sustain", "p_env_punch", "p_env_decay",
+    "p_base_freq", "p_freq_limit", "p_freq_ramp", "p_freq_dramp",
+    "p_vib_strength", "p_vib_speed", "p_arp_mod", "p_arp_speed", "p_duty",
+    "p_duty_ramp", "p_repeat_speed", "p_pha_offset", "p_pha_ramp",
+    "p_lpf_freq", "p_lpf_ramp", "p_lpf_resonance", "p_hpf_freq",
+    "p_hpf_ramp",
+];
+
+/// The alphabet used for the base58 encoding of jsfxr hashes.
+const BASE58_ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZ\
+                                 abcdefghijkmnopqrstuvwxyz";
+
+// ========================================================================= //
+
+/// The base waveform of an sfxr sound effect.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SfxrWaveType {
+    /// A square (pulse) wave, whose duty cycle is controlled by the `duty`
+    /// and `duty_ramp` parameters.
+    Square,
+    /// A sawtooth wave.
+    Sawtooth,
+    /// A sine wave.
+    Sine,
+    /// Noise, which is resampled once per cycle of the base frequency.
+    Noise,
+}
+
+impl SfxrWaveType {
+    fn from_index(index: i64) -> Result<SfxrWaveType, SfxrError> {
+        match index {
+            0 => Ok(SfxrWaveType::Square),
+            1 => Ok(SfxrWaveType::Sawtooth),
+            2 => Ok(SfxrWaveType::Sine),
+            3 => Ok(SfxrWaveType::Noise),
+            _ => Err(SfxrError::UnknownWaveType(index)),
+        }
+    }
+
+    fn index(self) -> u8 {
+        match self {
+            SfxrWaveType::Square => 0,
+            SfxrWaveType::Sawtooth => 1,
+            SfxrWaveType::Sine => 2,
+            SfxrWaveType::Noise => 3,
+        }
+    }
+}
+
+// ========================================================================= //
+
+/// An error encountered while importing an sfxr preset.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SfxrError {
+    /// The JSON text was malformed, or wasn't a flat JSON object.
+    InvalidJson,
+    /// The hash contained a character that isn't valid base58.
+    InvalidBase58(char),
+    /// The data was the wrong length (in bytes) for its format.
+    WrongLength(usize),
+    /// The `.sfs` file had an unsupported version number.
+    UnsupportedVersion(i32),
+    /// The preset used a wave type that isn't supported.
+    UnknownWaveType(i64),
+}
+
+impl fmt::Display for SfxrError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SfxrError::InvalidJson => {
+                formatter.write_str("invalid sfxr JSON object")
+            }
+            SfxrError::InvalidBase58(chr) => {
+                write!(formatter, "invalid base58 character {:?}", chr)
+            }
+            SfxrError::WrongLength(len) => {
+                write!(formatter, "sfxr data has wrong length ({})", len)
+            }
+            SfxrError::UnsupportedVersion(version) => {
+                write!(formatter, "unsupported sfxr version {}", version)
+            }
+            SfxrError::UnknownWaveType(index) => {
+                write!(formatter, "unknown sfxr wave type {}", index)
+            }
+        }
+    }
+}
+
+impl Error for SfxrError {}
+
+// ========================================================================= //
+
+/// The parameters of an sfxr sound effect, for use with
+/// [`Wave::sfxr`](struct.Wave.html#method.sfxr).
+///
+/// These are the parameters of DrPetter's original sfxr (and of jsfxr),
+/// with the same ranges and meanings: most parameters range from 0 to 1,
+/// while the "ramp" parameters, `freq_dramp`, `arp_mod`, and `pha_offset`
+/// range from -1 to 1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SfxrParams {
+    /// The base waveform.
+    pub wave_type: SfxrWaveType,
+    /// The envelope's attack time.
+    pub env_attack: f32,
+    /// The envelope's sustain time.
+    pub env_sustain: f32,
+    /// How much the volume is boosted at the start of the sustain, fading
+    /// back down over the sustain time.
+    pub env_punch: f32,
+    /// The envelope's decay time.
+    pub env_decay: f32,
+    /// The starting frequency.
+    pub base_freq: f32,
+    /// The minimum frequency; if a downward slide reaches this, the sound
+    /// stops.  Zero for no limit.
+    pub freq_limit: f32,
+    /// The frequency slide (negative to slide down, positive to slide up).
+    pub freq_ramp: f32,
+    /// The change in the frequency slide over time.
+    pub freq_dramp: f32,
+    /// The depth of the vibrato.
+    pub vib_strength: f32,
+    /// The speed of the vibrato.
+    pub vib_speed: f32,
+    /// The frequency change applied by the arpeggio (negative to jump down,
+    /// positive to jump up).
+    pub arp_mod: f32,
+    /// How soon the arpeggio's frequency change happens.
+    pub arp_speed: f32,
+    /// The duty cycle of the square wave.
+    pub duty: f32,
+    /// The change in duty cycle over time.
+    pub duty_ramp: f32,
+    /// How often the frequency and duty parameters restart, as in e.g. an
+    /// alarm sound.  Zero for no repeat.
+    pub repeat_speed: f32,
+    /// The phaser's delay offset.
+    pub pha_offset: f32,
+    /// The change in the phaser's delay offset over time.
+    pub pha_ramp: f32,
+    /// The lowpass filter's cutoff frequency; 1 disables the filter.
+    pub lpf_freq: f32,
+    /// The change in the lowpass filter's cutoff over time.
+    pub lpf_ramp: f32,
+    /// The lowpass filter's resonance.
+    pub lpf_resonance: f32,
+    /// The highpass filter's cutoff frequency.
+    pub hpf_freq: f32,
+    /// The change in the highpass filter's cutoff over time.
+    pub hpf_ramp: f32,
+    /// The overall volume.
+    pub sound_vol: f32,
+}
+
+impl Default for SfxrParams {
+    /// Returns the parameters of sfxr's default sound: a short square wave
+    /// beep.
+    fn default() -> SfxrParams {
+        SfxrParams {
+            wave_type: SfxrWaveType::Square,
+            env_attack: 0.0,
+            env_sustain: 0.3,
+            env_punch: 0.0,
+            env_decay: 0.4,
+            base_freq: 0.3,
+            freq_limit: 0.0,
+            freq_ramp: 0.0,
+            freq_dramp: 0.0,
+            vib_strength: 0.0,
+            vib_speed: 0.0,
+            arp_mod: 0.0,
+            arp_speed: 0.0,
+            duty: 0.0,
+            duty_ramp: 0.0,
+            repeat_speed: 0.0,
+            pha_offset: 0.0,
+            pha_ramp: 0.0,
+            lpf_freq: 1.0,
+            lpf_ramp: 0.0,
+            lpf_resonance: 0.0,
+            hpf_freq: 0.0,
+            hpf_ramp: 0.0,
+            sound_vol: 0.5,
+        }
+    }
+}
+
+impl SfxrParams {
+    /// Parses a preset in the JSON format exported by jsfxr, e.g.
+    /// `{"wave_type": 0, "p_env_attack": 0, "p_env_sustain": 0.3, ...}`.
+    /// Parameters missing from the JSON object keep their default values,
+    /// and unrecognized keys are ignored.
+    pub fn from_json(json: &str) -> Result<SfxrParams, SfxrError> {
+        let mut params = SfxrParams::default();
+        for (key, value) in parse_json_object(json)? {
+            if key == "wave_type" {
+                params.wave_type = SfxrWaveType::from_index(value as i64)?;
+            } else if key == "sound_vol" {
+                params.sound_vol = value as f32;
+            } else if let Some(param) = params.param_mut(&key) {
+                *param = value as f32;
+            }
+        }
+        Ok(params)
+    }
+
+    /// Decodes a preset from the base58 hash used in jsfxr's sharing URLs
+    /// (the part after the `#`, which may be included).  The hash encodes
+    /// the wave type as one byte, followed by each of the other parameters
+    /// (except `sound_vol`, which is left at its default) as a
+    /// little-endian 32-bit float.
+    pub fn from_b58(hash: &str) -> Result<SfxrParams, SfxrError> {
+        let bytes = base58_decode(hash.trim_start_matches('#'))?;
+        if bytes.len() != 1 + 4 * SFXR_HASH_ORDER.len() {
+            return Err(SfxrError::WrongLength(bytes.len()));
+        }
+        let mut params = SfxrParams {
+            wave_type: SfxrWaveType::from_index(bytes[0] as i64)?,
+            ..SfxrParams::default()
+        };
+        for (index, key) in SFXR_HASH_ORDER.iter().enumerate() {
+            let value = read_f32(&bytes[1 + 4 * index..]);
+            *params.param_mut(key).unwrap() = value;
+        }
+        Ok(params)
+    }
+
+    /// Encodes this preset as a base58 hash, suitable for use in a jsfxr
+    /// sharing URL (see `from_b58`).
+    pub fn to_b58(&self) -> String {
+        let mut bytes = vec![self.wave_type.index()];
+        // Parameters are only looked up by name mutably, so use a copy.
+        let mut params = self.clone();
+        for key in SFXR_HASH_ORDER.iter() {
+            let value = *params.param_mut(key).unwrap();
+            bytes.extend_from_slice(&value.to_bits().to_le_bytes());
+        }
+        base58_encode(&bytes)
+    }
+
+    /// Parses a preset from the contents of a `.sfs` file saved by
+    /// DrPetter's original sfxr (versions 100 to 102).
+    pub fn from_sfs(data: &[u8]) -> Result<SfxrParams, SfxrError> {
+        let mut reader = SfsReader { data, offset: 0 };
+        let version = reader.int()?;
+        if !(100..=102).contains(&version) {
+            return Err(SfxrError::UnsupportedVersion(version));
+        }
+        let mut params = SfxrParams {
+            wave_type: SfxrWaveType::from_index(reader.int()? as i64)?,
+            ..SfxrParams::default()
+        };
+        if version == 102 {
+            params.sound_vol = reader.float()?;
+        }
+        params.base_freq = reader.float()?;
+        params.freq_limit = reader.float()?;
+        params.freq_ramp = reader.float()?;
+        if version >= 101 {
+            params.freq_dramp = reader.float()?;
+        }
+        params.duty = reader.float()?;
+        params.duty_ramp = reader.float()?;
+        params.vib_strength = reader.float()?;
+        params.vib_speed = reader.float()?;
+        reader.float()?; // vibrato delay (unused by sfxr)
+        params.env_attack = reader.float()?;
+        params.env_sustain = reader.float()?;
+        params.env_decay = reader.float()?;
+        params.env_punch = reader.float()?;
+        reader.byte()?; // filter on/off flag (unused by sfxr)
+        params.lpf_resonance = reader.float()?;
+        params.lpf_freq = reader.float()?;
+        params.lpf_ramp = reader.float()?;
+        params.hpf_freq = reader.float()?;
+        params.hpf_ramp = reader.float()?;
+        params.pha_offset = reader.float()?;
+        params.pha_ramp = reader.float()?;
+        params.repeat_speed = reader.float()?;
+        if version >= 101 {
+            params.arp_speed = reader.float()?;
+            params.arp_mod = reader.float()?;
+        }
+        Ok(params)
+    }
+
+    /// Returns the parameter with the given jsfxr name (other than
+    /// `wave_type` and `sound_vol`).
+    fn param_mut(&mut self, key: &str) -> Option<&mut f32> {
+        Some(match key {
+            "p_env_attack" => &mut self.env_attack,
+            "p_env_sustain" => &mut self.env_sustain,
+            "p_env_punch" => &mut self.env_punch,
+            "p_env_decay" => &mut self.env_decay,
+            "p_base_freq" => &mut self.base_freq,
+            "p_freq_limit" => &mut self.freq_limit,
+            "p_freq_ramp" => &mut self.freq_ramp,
+            "p_freq_dramp" => &mut self.freq_dramp,
+            "p_vib_strength" => &mut self.vib_strength,
+            "p_vib_speed" => &mut self.vib_speed,
+            "p_arp_mod" => &mut self.arp_mod,
+            "p_arp_speed" => &mut self.arp_speed,
+            "p_duty" => &mut self.duty,
+            "p_duty_ramp" => &mut self.duty_ramp,
+            "p_repeat_speed" => &mut self.repeat_speed,
+            "p_pha_offset" => &mut self.pha_offset,
+            "p_pha_ramp" => &mut self.pha_ramp,
+            "p_lpf_freq" => &mut self.lpf_freq,
+            "p_lpf_ramp" => &mut self.lpf_ramp,
+            "p_lpf_resonance" => &mut self.lpf_resonance,
+            "p_hpf_freq" => &mut self.hpf_freq,
+            "p_hpf_ramp" => &mut self.hpf_ramp,
+            _ => return None,
+        })
+    }
+}
+
+// ========================================================================= //
+
+impl Wave {
+    /// Creates a sound effect from the given sfxr parameters, using the same
+    /// synthesis algorithm as sfxr.  The sound is synthesized at sfxr's
+    /// sample rate of 44.1 kHz (and linearly interpolated for other step
+    /// sizes), with its volume scaled as in jsfxr.  Noise is generated
+    /// pseudo-randomly but deterministically.  The wave finishes when the
+    /// sound's envelope does, or when its frequency slides below the
+    /// frequency limit.
+    pub fn sfxr(params: SfxrParams) -> Wave {
+        Wave::new(Box::new(SfxrWave::new(params)))
+    }
+}
+
+// ========================================================================= //
+
+/// A sound effect synthesized by the sfxr algorithm.
+#[derive(Clone)]
+struct SfxrWave {
+    params: SfxrParams,
+    rng: Rng,
+    playing: bool,
+    // The last two synthesized samples, and the position between them.
+    outputs: [Option<f32>; 2],
+    position: f64,
+    // Base waveform state:
+    phase: i32,
+    period: i32,
+    fperiod: f64,
+    fmaxperiod: f64,
+    fslide: f64,
+    fdslide: f64,
+    square_duty: f32,
+    square_slide: f32,
+    noise_buffer: [f32; SFXR_NOISE_LEN],
+    // Arpeggio and repeat state:
+    arp_mod: f64,
+    arp_time: u32,
+    arp_limit: u32,
+    rep_time: u32,
+    rep_limit: u32,
+    // Vibrato state:
+    vib_phase: f32,
+    vib_speed: f32,
+    vib_amp: f32,
+    // Envelope state:
+    env_vol: f32,
+    env_stage: usize,
+    env_time: u32,
+    env_length: [u32; 3],
+    // Filter state:
+    fltp: f32,
+    fltdp: f32,
+    fltw: f32,
+    fltw_d: f32,
+    fltdmp: f32,
+    fltphp: f32,
+    flthp: f32,
+    flthp_d: f32,
+    // Phaser state:
+    fphase: f32,
+    fdphase: f32,
+    iphase: usize,
+    ipp: usize,
+    phaser_buffer: Vec<f32>,
+}
+
+impl SfxrWave {
+    fn new(params: SfxrParams) -> SfxrWave {
+        let mut wave = SfxrWave {
+            params,
+            rng: Rng::new(SFXR_INIT_SEED),
+            playing: true,
+            outputs: [None, None],
+            position: 0.0,
+            phase: 0,
+            period: 0,
+            fperiod: 0.0,
+            fmaxperiod: 0.0,
+            fslide: 0.0,
+            fdslide: 0.0,
+            square_duty: 0.0,
+            square_slide: 0.0,
+            noise_buffer: [0.0; SFXR_NOISE_LEN],
+            arp_mod: 0.0,
+            arp_time: 0,
+            arp_limit: 0,
+            rep_time: 0,
+            rep_limit: 0,
+            vib_phase: 0.0,
+            vib_speed: 0.0,
+            vib_amp: 0.0,
+            env_vol: 0.0,
+            env_stage: 0,
+            env_time: 0,
+            env_length: [0; 3],
+            fltp: 0.0,
+            fltdp: 0.0,
+            fltw: 0.0,
+            fltw_d: 0.0,
+            fltdmp: 0.0,
+            fltphp: 0.0,
+            flthp: 0.0,
+            flthp_d: 0.0,
+            fphase: 0.0,
+            fdphase: 0.0,
+            iphase: 0,
+            ipp: 0,
+            phaser_buffer: vec![0.0; SFXR_PHASER_LEN],
+        };
+        wave.reset();
+        wave
+    }
+
+    /// Resets the frequency, duty, and arpeggio parameters; this happens at
+    /// the start of the sound and on each repeat.
+    fn restart(&mut self) {
+        let p = &self.params;
+        self.fperiod = 100.0 / (p.base_freq as f64 * p.base_freq as f64 +
+                                0.001);
+        self.period = self.fperiod as i32;
+        self.fmaxperiod = 100.0 /
+                          (p.freq_limit as f64 * p.freq_limit as f64 + 0.001);
+        self.fslide = 1.0 - (p.freq_ramp as f64).powi(3) * 0.01;
+        self.fdslide = -(p.freq_dramp as f64).powi(3) * 0.000001;
+        self.square_duty = 0.5 - p.duty * 0.5;
+        self.square_slide = -p.duty_ramp * 0.00005;
+        self.arp_mod = if p.arp_mod >= 0.0 {
+            1.0 - (p.arp_mod as f64).powi(2) * 0.9
+        } else {
+            1.0 + (p.arp_mod as f64).powi(2) * 10.0
+        };
+        self.arp_time = 0;
+        self.arp_limit = if p.arp_speed == 1.0 {
+            0
+        } else {
+            ((1.0 - p.arp_speed).powi(2) * 20000.0 + 32.0) as u32
+        };
+    }
+
+    /// Synthesizes the next sample at sfxr's sample rate, or returns `None`
+    /// if the sound has finished.
+    fn synth_sample(&mut self) -> Option<f32> {
+        if !self.playing {
+            return None;
+        }
+        self.rep_time += 1;
+        if self.rep_limit != 0 && self.rep_time >= self.rep_limit {
+            self.rep_time = 0;
+            self.restart();
+        }
+        // Frequency envelopes and arpeggios:
+        self.arp_time += 1;
+        if self.arp_limit != 0 && self.arp_time >= self.arp_limit {
+            self.arp_limit = 0;
+            self.fperiod *= self.arp_mod;
+        }
+        self.fslide += self.fdslide;
+        self.fperiod *= self.fslide;
+        if self.fperiod > self.fmaxperiod {
+            self.fperiod = self.fmaxperiod;
+            if self.params.freq_limit > 0.0 {
+                self.playing = false;
+            }
+        }
+        let mut rfperiod = self.fperiod;
+        if self.vib_amp > 0.0 {
+            self.vib_phase += self.vib_speed;
+            rfperiod = self.fperiod *
+                       (1.0 + (self.vib_phase.sin() * self.vib_amp) as f64);
+        }
+        self.period = (rfperiod as i32).max(8);
+        self.square_duty = (self.square_duty + self.square_slide)
+            .clamp(0.0, 0.5);
+        // Volume envelope:
+        self.env_time += 1;
+        if self.env_time > self.env_length[self.env_stage] {
+            self.env_time = 0;
+            self.env_stage += 1;
+            if self.env_stage == 3 {
+                self.playing = false;
+            }
+        }
+        if self.env_stage < 3 {
+            let progress = self.env_time as f32 /
+                           self.env_length[self.env_stage].max(1) as f32;
+            self.env_vol = match self.env_stage {
+                0 => progress,
+                1 => 1.0 + (1.0 - progress) * 2.0 * self.params.env_punch,
+                _ => 1.0 - progress,
+            };
+        }
+        // Phaser step:
+        self.fphase += self.fdphase;
+        self.iphase = (self.fphase as i32).unsigned_abs().min(1023) as usize;
+        if self.flthp_d != 0.0 {
+            self.flthp = (self.flthp * self.flthp_d).clamp(0.00001, 0.1);
+        }
+        let mut total = 0.0;
+        for _ in 0..SFXR_SUPERSAMPLING {
+            total += self.synth_subsample();
+        }
+        let gain = self.params.sound_vol.exp() - 1.0;
+        Some((total / SFXR_SUPERSAMPLING as f32 * gain).clamp(-1.0, 1.0))
+    }
+
+    /// Synthesizes the next sub-sample, including the filters, phaser, and
+    /// envelope.
+    fn synth_subsample(&mut self) -> f32 {
+        self.phase += 1;
+        if self.phase >= self.period {
+            self.phase %= self.period;
+            if self.params.wave_type == SfxrWaveType::Noise {
+                self.fill_noise_buffer();
+            }
+        }
+        // Base waveform:
+        let fp = self.phase as f32 / self.period as f32;
+        let mut sample = match self.params.wave_type {
+            SfxrWaveType::Square => {
+                if fp < self.square_duty { 0.5 } else { -0.5 }
+            }
+            SfxrWaveType::Sawtooth => 1.0 - fp * 2.0,
+            SfxrWaveType::Sine => (fp * 2.0 * std::f32::consts::PI).sin(),
+            SfxrWaveType::Noise => {
+                let index = self.phase as usize * SFXR_NOISE_LEN /
+                            self.period as usize;
+                self.noise_buffer[index]
+            }
+        };
+        // Lowpass filter:
+        let pp = self.fltp;
+        self.fltw = (self.fltw * self.fltw_d).clamp(0.0, 0.1);
+        if self.params.lpf_freq != 1.0 {
+            self.fltdp += (sample - self.fltp) * self.fltw;
+            self.fltdp -= self.fltdp * self.fltdmp;
+        } else {
+            self.fltp = sample;
+            self.fltdp = 0.0;
+        }
+        self.fltp += self.fltdp;
+        // Highpass filter:
+        self.fltphp += self.fltp - pp;
+        self.fltphp -= self.fltphp * self.flthp;
+        sample = self.fltphp;
+        // Phaser:
+        self.phaser_buffer[self.ipp % SFXR_PHASER_LEN] = sample;
+        let delayed = (self.ipp + SFXR_PHASER_LEN - self.iphase) %
+                      SFXR_PHASER_LEN;
+        sample += self.phaser_buffer[delayed];
+        self.ipp = (self.ipp + 1) % SFXR_PHASER_LEN;
+        sample * self.env_vol
+    }
+
+    fn fill_noise_buffer(&mut self) {
+        for value in self.noise_buffer.iter_mut() {
+            *value = self.rng.next_signed();
+        }
+    }
+}
+
+impl WaveGen for SfxrWave {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        let first = self.outputs[0]?;
+        let value = match self.outputs[1] {
+            Some(second) => first + (second - first) * self.position as f32,
+            None => first,
+        };
+        // Snap the step to a whole number of sfxr samples when it is very
+        // close to one, so that playing back at sfxr's own sample rate
+        // reproduces its output exactly.
+        let mut advance = step as f64 * SFXR_SAMPLE_RATE;
+        if (advance - advance.round()).abs() < 1e-4 {
+            advance = advance.round();
+        }
+        self.position += advance;
+        while self.position >= 1.0 {
+            self.position -= 1.0;
+            let next = match self.outputs[1] {
+                Some(_) => self.synth_sample(),
+                None => None,
+            };
+            self.outputs = [self.outputs[1], next];
+        }
+        Some(value)
+    }
+
+    fn reset(&mut self) {
+        self.rng = Rng::new(SFXR_INIT_SEED);
+        self.playing = true;
+        self.phase = 0;
+        self.restart();
+        let p = &self.params;
+        self.fltp = 0.0;
+        self.fltdp = 0.0;
+        self.fltw = p.lpf_freq.powi(3) * 0.1;
+        self.fltw_d = 1.0 + p.lpf_ramp * 0.0001;
+        self.fltdmp = (5.0 / (1.0 + p.lpf_resonance.powi(2) * 20.0) *
+                       (0.01 + self.fltw))
+            .min(0.8);
+        self.fltphp = 0.0;
+        self.flthp = p.hpf_freq.powi(2) * 0.1;
+        self.flthp_d = 1.0 + p.hpf_ramp * 0.0003;
+        self.vib_phase = 0.0;
+        self.vib_speed = p.vib_speed.powi(2) * 0.01;
+        self.vib_amp = p.vib_strength * 0.5;
+        self.env_vol = 0.0;
+        self.env_stage = 0;
+        self.env_time = 0;
+        self.env_length = [(p.env_attack * p.env_attack * 100000.0) as u32,
+                           (p.env_sustain * p.env_sustain * 100000.0) as u32,
+                           (p.env_decay * p.env_decay * 100000.0) as u32];
+        self.fphase = p.pha_offset.powi(2) * 1020.0 * p.pha_offset.signum();
+        self.fdphase = p.pha_ramp.powi(2) * p.pha_ramp.signum();
+        self.iphase = (self.fphase as i32).unsigned_abs() as usize;
+        self.ipp = 0;
+        for value in self.phaser_buffer.iter_mut() {
+            *value = 0.0;
+        }
+        self.rep_time = 0;
+        self.rep_limit = if p.repeat_speed == 0.0 {
+            0
+        } else {
+            ((1.0 - p.repeat_speed).powi(2) * 20000.0 + 32.0) as u32
+        };
+        self.fill_noise_buffer();
+        let first = self.synth_sample();
+        let second = self.synth_sample();
+        self.outputs = [first, second];
+        self.position = 0.0;
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+}
+
+// ========================================================================= //
+
+/// Reads little-endian values from the contents of a `.sfs` file.
+struct SfsReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> SfsReader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], SfxrError> {
+        if self.offset + len > self.data.len() {
+            return Err(SfxrError::WrongLength(self.data.len()));
+        }
+        let bytes = &self.data[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, SfxrError> { Ok(self.bytes(1)?[0]) }
+
+    fn int(&mut self) -> Result<i32, SfxrError> {
+        Ok(read_u32(self.bytes(4)?) as i32)
+    }
+
+    fn float(&mut self) -> Result<f32, SfxrError> {
+        Ok(read_f32(self.bytes(4)?))
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn read_f32(bytes: &[u8]) -> f32 { f32::from_bits(read_u32(bytes)) }
+
+// ========================================================================= //
+
+fn base58_decode(text: &str) -> Result<Vec<u8>, SfxrError> {
+    // Each character multiplies the big-endian number so far by 58 and adds
+    // the character's digit value.
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut leading_zeros = 0;
+    for chr in text.chars() {
+        let digit = BASE58_ALPHABET.iter()
+            .position(|&letter| letter as char == chr)
+            .ok_or(SfxrError::InvalidBase58(chr))?;
+        if digit == 0 && bytes.is_empty() {
+            leading_zeros += 1;
+            continue;
+        }
+        let mut carry = digit as u32;
+        for byte in bytes.iter_mut().rev() {
+            carry += *byte as u32 * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.insert(0, carry as u8);
+            carry >>= 8;
+        }
+    }
+    let mut result = vec![0; leading_zeros];
+    result.extend(bytes);
+    Ok(result)
+}
+
+fn base58_encode(bytes: &[u8]) -> String {
+    // Each byte multiplies the little-endian base58 digits so far by 256 and
+    // adds the byte's value.
+    let leading_zeros = bytes.iter().take_while(|&&byte| byte == 0).count();
+    let mut digits: Vec<u8> = Vec::new();
+    for &byte in bytes[leading_zeros..].iter() {
+        let mut carry = byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let mut text: String = (0..leading_zeros).map(|_| '1').collect();
+    text.extend(digits.iter().rev().map(|&digit| {
+        BASE58_ALPHABET[digit as usize] as char
+    }));
+    text
+}
+
+// ========================================================================= //
+
+/// Parses a flat JSON object, returning its numeric (and boolean) values;
+/// string and null values are skipped.
+fn parse_json_object(json: &str) -> Result<Vec<(String, f64)>, SfxrError> {
+    let mut chars = json.chars().peekable();
+    let mut values = Vec::new();
+    expect_json_char(&mut chars, '{')?;
+    skip_json_whitespace(&mut chars);
+    if chars.peek() == Some(&'}') {
+        chars.next();
+    } else {
+        loop {
+            skip_json_whitespace(&mut chars);
+            let key = parse_json_string(&mut chars)?;
+            expect_json_char(&mut chars, ':')?;
+            skip_json_whitespace(&mut chars);
+            if let Some(value) = parse_json_scalar(&mut chars)? {
+                values.push((key, value));
+            }
+            skip_json_whitespace(&mut chars);
+            match chars.next() {
+                Some(',') => {}
+                Some('}') => break,
+                _ => return Err(SfxrError::InvalidJson),
+            }
+        }
+    }
+    skip_json_whitespace(&mut chars);
+    if chars.next().is_some() {
+        return Err(SfxrError::InvalidJson);
+    }
+    Ok(values)
+}
+
+fn skip_json_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.peek().is_some_and(|chr| chr.is_whitespace()) {
+        chars.next();
+    }
+}
+
+fn expect_json_char(chars: &mut Peekable<Chars>, expected: char)
+                    -> Result<(), SfxrError> {
+    skip_json_whitespace(chars);
+    if chars.next() == Some(expected) {
+        Ok(())
+    } else {
+        Err(SfxrError::InvalidJson)
+    }
+}
+
+fn parse_json_string(chars: &mut Peekable<Chars>)
+                     -> Result<String, SfxrError> {
+    expect_json_char(chars, '"')?;
+    let mut string = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(string),
+            Some('\\') => {
+                match chars.next() {
+                    Some(chr) => string.push(chr),
+                    None => return Err(SfxrError::InvalidJson),
+                }
+            }
+            Some(chr) => string.push(chr),
+            None => return Err(SfxrError::InvalidJson),
+        }
+    }
+}
+
+/// Parses a JSON number, boolean (as 0 or 1), string, or null, returning
+/// `None` for strings and nulls.
+fn parse_json_scalar(chars: &mut Peekable<Chars>)
+                     -> Result<Option<f64>, SfxrError> {
+    if chars.peek() == Some(&'"') {
+        parse_json_string(chars)?;
+        return Ok(None);
+    }
+    let mut token = String::new();
+    while let Some(&chr) = chars.peek() {
+        if chr.is_ascii_alphanumeric() || chr == '-' || chr == '+' ||
+           chr == '.' {
+            token.push(chr);
+            chars.next();
+        } else {
+            break;
+        }
+    }
+    match token.as_str() {
+        "true" => Ok(Some(1.0)),
+        "false" => Ok(Some(0.0)),
+        "null" => Ok(None),
+        _ => token.parse().map(Some).map_err(|_| SfxrError::InvalidJson),
+    }
+}
+
+// ========================================================================= //
+
+#[cfg(test)]
+mod tests {
+    use super::{SfxrError, SfxrParams, SfxrWaveType, base58_decode,
+                base58_encode};
+    use super::super::{Wave, WaveGen, render};
+
+    #[test]
+    fn default_sound() {
+        // The default sound lasts for the attack, sustain, and decay times
+        // (0, 0.3^2, and 0.4^2 times 100000 samples), plus one sample for
+        // each stage transition.
+        let samples = render(Wave::sfxr(SfxrParams::default()),
+                             1.0 / 44100.0,
+                             1000000);
+        assert_eq!(25003, samples.len());
+        // It's a square wave of amplitude 0.5, doubled by the phaser (which,
+        // with no offset, adds each sample to itself), and scaled by the
+        // volume, give or take a little drift from the highpass filter.
+        let gain = 0.5f32.exp() - 1.0;
+        let peak = samples.iter().fold(0.0f32, |peak, &v| peak.max(v.abs()));
+        assert!((peak - gain).abs() < 0.01, "peak = {}", peak);
+        // Playing back at a different rate takes the same amount of time.
+        let samples = render(Wave::sfxr(SfxrParams::default()),
+                             1.0 / 22050.0,
+                             1000000);
+        assert_eq!(12502, samples.len());
+    }
+
+    #[test]
+    fn freq_limit() {
+        // A downward slide that hits the frequency limit stops the sound
+        // before the envelope finishes.
+        let params = SfxrParams {
+            freq_ramp: -0.5,
+            freq_limit: 0.2,
+            env_sustain: 1.0,
+            ..SfxrParams::default()
+        };
+        let samples = render(Wave::sfxr(params), 1.0 / 44100.0, 1000000);
+        assert!(samples.len() < 20000, "len = {}", samples.len());
+    }
+
+    #[test]
+    fn sfxr_is_deterministic() {
+        let params = SfxrParams {
+            wave_type: SfxrWaveType::Noise,
+            vib_strength: 0.5,
+            vib_speed: 0.5,
+            pha_offset: 0.3,
+            lpf_freq: 0.5,
+            lpf_resonance: 0.5,
+            hpf_freq: 0.2,
+            repeat_speed: 0.5,
+            arp_mod: -0.5,
+            arp_speed: 0.5,
+            ..SfxrParams::default()
+        };
+        let mut wave = Wave::sfxr(params);
+        let first = render(wave.clone(), 1.0 / 44100.0, 1000000);
+        assert!(first.iter().all(|value| value.abs() <= 1.0));
+        wave.next(1.0 / 44100.0);
+        wave.reset();
+        assert_eq!(first, render(wave, 1.0 / 44100.0, 1000000));
+    }
+
+    #[test]
+    fn base58() {
+        assert_eq!(Vec::<u8>::new(), base58_decode("").unwrap());
+        assert_eq!(vec![0, 0, 1], base58_decode("112").unwrap());
+        assert_eq!(vec![0x01, 0x00], base58_decode("5R").unwrap());
+        assert_eq!("5R", base58_encode(&[0x01, 0x00]));
+        assert_eq!("112", base58_encode(&[0, 0, 1]));
+        assert_eq!(Err(SfxrError::InvalidBase58('0')), base58_decode("10"));
+    }
+
+    #[test]
+    fn hash_round_trip() {
+        let params = SfxrParams {
+            wave_type: SfxrWaveType::Sawtooth,
+            freq_ramp: -0.25,
+            arp_mod: 0.75,
+            hpf_ramp: 0.125,
+            ..SfxrParams::default()
+        };
+        let hash = params.to_b58();
+        assert_eq!(Ok(params.clone()), SfxrParams::from_b58(&hash));
+        assert_eq!(Ok(params), SfxrParams::from_b58(&format!("#{}", hash)));
+        assert_eq!(Err(SfxrError::WrongLength(1)),
+                   SfxrParams::from_b58("2"));
+    }
+
+    #[test]
+    fn jsfxr_hash() {
+        // This is the example sound from jsfxr's README, which was made with
+        // its randomize button.
+        let hash = "5EoyNVSymuxD8s7HP1ixqdaCn5uVGEgwQ3kJBR7bSoApFQzm7E4zZ\
+                    PW2EcXm3jmNdTtTPeDuvwjY8z4exqaXz3NGBHRKBx3igYfBBMRBxDA\
+                    LhBSvzkF6VE2Pv";
+        let params = SfxrParams::from_b58(hash).unwrap();
+        assert_eq!(SfxrWaveType::Sine, params.wave_type);
+        assert_eq!(0.1814707, params.env_sustain);
+        assert_eq!(0.89438933, params.base_freq);
+        assert_eq!(0.0, params.freq_limit);
+        assert_eq!(0.99997437, params.lpf_freq);
+        assert_eq!(0.09905284, params.hpf_ramp);
+        assert_eq!(hash, params.to_b58());
+        let samples = render(Wave::sfxr(params), 1.0 / 44100.0, 1000000);
+        assert!(samples.iter().any(|value| value.abs() > 0.1));
+    }
+
+    #[test]
+    fn json_import() {
+        let json = r#"{"oldParams": true, "wave_type": 2,
+                       "p_env_attack": 0.1, "p_env_sustain": 0.25,
+                       "p_freq_ramp": -1.5e-1, "p_lpf_freq": 1,
+                       "sound_vol": 0.25, "sample_rate": 44100,
+                       "name": "Test \"sound\""}"#;
+        let params = SfxrParams::from_json(json).unwrap();
+        assert_eq!(SfxrWaveType::Sine, params.wave_type);
+        assert_eq!(0.1, params.env_attack);
+        assert_eq!(0.25, params.env_sustain);
+        assert_eq!(-0.15, params.freq_ramp);
+        assert_eq!(0.25, params.sound_vol);
+        assert_eq!(SfxrParams::default().env_decay, params.env_decay);
+        assert_eq!(Err(SfxrError::InvalidJson),
+                   SfxrParams::from_json("{\"wave_type\": 1"));
+        assert_eq!(Err(SfxrError::UnknownWaveType(7)),
+                   SfxrParams::from_json("{\"wave_type\": 7}"));
+    }
+
+    #[test]
+    fn sfs_import() {
+        let mut data = Vec::new();
+        data.extend_from_slice(&102i32.to_le_bytes());
+        data.extend_from_slice(&1i32.to_le_bytes());
+        let floats = [0.75, 0.5, 0.0, 0.0, -0.25, 0.125, 0.0, 0.0, 0.0,
+                      0.0, 0.0, 0.3, 0.4, 0.2];
+        for value in floats.iter() {
+            data.extend_from_slice(&f32::to_le_bytes(*value));
+        }
+        data.push(0);
+        let floats = [0.0, 0.9, 0.0, 0.1, 0.0, 0.0, 0.0, 0.6, 0.7, 0.8];
+        for value in floats.iter() {
+            data.extend_from_slice(&f32::to_le_bytes(*value));
+        }
+        let params = SfxrParams::from_sfs(&data).unwrap();
+        assert_eq!(SfxrWaveType::Sawtooth, params.wave_type);
+        assert_eq!(0.75, params.sound_vol);
+        assert_eq!(0.5, params.base_freq);
+        assert_eq!(-0.25, params.freq_dramp);
+        assert_eq!(0.125, params.duty);
+        assert_eq!(0.3, params.env_sustain);
+        assert_eq!(0.2, params.env_punch);
+        assert_eq!(0.9, params.lpf_freq);
+        assert_eq!(0.1, params.hpf_freq);
+        assert_eq!(0.6, params.repeat_speed);
+        assert_eq!(0.7, params.arp_speed);
+        assert_eq!(0.8, params.arp_mod);
+        // Version 100 files lack the volume, delta slide, and arpeggio.
+        let mut old = 100i32.to_le_bytes().to_vec();
+        old.extend_from_slice(&data[4..8]);
+        old.extend_from_slice(&data[12..24]);
+        old.extend_from_slice(&data[28..data.len() - 8]);
+        let params = SfxrParams::from_sfs(&old).unwrap();
+        assert_eq!(0.5, params.sound_vol);
+        assert_eq!(0.0, params.freq_dramp);
+        assert_eq!(0.125, params.duty);
+        assert_eq!(0.6, params.repeat_speed);
+        assert_eq!(0.0, params.arp_mod);
+        assert_eq!(Err(SfxrError::UnsupportedVersion(99)),
+                   SfxrParams::from_sfs(&99i32.to_le_bytes()));
+        assert_eq!(Err(SfxrError::WrongLength(20)),
+                   SfxrParams::from_sfs(&data[..20]));
+    }
+}
+
+// ========================================================================= //
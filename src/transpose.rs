@@ -0,0 +1,530 @@
+//! Changing the pitch and speed of existing waveforms.
+
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use super::{Sample, Wave, WaveGen};
+
+// ========================================================================= //
+
+/// The smallest pitch ratio used by `Wave::pitched`; smaller (or negative)
+/// ratios are clamped to this.
+const PITCH_MIN_RATIO: f32 = 1e-3;
+
+/// The smallest pitch ratio used by the formant-preserving pitch shifter;
+/// smaller (or negative) ratios are clamped to this.
+const PSOLA_MIN_RATIO: f32 = 1e-3;
+
+/// The range of fundamental frequencies (in hertz) detected by the
+/// formant-preserving pitch shifter.
+const PSOLA_MIN_FREQ: f32 = 60.0;
+const PSOLA_MAX_FREQ: f32 = 1000.0;
+
+/// The longest period (in samples) that the formant-preserving pitch shifter
+/// looks for, which keeps the cost of pitch estimation bounded at very small
+/// steps.
+const PSOLA_MAX_PERIOD: usize = 4096;
+
+/// The grain rate (in hertz) used by the formant-preserving pitch shifter
+/// when the source has no detectable pitch.
+const PSOLA_UNVOICED_FREQ: f32 = 100.0;
+
+/// How often the formant-preserving pitch shifter re-estimates the source's
+/// pitch, in seconds.
+const PSOLA_ESTIMATE_INTERVAL: f32 = 0.02;
+
+/// The minimum normalized autocorrelation for the source to be considered
+/// pitched.
+const PSOLA_VOICED_THRESHOLD: f32 = 0.5;
+
+/// When estimating the source's period, the shortest lag whose
+/// autocorrelation is at least this fraction of the best one is chosen, to
+/// avoid mistaking a multiple of the period for the period itself.
+const PSOLA_PEAK_TOLERANCE: f32 = 0.9;
+
+// ========================================================================= //
+
+/// How `Wave::pitched_with` and `Wave::transpose_with` change the pitch of a
+/// waveform.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PitchMode {
+    /// Plays the waveform faster or slower, like changing the speed of a
+    /// tape: everything about the sound, including its formants and its
+    /// duration, scales with the pitch.
+    Resample,
+    /// Changes the pitch of the waveform while preserving its formants (its
+    /// spectral envelope) and its duration, using pitch-synchronous
+    /// overlap-add (PSOLA).  This works best on sounds with a single clear
+    /// pitch between 60 and 1000 Hz, such as voices or monophonic
+    /// instruments, and for ratios within an octave or so of 1.
+    PreserveFormants,
+}
+
+// ========================================================================= //
+
+impl Wave {
+    /// Plays this waveform at a varying speed, by scaling the step passed to
+    /// it by the value of the `factor` waveform (which may be a constant);
+    /// e.g. a factor of 2 plays it twice as fast (and so an octave higher),
+    /// while a factor of 0 pauses it, holding its current value.  Negative
+    /// factors are treated as 0.  Sliding the factor down to 0 gives a "tape
+    /// stop" effect.  The new waveform finishes as soon as either input
+    /// waveform does.
+    pub fn speed<F: Into<Wave>>(self, factor: F) -> Wave {
+        Wave::new(Box::new(Speed {
+            wave: self,
+            factor: factor.into(),
+            min_factor: 0.0,
+        }))
+    }
+
+    /// Plays this waveform at a different pitch, scaling its speed by the
+    /// value of the `ratio` waveform (which may be a constant); e.g. a ratio
+    /// of 2 plays it an octave higher, and twice as fast.  This is
+    /// equivalent to `speed`, and lets one instrument definition be reused
+    /// at many pitches, even when its frequency inputs are buried inside it.
+    /// Unlike with `speed`, ratios below 0.001 (including zero and negative
+    /// ratios) are treated as 0.001, so the waveform never quite stops.  See
+    /// `pitched_with` to preserve formants and duration instead.
+    pub fn pitched<R: Into<Wave>>(self, ratio: R) -> Wave {
+        self.pitched_with(ratio, PitchMode::Resample)
+    }
+
+    /// Like `pitched`, but changes the pitch using the given mode.
+    pub fn pitched_with<R: Into<Wave>>(self, ratio: R, mode: PitchMode)
+                                       -> Wave {
+        match mode {
+            PitchMode::Resample => {
+                Wave::new(Box::new(Speed {
+                    wave: self,
+                    factor: ratio.into(),
+                    min_factor: PITCH_MIN_RATIO,
+                }))
+            }
+            PitchMode::PreserveFormants => {
+                Wave::new(Box::new(Psola::new(self, ratio.into())))
+            }
+        }
+    }
+
+    /// Plays this waveform shifted in pitch by the number of equal-tempered
+    /// semitones given by the `semitones` waveform (which may be a constant,
+    /// and may be negative to lower the pitch).  This is equivalent to
+    /// `pitched` with the corresponding frequency ratio.
+    pub fn transpose<S: Into<Wave>>(self, semitones: S) -> Wave {
+        self.transpose_with(semitones, PitchMode::Resample)
+    }
+
+    /// Like `transpose`, but changes the pitch using the given mode.
+    pub fn transpose_with<S: Into<Wave>>(self, semitones: S, mode: PitchMode)
+                                         -> Wave {
+        self.pitched_with(Wave::from(1.0).offset_semitones(semitones), mode)
+    }
+}
+
+// ========================================================================= //
+
+/// A waveform played with a scaled step.
+#[derive(Clone)]
+struct Speed {
+    wave: Wave,
+    factor: Wave,
+    /// Smaller factors are clamped to this.
+    min_factor: f32,
+}
+
+impl WaveGen for Speed {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        let factor = self.factor.next(step)?.max(self.min_factor);
+        self.wave.next(step * factor)
+    }
+
+    fn reset(&mut self) {
+        self.wave.reset();
+        self.factor.reset();
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+}
+
+// ========================================================================= //
+
+/// A formant-preserving pitch shifter, using pitch-synchronous overlap-add.
+///
+/// The source is generated at its normal speed, a little ahead of the
+/// output.  Grains two periods long are cut from the source at intervals of
+/// whole periods, windowed, and laid down in the output at intervals of the
+/// period divided by the pitch ratio; each grain keeps the source's
+/// spectral envelope, while their spacing sets the new pitch.  All times are
+/// measured in samples.
+#[derive(Clone)]
+struct Psola {
+    wave: Wave,
+    ratio: Wave,
+    /// Recent samples of the source, starting with sample `input_start`.
+    input: VecDeque<Sample>,
+    input_start: usize,
+    input_done: bool,
+    /// The index of the next output sample.
+    position: usize,
+    /// The range of periods that can be detected; zero until the first
+    /// positive step.
+    min_period: usize,
+    max_period: usize,
+    /// The current estimate of the source's period.
+    period: f64,
+    next_estimate: usize,
+    /// The output time at which the next grain is centered.
+    next_mark: f64,
+    /// The source time at which the previous grain was centered.
+    last_analysis: Option<f64>,
+    grains: Vec<PsolaGrain>,
+    /// The most recent output value.
+    output: Sample,
+}
+
+impl Psola {
+    fn new(wave: Wave, ratio: Wave) -> Psola {
+        Psola {
+            wave,
+            ratio,
+            input: VecDeque::new(),
+            input_start: 0,
+            input_done: false,
+            position: 0,
+            min_period: 0,
+            max_period: 0,
+            period: 0.0,
+            next_estimate: 0,
+            next_mark: 0.0,
+            last_analysis: None,
+            grains: Vec::new(),
+            output: 0.0,
+        }
+    }
+
+    fn input_end(&self) -> usize { self.input_start + self.input.len() }
+
+    /// Returns the period to use when the source has no detectable pitch.
+    fn unvoiced_period(&self, step: f32) -> f64 {
+        let period = 1.0 / (PSOLA_UNVOICED_FREQ * step) as f64;
+        period.min(self.max_period as f64)
+    }
+
+    /// Returns the source sample at the given index, or zero if it isn't
+    /// available.
+    fn sample(&self, index: isize) -> Sample {
+        if index < self.input_start as isize {
+            return 0.0;
+        }
+        let offset = index as usize - self.input_start;
+        self.input.get(offset).cloned().unwrap_or(0.0)
+    }
+
+    /// Returns the source value at the given time, interpolating linearly.
+    fn value_at(&self, time: f64) -> Sample {
+        let floor = time.floor();
+        let fraction = (time - floor) as f32;
+        let first = self.sample(floor as isize);
+        let second = self.sample(floor as isize + 1);
+        first + fraction * (second - first)
+    }
+
+    /// Re-estimates the source's period from the normalized autocorrelation
+    /// of the source just ahead of the current position, keeping the
+    /// previous estimate during silence.
+    fn estimate_period(&mut self, step: f32) {
+        let start = self.position as isize;
+        let window = self.max_period as isize;
+        let energy_at = |psola: &Psola, offset: isize| -> f32 {
+            (0..window)
+                .map(|index| psola.sample(start + offset + index))
+                .map(|value| value * value)
+                .sum()
+        };
+        let energy = energy_at(self, 0);
+        if energy <= 0.0 {
+            return;
+        }
+        let correlations: Vec<f32> = (self.min_period..self.max_period + 1)
+            .map(|lag| {
+                let lag = lag as isize;
+                let product: f32 = (0..window)
+                    .map(|index| {
+                        self.sample(start + index) *
+                        self.sample(start + index + lag)
+                    })
+                    .sum();
+                let lagged = energy_at(self, lag);
+                if lagged > 0.0 {
+                    product / (energy * lagged).sqrt()
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        let best = correlations.iter().cloned().fold(0.0, f32::max);
+        if best < PSOLA_VOICED_THRESHOLD {
+            self.period = self.unvoiced_period(step);
+            return;
+        }
+        let last = correlations.len() - 1;
+        let index = (0..correlations.len())
+            .find(|&index| {
+                let value = correlations[index];
+                value >= PSOLA_PEAK_TOLERANCE * best &&
+                (index == 0 || value >= correlations[index - 1]) &&
+                (index == last || value >= correlations[index + 1])
+            })
+            .unwrap();
+        // Refine the peak to a fractional lag with parabolic interpolation.
+        let mut lag = (self.min_period + index) as f64;
+        if index > 0 && index < last {
+            let (before, peak, after) = (correlations[index - 1],
+                                         correlations[index],
+                                         correlations[index + 1]);
+            let curvature = before - 2.0 * peak + after;
+            if curvature < 0.0 {
+                lag += (0.5 * (before - after) / curvature) as f64;
+            }
+        }
+        self.period = lag;
+    }
+
+    /// Starts a new grain, centered at the next mark in the output, and
+    /// taken from a whole number of periods after the previous grain in the
+    /// source.
+    fn spawn(&mut self, ratio: f64) {
+        let period = self.period;
+        let mark = self.next_mark;
+        let analysis = match self.last_analysis {
+            Some(last) => {
+                last + ((mark - last) / period).round().max(0.0) * period
+            }
+            None => mark,
+        };
+        self.grains.push(PsolaGrain {
+            input_start: analysis - period,
+            output_start: mark - period,
+            length: 2.0 * period,
+        });
+        self.last_analysis = Some(analysis);
+        self.next_mark += period / ratio;
+    }
+}
+
+impl WaveGen for Psola {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        let ratio = self.ratio.next(step)?.max(PSOLA_MIN_RATIO);
+        if step <= 0.0 {
+            // Time isn't passing, so hold the current output.
+            if self.input_done && self.position >= self.input_end() {
+                return None;
+            }
+            return Some(self.output);
+        }
+        if self.max_period == 0 {
+            let max_period = (1.0 / (PSOLA_MIN_FREQ * step)).ceil();
+            let min_period = (1.0 / (PSOLA_MAX_FREQ * step)).floor();
+            self.max_period = (max_period as usize).clamp(2, PSOLA_MAX_PERIOD);
+            self.min_period = (min_period as usize).clamp(1, self.max_period);
+            self.period = self.unvoiced_period(step);
+        }
+        // Keep the source far enough ahead to cover both the grains and the
+        // pitch estimation window.
+        let lookahead = 3 * self.max_period + 2;
+        while !self.input_done &&
+              self.input_end() <= self.position + lookahead {
+            match self.wave.next(step) {
+                Some(value) => self.input.push_back(value),
+                None => self.input_done = true,
+            }
+        }
+        if self.input_done && self.position >= self.input_end() {
+            return None;
+        }
+        if self.position >= self.next_estimate {
+            self.estimate_period(step);
+            let interval = (PSOLA_ESTIMATE_INTERVAL / step).ceil() as usize;
+            self.next_estimate = self.position + interval.max(1);
+        }
+        let time = self.position as f64;
+        while self.next_mark - self.period <= time {
+            self.spawn(ratio as f64);
+        }
+        let mut output = 0.0;
+        for grain in self.grains.iter() {
+            let offset = time - grain.output_start;
+            if offset >= 0.0 && offset < grain.length {
+                let phase = (offset / grain.length) as f32;
+                let gain = 0.5 - 0.5 * (2.0 * PI * phase).cos();
+                output += gain * self.value_at(grain.input_start + offset);
+            }
+        }
+        self.grains.retain(|grain| time + 1.0 - grain.output_start <
+                                   grain.length);
+        while self.input_start + 4 * self.max_period < self.position {
+            self.input.pop_front();
+            self.input_start += 1;
+        }
+        self.position += 1;
+        // Overlapping grains add up, so scale down when raising the pitch.
+        self.output = output / ratio.max(1.0);
+        Some(self.output)
+    }
+
+    fn reset(&mut self) {
+        self.wave.reset();
+        self.ratio.reset();
+        self.input.clear();
+        self.input_start = 0;
+        self.input_done = false;
+        self.position = 0;
+        self.max_period = 0;
+        self.next_estimate = 0;
+        self.next_mark = 0.0;
+        self.last_analysis = None;
+        self.grains.clear();
+        self.output = 0.0;
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+}
+
+/// One grain of a formant-preserving pitch shifter.
+#[derive(Clone)]
+struct PsolaGrain {
+    /// The source time at which the grain starts.
+    input_start: f64,
+    /// The output time at which the grain starts.
+    output_start: f64,
+    /// The grain's length.
+    length: f64,
+}
+
+// ========================================================================= //
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+    use super::PitchMode;
+    use super::super::{Wave, WaveGen, render};
+
+    /// Returns the power of the given frequency in the samples, using the
+    /// Goertzel algorithm.
+    fn power_at(samples: &[f32], freq: f32, step: f32) -> f32 {
+        let coeff = 2.0 * (2.0 * PI * freq * step).cos();
+        let (mut prev, mut prev2) = (0.0, 0.0);
+        for &sample in samples {
+            let value = sample + coeff * prev - prev2;
+            prev2 = prev;
+            prev = value;
+        }
+        prev * prev + prev2 * prev2 - coeff * prev * prev2
+    }
+
+    #[test]
+    fn pitched_scales_step() {
+        let wave = Wave::slide(0.0, 1.0, 0.0).take(1.0).pitched(2.0);
+        assert_eq!(vec![0.0, 0.5], render(wave, 0.25, 100));
+        let wave = Wave::slide(0.0, 1.0, 0.0).pitched(Wave::sequence(vec![
+            Wave::from(1.0).take(0.5),
+            Wave::from(3.0).take(0.5),
+        ]));
+        assert_eq!(vec![0.0, 0.25, 0.5, 1.25], render(wave, 0.25, 100));
+        // Transposing by an octave doubles the pitch.
+        let samples = render(Wave::sine(100.0).transpose(12.0), 0.001, 100);
+        let expected = render(Wave::sine(200.0), 0.001, 100);
+        for (actual, expected) in samples.iter().zip(expected.iter()) {
+            assert!((actual - expected).abs() < 1e-3);
+        }
+        // Reset restarts both waves.
+        let mut wave = Wave::slide(0.0, 1.0, 0.0).transpose(-12.0);
+        wave.next(1.0);
+        wave.reset();
+        assert_eq!(Some(0.0), wave.next(1.0));
+    }
+
+    #[test]
+    fn speed_factor() {
+        // A tape stop: the speed slides down from 1 to 0 (and then below 0,
+        // which is treated as 0), after which the wave holds its value.
+        let factor = Wave::slide(1.0, -1.0, 0.0);
+        let wave = Wave::slide(0.0, 1.0, 0.0).speed(factor).take(2.0);
+        assert_eq!(vec![0.0, 0.5, 0.75, 0.75], render(wave, 0.5, 100));
+        // Very small (or negative) pitch ratios play very slowly.
+        for &ratio in [0.0, -1.0].iter() {
+            let wave = Wave::slide(0.0, 1.0, 0.0).pitched(ratio);
+            assert_approx!(0.002, render(wave, 1.0, 3)[2]);
+        }
+        // Pausing waveforms that need care with zero steps.
+        let wave = Wave::sine(100.0)
+            .pitched_with(2.0, PitchMode::PreserveFormants)
+            .speed(0.0);
+        assert_eq!(vec![0.0; 10], render(wave, 1.0 / 44100.0, 10));
+        let wave = Wave::sine(100.0).reversed().speed(0.0);
+        assert_eq!(vec![0.0; 10], render(wave, 1.0 / 44100.0, 10));
+        let wave = Wave::slide(0.0, 1.0, 0.0).skip(0.5).speed(0.0);
+        assert_eq!(vec![0.5; 10], render(wave, 1.0 / 44100.0, 10));
+    }
+
+    #[test]
+    fn preserve_formants() {
+        // A 150 Hz buzz through a resonant filter at 1200 Hz.
+        let step = 1.0 / 16000.0;
+        let source = || Wave::saw(150.0).bandpass(1200.0, 200.0).take(0.5);
+        let len = render(source(), step, 100000).len();
+        // Raising the pitch by an octave with resampling moves the formant
+        // up with it, and halves the duration.
+        let resampled = render(source().pitched(2.0), step, 100000);
+        assert!((len / 2).abs_diff(resampled.len()) <= 1);
+        assert!(power_at(&resampled, 2400.0, step) >
+                10.0 * power_at(&resampled, 1200.0, step));
+        // Preserving the formants keeps both the formant and the duration.
+        let preserved =
+            render(source().pitched_with(2.0, PitchMode::PreserveFormants),
+                   step,
+                   100000);
+        assert_eq!(len, preserved.len());
+        assert!(power_at(&preserved, 1200.0, step) >
+                10.0 * power_at(&preserved, 2400.0, step));
+        // The fundamental is now 300 Hz, with no energy left at 150 Hz.
+        let settled = &preserved[1600..];
+        assert!(power_at(settled, 300.0, step) >
+                100.0 * power_at(settled, 150.0, step));
+        // The result is deterministic, and reset restarts it.
+        let mut wave = source()
+            .transpose_with(12.0, PitchMode::PreserveFormants);
+        let samples: Vec<f32> = (0..len).map_while(|_| wave.next(step))
+            .collect();
+        assert_eq!(preserved, samples);
+        wave.reset();
+        assert_eq!(preserved, render(wave, step, 100000));
+    }
+
+    #[test]
+    fn preserve_formants_small_steps() {
+        // A zero step holds the current output, without advancing.
+        let mut wave = Wave::sine(150.0)
+            .pitched_with(2.0, PitchMode::PreserveFormants);
+        assert_eq!(Some(0.0), wave.next(0.0));
+        let step = 1.0 / 16000.0;
+        let value = wave.next(step).unwrap();
+        assert_eq!(Some(value), wave.next(0.0));
+        // Zero steps don't change the rest of the output.
+        wave.reset();
+        let expected = render(wave.clone(), step, 100);
+        let samples: Vec<f32> = (0..100)
+            .map(|_| {
+                wave.next(0.0);
+                wave.next(step).unwrap()
+            })
+            .collect();
+        assert_eq!(expected, samples);
+        // Very small steps don't make pitch estimation prohibitively slow.
+        let wave = Wave::sine(150.0)
+            .pitched_with(2.0, PitchMode::PreserveFormants);
+        assert_eq!(100, render(wave, 1e-8, 100).len());
+    }
+}
+
+// ========================================================================= //
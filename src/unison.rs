@@ -0,0 +1,241 @@
+//! Unison oscillators, which stack several detuned copies of an oscillator.
+
+use std::f32::consts::PI;
+use super::{Rng, Sample, Wave, WaveGen};
+
+// ========================================================================= //
+
+const UNISON_INIT_SEED: u64 = 192837465564738291;
+
+// ========================================================================= //
+
+impl Wave {
+    /// Creates a unison wave by stacking `voices` copies of an oscillator,
+    /// spread evenly in pitch across a total range of `detune` cents around
+    /// the frequency given by the `freq` waveform (which may be a constant).
+    /// For each voice, `osc_fn` is called with that voice's frequency
+    /// waveform and a starting phase (from 0 to 1) and should return the
+    /// oscillator to use for that voice, e.g. `|freq, phase|
+    /// Wave::sine(freq).with_initial_phase(phase)`.  The starting phases are
+    /// chosen pseudo-randomly (but deterministically) so that the voices
+    /// don't all start out in phase with each other; giving the unison wave
+    /// an initial phase (see `with_initial_phase`) shifts all of the voices'
+    /// starting phases by that amount.  The voices are averaged together, and
+    /// the wave finishes once all of them have finished.
+    pub fn unison<F, O>(freq: F, voices: usize, detune: f32, osc_fn: O)
+                        -> Wave
+        where F: Into<Wave>,
+              O: FnMut(Wave, f32) -> Wave
+    {
+        let (wave, _) = unison_voices(freq.into(),
+                                      voices,
+                                      detune,
+                                      0.0,
+                                      osc_fn,
+                                      false);
+        wave
+    }
+
+    /// Like `unison`, but spreads the voices across the stereo field,
+    /// returning a pair of (left, right) waves.  A `spread` of 0 places all
+    /// voices in the center, while a spread of 1 pans the lowest- and
+    /// highest-pitched voices all the way to the left and right,
+    /// respectively, with the others evenly in between.  The `osc_fn` is
+    /// called twice for each voice (once for each channel), with the same
+    /// arguments each time, and should return the same oscillator both times.
+    pub fn unison_stereo<F, O>(freq: F, voices: usize, detune: f32,
+                               spread: f32, osc_fn: O)
+                               -> (Wave, Wave)
+        where F: Into<Wave>,
+              O: FnMut(Wave, f32) -> Wave
+    {
+        let (left, right) = unison_voices(freq.into(),
+                                          voices,
+                                          detune,
+                                          spread,
+                                          osc_fn,
+                                          true);
+        (left, right.unwrap())
+    }
+
+    /// Creates a "supersaw" wave: a unison stack of `voices` sawtooth waves,
+    /// spread evenly in pitch across a total range of `detune` cents around
+    /// the frequency given by the `freq` waveform (which may be a constant),
+    /// with pseudo-random (but deterministic) starting phases.
+    pub fn supersaw<F: Into<Wave>>(freq: F, voices: usize, detune: f32)
+                                   -> Wave {
+        Wave::unison(freq, voices, detune, |freq, phase| {
+            Wave::saw(freq).with_initial_phase(phase)
+        })
+    }
+}
+
+/// Builds the mono (or left and right) mix for a unison wave.
+fn unison_voices<O>(freq: Wave, voices: usize, detune: f32, spread: f32,
+                    mut osc_fn: O, stereo: bool)
+                    -> (Wave, Option<Wave>)
+    where O: FnMut(Wave, f32) -> Wave
+{
+    let voices = voices.max(1);
+    let scale = 1.0 / voices as f32;
+    let mut rng = Rng::new(UNISON_INIT_SEED);
+    let mut left = Vec::with_capacity(voices);
+    let mut right = Vec::with_capacity(if stereo { voices } else { 0 });
+    for index in 0..voices {
+        // Position of this voice within the stack, from -1 to 1.
+        let position = if voices > 1 {
+            2.0 * index as f32 / (voices - 1) as f32 - 1.0
+        } else {
+            0.0
+        };
+        let ratio = 2f32.powf(0.5 * position * detune / 1200.0);
+        let phase = rng.next_unit();
+        let voice_freq = freq.clone() * ratio;
+        if stereo {
+            // Use a constant-power pan law.
+            let angle = 0.25 * PI * (spread * position + 1.0);
+            left.push(UnisonVoice {
+                wave: osc_fn(voice_freq.clone(), phase),
+                gain: scale * angle.cos(),
+                phase,
+            });
+            right.push(UnisonVoice {
+                wave: osc_fn(voice_freq, phase),
+                gain: scale * angle.sin(),
+                phase,
+            });
+        } else {
+            left.push(UnisonVoice {
+                wave: osc_fn(voice_freq, phase),
+                gain: scale,
+                phase,
+            });
+        }
+    }
+    let left = Wave::new(Box::new(UnisonMix { voices: left }));
+    let right = if stereo {
+        Some(Wave::new(Box::new(UnisonMix { voices: right })))
+    } else {
+        None
+    };
+    (left, right)
+}
+
+// ========================================================================= //
+
+/// One voice of a unison wave.
+#[derive(Clone)]
+struct UnisonVoice {
+    wave: Wave,
+    gain: f32,
+    /// The starting phase that was chosen for this voice.
+    phase: f32,
+}
+
+/// A weighted mix of unison voices.
+#[derive(Clone)]
+struct UnisonMix {
+    voices: Vec<UnisonVoice>,
+}
+
+impl WaveGen for UnisonMix {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        let mut total = None;
+        for voice in self.voices.iter_mut() {
+            if let Some(value) = voice.wave.next(step) {
+                total = Some(total.unwrap_or(0.0) + voice.gain * value);
+            }
+        }
+        total
+    }
+
+    fn reset(&mut self) {
+        for voice in self.voices.iter_mut() {
+            voice.wave.reset();
+        }
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+
+    fn set_initial_phase(&mut self, phase: f32) {
+        for voice in self.voices.iter_mut() {
+            voice.wave.set_initial_phase(voice.phase + phase);
+        }
+    }
+
+    fn set_phase_offset(&mut self, offset: Wave) {
+        for voice in self.voices.iter_mut() {
+            voice.wave.set_phase_offset(offset.clone());
+        }
+    }
+}
+
+// ========================================================================= //
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Wave, WaveGen};
+
+    #[test]
+    fn unison_single_voice() {
+        let step = 1.0 / 100.0;
+        let mut wave = Wave::unison(7.0, 1, 50.0, |freq, _| Wave::sine(freq));
+        let mut sine = Wave::sine(7.0);
+        for _ in 0..100 {
+            assert_approx!(sine.next(step).unwrap(), wave.next(step).unwrap());
+        }
+    }
+
+    #[test]
+    fn unison_voices() {
+        let mut calls = Vec::new();
+        Wave::unison(100.0, 3, 100.0, |mut freq, phase| {
+            calls.push((freq.next(1.0).unwrap(), phase));
+            Wave::from(0.0)
+        });
+        assert_eq!(3, calls.len());
+        assert_approx!(100.0 * 2f32.powf(-50.0 / 1200.0), calls[0].0);
+        assert_approx!(100.0, calls[1].0);
+        assert_approx!(100.0 * 2f32.powf(50.0 / 1200.0), calls[2].0);
+        assert!(calls.iter().all(|&(_, phase)| (0.0..1.0).contains(&phase)));
+        assert!(calls[0].1 != calls[1].1 && calls[1].1 != calls[2].1);
+        // The phases are deterministic.
+        let mut phases = Vec::new();
+        Wave::unison(200.0, 3, 0.0, |_, phase| {
+            phases.push(phase);
+            Wave::from(0.0)
+        });
+        let expected: Vec<f32> =
+            calls.iter().map(|&(_, phase)| phase).collect();
+        assert_eq!(expected, phases);
+    }
+
+    #[test]
+    fn unison_stereo() {
+        let mut values = vec![1.0, 1.0, 2.0, 2.0].into_iter();
+        let (mut left, mut right) =
+            Wave::unison_stereo(100.0, 2, 10.0, 1.0, |_, _| {
+                Wave::from(values.next().unwrap()).take(1.0)
+            });
+        assert_approx!(0.5, left.next(1.0).unwrap());
+        assert_approx!(1.0, right.next(1.0).unwrap());
+        assert_eq!(None, left.next(1.0));
+        assert_eq!(None, right.next(1.0));
+    }
+
+    #[test]
+    fn supersaw() {
+        let step = 1.0 / 44100.0;
+        let mut wave = Wave::supersaw(220.0, 7, 30.0);
+        let samples: Vec<f32> = (0..1000)
+            .map(|_| wave.next(step).unwrap())
+            .collect();
+        assert!(samples.iter().all(|sample| sample.abs() <= 1.0));
+        wave.reset();
+        for &sample in samples.iter() {
+            assert_eq!(sample, wave.next(step).unwrap());
+        }
+    }
+}
+
+// ========================================================================= //
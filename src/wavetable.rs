@@ -0,0 +1,299 @@
+//! Wavetable oscillators.
+
+use std::f64::consts::PI;
+use std::sync::Arc;
+use super::{Phase, Sample, Wave, WaveGen};
+
+// ========================================================================= //
+
+/// A set of single-cycle waveform tables, for use with
+/// [`Wave::wavetable`](struct.Wave.html#method.wavetable).
+///
+/// When a `Wavetable` is created, each table is analyzed to produce a series
+/// of band-limited versions of it (each with half as many harmonics as the
+/// last), so that the oscillator can avoid aliasing at high pitches.  This
+/// can be expensive for large tables, but cloning a `Wavetable` is cheap, so
+/// one `Wavetable` can be shared by many oscillators.
+#[derive(Clone)]
+pub struct Wavetable {
+    tables: Arc<Vec<MipTable>>,
+}
+
+impl Wavetable {
+    /// Creates a new set of wavetables from the given single-cycle tables of
+    /// sample values.  The tables need not all be the same length, but empty
+    /// tables are ignored.
+    pub fn new<I>(tables: I) -> Wavetable
+        where I: IntoIterator,
+              I::Item: Into<Vec<Sample>>
+    {
+        let tables = tables.into_iter()
+            .map(Into::into)
+            .filter(|table: &Vec<Sample>| !table.is_empty())
+            .map(MipTable::new)
+            .collect();
+        Wavetable { tables: Arc::new(tables) }
+    }
+
+    /// Returns the number of tables.
+    pub fn len(&self) -> usize { self.tables.len() }
+
+    /// Returns true if there are no tables.
+    pub fn is_empty(&self) -> bool { self.tables.is_empty() }
+}
+
+impl<T: Into<Vec<Sample>>> From<Vec<T>> for Wavetable {
+    fn from(tables: Vec<T>) -> Wavetable { Wavetable::new(tables) }
+}
+
+// ========================================================================= //
+
+impl Wave {
+    /// Creates a wavetable oscillator whose frequency over time is controlled
+    /// by the `freq` waveform, and which crossfades between the given tables
+    /// according to the `position` waveform (either or both of which may be
+    /// constants).  The input frequency values are measured in hertz (cycles
+    /// per second); a position of 0 plays the first table, a position of 1
+    /// plays the last table, and positions in between blend the two nearest
+    /// tables.  Each table is played back with linear interpolation, using a
+    /// band-limited version of the table suited to the current frequency and
+    /// step size.  If there are no tables, the wave is silent.
+    pub fn wavetable<T, F, P>(tables: T, freq: F, position: P) -> Wave
+        where T: Into<Wavetable>,
+              F: Into<Wave>,
+              P: Into<Wave>
+    {
+        Wave::new(Box::new(WavetableWave::new(tables.into(),
+                                              freq.into(),
+                                              position.into())))
+    }
+}
+
+// ========================================================================= //
+
+/// One single-cycle table, along with band-limited versions of it.
+struct MipTable {
+    /// Pairs of (maximum harmonic, samples), in order of decreasing maximum
+    /// harmonic.  The first level is always the original table.
+    levels: Vec<(usize, Vec<Sample>)>,
+}
+
+impl MipTable {
+    fn new(table: Vec<Sample>) -> MipTable {
+        let len = table.len();
+        let mut max_harmonic = len / 2;
+        let mut levels = Vec::new();
+        if max_harmonic > 1 {
+            let spectrum = Spectrum::new(&table);
+            levels.push((max_harmonic, table));
+            while max_harmonic > 1 {
+                max_harmonic /= 2;
+                levels.push((max_harmonic, spectrum.synthesize(max_harmonic)));
+            }
+        } else {
+            levels.push((max_harmonic, table));
+        }
+        MipTable { levels }
+    }
+
+    /// Reads the table at the given phase (from 0 to 1), using the most
+    /// detailed level whose harmonics all fall below `max_harmonic`.
+    fn read(&self, phase: f32, max_harmonic: f32) -> Sample {
+        let samples = self.levels
+            .iter()
+            .find(|&&(harmonic, _)| harmonic as f32 <= max_harmonic)
+            .unwrap_or(&self.levels[self.levels.len() - 1])
+            .1
+            .as_slice();
+        let position = phase * samples.len() as f32;
+        let index = (position as usize) % samples.len();
+        let next = (index + 1) % samples.len();
+        let frac = position - position.floor();
+        samples[index] + (samples[next] - samples[index]) * frac
+    }
+}
+
+/// The discrete Fourier transform of a table.
+struct Spectrum {
+    cos_terms: Vec<f64>,
+    sin_terms: Vec<f64>,
+    cos_table: Vec<f64>,
+    sin_table: Vec<f64>,
+}
+
+impl Spectrum {
+    fn new(table: &[Sample]) -> Spectrum {
+        let len = table.len();
+        let cos_table: Vec<f64> = (0..len)
+            .map(|n| (2.0 * PI * n as f64 / len as f64).cos())
+            .collect();
+        let sin_table: Vec<f64> = (0..len)
+            .map(|n| (2.0 * PI * n as f64 / len as f64).sin())
+            .collect();
+        let mut cos_terms = Vec::with_capacity(len / 2 + 1);
+        let mut sin_terms = Vec::with_capacity(len / 2 + 1);
+        for harmonic in 0..(len / 2 + 1) {
+            let mut re = 0.0;
+            let mut im = 0.0;
+            for (n, &sample) in table.iter().enumerate() {
+                let index = (harmonic * n) % len;
+                re += sample as f64 * cos_table[index];
+                im += sample as f64 * sin_table[index];
+            }
+            cos_terms.push(re);
+            sin_terms.push(im);
+        }
+        Spectrum {
+            cos_terms,
+            sin_terms,
+            cos_table,
+            sin_table,
+        }
+    }
+
+    /// Resynthesizes the table, keeping only harmonics up to `max_harmonic`.
+    fn synthesize(&self, max_harmonic: usize) -> Vec<Sample> {
+        let len = self.cos_table.len();
+        (0..len)
+            .map(|n| {
+                let mut sum = self.cos_terms[0];
+                for harmonic in 1..(max_harmonic + 1) {
+                    // The Nyquist harmonic of an even-length table has no
+                    // mirror image, so it is only counted once.
+                    let weight = if 2 * harmonic == len { 1.0 } else { 2.0 };
+                    let index = (harmonic * n) % len;
+                    sum += weight *
+                           (self.cos_terms[harmonic] * self.cos_table[index] +
+                            self.sin_terms[harmonic] * self.sin_table[index]);
+                }
+                (sum / len as f64) as Sample
+            })
+            .collect()
+    }
+}
+
+// ========================================================================= //
+
+/// A variable-frequency wavetable oscillator.
+#[derive(Clone)]
+struct WavetableWave {
+    tables: Wavetable,
+    freq: Wave,
+    position: Wave,
+    phase: Phase,
+}
+
+impl WavetableWave {
+    fn new(tables: Wavetable, freq: Wave, position: Wave) -> WavetableWave {
+        WavetableWave {
+            tables,
+            freq,
+            position,
+            phase: Phase::default(),
+        }
+    }
+}
+
+impl WaveGen for WavetableWave {
+    fn next(&mut self, step: f32) -> Option<Sample> {
+        let freq = self.freq.next(step)?;
+        let position = self.position.next(step)?;
+        let phase = self.phase.advance(freq, step)?;
+        let tables = &self.tables.tables;
+        if tables.is_empty() {
+            return Some(0.0);
+        }
+        // The highest harmonic that can be played without aliasing.
+        let max_harmonic = 0.5 / (step * freq).abs();
+        let position = position.clamp(0.0, 1.0) * (tables.len() - 1) as f32;
+        let index = position as usize;
+        let value = tables[index].read(phase, max_harmonic);
+        if index + 1 < tables.len() {
+            let frac = position - index as f32;
+            let next_value = tables[index + 1].read(phase, max_harmonic);
+            Some(value + (next_value - value) * frac)
+        } else {
+            Some(value)
+        }
+    }
+
+    fn reset(&mut self) {
+        self.freq.reset();
+        self.position.reset();
+        self.phase.reset();
+    }
+
+    fn as_wave(&self) -> Wave { Wave::new(Box::new(self.clone())) }
+
+    fn phase_wrap(&self) -> Option<f32> { self.phase.wrap }
+
+    fn sync(&mut self, elapsed: f32) { self.phase.sync(elapsed); }
+
+    fn set_initial_phase(&mut self, phase: f32) {
+        self.phase.set_initial(phase);
+    }
+
+    fn set_phase_offset(&mut self, offset: Wave) {
+        self.phase.offset = Some(offset);
+    }
+}
+
+// ========================================================================= //
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+    use super::super::{Wave, WaveGen};
+    use super::Wavetable;
+
+    fn harmonic_table(harmonic: f32) -> Vec<f32> {
+        (0..64)
+            .map(|n| (2.0 * PI * harmonic * n as f32 / 64.0).sin())
+            .collect()
+    }
+
+    #[test]
+    fn wavetable_sine() {
+        let step = 1.0 / 22050.0;
+        let mut wave = Wave::wavetable(vec![harmonic_table(1.0)],
+                                       2756.25,
+                                       0.0);
+        let mut sine = Wave::sine(2756.25);
+        for _ in 0..20 {
+            assert_approx!(sine.next(step).unwrap(), wave.next(step).unwrap());
+        }
+    }
+
+    #[test]
+    fn wavetable_morph() {
+        let tables = Wavetable::new(vec![vec![1.0, 1.0], vec![-1.0, -1.0]]);
+        let position = Wave::slide(0.0, 0.25, 0.0);
+        let mut wave = Wave::wavetable(tables, 1.0, position);
+        assert_approx!(1.0, wave.next(1.0).unwrap());
+        assert_approx!(0.5, wave.next(1.0).unwrap());
+        assert_approx!(0.0, wave.next(1.0).unwrap());
+        assert_approx!(-0.5, wave.next(1.0).unwrap());
+        assert_approx!(-1.0, wave.next(1.0).unwrap());
+        assert_approx!(-1.0, wave.next(1.0).unwrap());
+    }
+
+    #[test]
+    fn wavetable_band_limited() {
+        let step = 1.0 / 100.0;
+        let tables = Wavetable::new(vec![harmonic_table(3.0)]);
+        // At 10 Hz, the third harmonic is below the Nyquist frequency of 50
+        // Hz, so it can be played.
+        let mut wave = Wave::wavetable(tables.clone(), 10.0, 0.0);
+        let peak = (0..10)
+            .map(|_| wave.next(step).unwrap().abs())
+            .fold(0.0, f32::max);
+        assert!(peak > 0.9);
+        // At 20 Hz, the third harmonic would alias, so it gets filtered out.
+        let mut wave = Wave::wavetable(tables, 20.0, 0.0);
+        for _ in 0..10 {
+            assert_approx!(0.0, wave.next(step).unwrap());
+        }
+    }
+}
+
+// ========================================================================= //